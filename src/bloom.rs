@@ -0,0 +1,327 @@
+use crate::texture;
+
+/// The default for Bloom::intensity
+pub const DEFAULT_BLOOM_INTENSITY: f32 = 0.8;
+/// The default for Bloom::threshold
+pub const DEFAULT_BLOOM_THRESHOLD: f32 = 0.7;
+
+// Mirrors the BloomParams uniform in bloom.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomParams {
+    direction: [f32; 2],
+    threshold: f32,
+    intensity: f32,
+}
+
+/// The passes bloom runs, in order. Each has its own params and reads from its own texture
+#[derive(Debug, Copy, Clone)]
+enum Step {
+    /// Picks out the bright parts of the scene into the first half size texture
+    Bright,
+    /// Blurs the first half size texture sideways into the second
+    Horizontal,
+    /// Blurs the second half size texture upwards back into the first
+    Vertical,
+    /// Adds the first half size texture over the scene
+    Composite,
+}
+
+const STEPS: [Step; 4] = [
+    Step::Bright,
+    Step::Horizontal,
+    Step::Vertical,
+    Step::Composite,
+];
+
+/// A glow around the brightest parts of the scene, so stars look like they give off light.
+/// The scene is drawn into an offscreen texture, whose bright parts are blurred at half
+/// size and added back over it on the way to the screen
+pub struct Bloom {
+    /// How strongly the glow is added over the scene. 0 turns bloom off entirely
+    pub intensity: f32,
+    /// How bright a pixel has to be, from 0 to 1, before it starts to glow
+    pub threshold: f32,
+    /// Where the scene is drawn when bloom is on, at the size of the surface
+    pub scene: texture::Texture,
+    /// The width and height of `scene`
+    scene_size: (u32, u32),
+    /// The two half size textures the blur ping-pongs between
+    half: [texture::Texture; 2],
+    bind_group_layout: wgpu::BindGroupLayout,
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// The params of each step, in the order of STEPS
+    params_buffers: Vec<wgpu::Buffer>,
+    /// What each step reads, in the order of STEPS. Rebuilt whenever the textures are
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Bloom {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom_bind_group_layout"),
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(3),
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+        let pipeline = |label, entry_point| {
+            create_bloom_pipeline(device, &layout, &shader, config.format, label, entry_point)
+        };
+        let bright_pipeline = pipeline("Bloom Bright Pipeline", "fs_bright");
+        let blur_pipeline = pipeline("Bloom Blur Pipeline", "fs_blur");
+        let composite_pipeline = pipeline("Bloom Composite Pipeline", "fs_composite");
+
+        let params_buffers = STEPS
+            .iter()
+            .map(|step| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Bloom {:?} Params Buffer", step)),
+                    size: std::mem::size_of::<BloomParams>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let (scene, half) = create_targets(device, config);
+        let bind_groups =
+            create_bind_groups(device, &bind_group_layout, &params_buffers, &scene, &half);
+
+        Self {
+            intensity: DEFAULT_BLOOM_INTENSITY,
+            threshold: DEFAULT_BLOOM_THRESHOLD,
+            scene,
+            scene_size: (config.width, config.height),
+            half,
+            bind_group_layout,
+            bright_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            params_buffers,
+            bind_groups,
+        }
+    }
+
+    /// Whether there's any glow to add, so the scene needs drawing offscreen first
+    pub fn enabled(&self) -> bool {
+        self.intensity > 0.0
+    }
+
+    /// Rebuilds the textures to match a resized surface
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (scene, half) = create_targets(device, config);
+        self.bind_groups = create_bind_groups(
+            device,
+            &self.bind_group_layout,
+            &self.params_buffers,
+            &scene,
+            &half,
+        );
+        self.scene = scene;
+        self.scene_size = (config.width, config.height);
+        self.half = half;
+    }
+
+    /// Records the passes that take the scene already drawn into `self.scene`
+    /// and draw it with its glow into `view`
+    pub fn apply(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let (width, height) = half_size(self.scene_size);
+        for (i, step) in STEPS.iter().enumerate() {
+            let direction = match step {
+                Step::Horizontal => [1.0 / width as f32, 0.0],
+                Step::Vertical => [0.0, 1.0 / height as f32],
+                Step::Bright | Step::Composite => [0.0, 0.0],
+            };
+            let params = BloomParams {
+                direction,
+                threshold: self.threshold,
+                intensity: self.intensity,
+            };
+            queue.write_buffer(&self.params_buffers[i], 0, bytemuck::cast_slice(&[params]));
+
+            let (target, pipeline) = match step {
+                Step::Bright => (&self.half[0].view, &self.bright_pipeline),
+                Step::Horizontal => (&self.half[1].view, &self.blur_pipeline),
+                Step::Vertical => (&self.half[0].view, &self.blur_pipeline),
+                Step::Composite => (view, &self.composite_pipeline),
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bloom Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Every pixel gets drawn over, so there's nothing worth loading
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[i], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// The size of the blur textures for a scene of `size`. Blurring at half size is four times
+/// cheaper and spreads the glow twice as far
+fn half_size(size: (u32, u32)) -> (u32, u32) {
+    ((size.0 / 2).max(1), (size.1 / 2).max(1))
+}
+
+/// The full size texture the scene is drawn into and the two half size blur textures
+fn create_targets(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> (texture::Texture, [texture::Texture; 2]) {
+    let scene = texture::Texture::create_render_target(
+        device,
+        config.width,
+        config.height,
+        config.format,
+        "Bloom Scene Texture",
+    );
+    let (width, height) = half_size((config.width, config.height));
+    let half = [
+        texture::Texture::create_render_target(device, width, height, config.format, "Bloom A"),
+        texture::Texture::create_render_target(device, width, height, config.format, "Bloom B"),
+    ];
+    (scene, half)
+}
+
+/// What each step reads, in the order of STEPS. A pass can't read the texture it draws to,
+/// so the second texture slot, only used when compositing, repeats the first elsewhere
+fn create_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    params_buffers: &[wgpu::Buffer],
+    scene: &texture::Texture,
+    half: &[texture::Texture; 2],
+) -> Vec<wgpu::BindGroup> {
+    STEPS
+        .iter()
+        .zip(params_buffers)
+        .map(|(step, params_buffer)| {
+            let (source, bloom) = match step {
+                Step::Bright => (scene, scene),
+                Step::Horizontal => (&half[0], &half[0]),
+                Step::Vertical => (&half[1], &half[1]),
+                Step::Composite => (scene, &half[0]),
+            };
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("bloom_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&scene.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&bloom.view),
+                    },
+                ],
+            })
+        })
+        .collect()
+}
+
+fn create_bloom_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+    entry_point: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
@@ -0,0 +1,77 @@
+// Makes the brightest parts of the scene bleed light into their surroundings.
+// Every pass draws one screen-covering triangle and reads the output of the pass before it
+
+[[block]]
+struct BloomParams {
+    // One texel of the source along the blur direction, unused outside the blur passes
+    direction: vec2<f32>;
+    // How bright a pixel has to be, from 0 to 1, before it starts to glow
+    threshold: f32;
+    // How strongly the glow is added back over the scene
+    intensity: f32;
+};
+
+[[group(0), binding(0)]]
+var t_source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_source: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: BloomParams;
+// The blurred glow, only read when compositing
+[[group(0), binding(3)]]
+var t_bloom: texture_2d<f32>;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// A single triangle big enough to cover the whole screen, so no vertex buffer is needed
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index & 2u) * 2 - 1);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    // Texture coordinates start at the top of the screen
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+// Keeps only what's brighter than the threshold, fading in above it rather than cutting off
+[[stage(fragment)]]
+fn fs_bright(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv).rgb;
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let weight = clamp((luminance - params.threshold) / max(1.0 - params.threshold, 0.0001), 0.0, 1.0);
+    return vec4<f32>(color * weight, 1.0);
+}
+
+// One direction of a separable 9 tap Gaussian blur
+[[stage(fragment)]]
+fn fs_blur(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var weights: array<f32, 5> = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = textureSample(t_source, s_source, in.uv).rgb * weights[0];
+    var i: i32 = 1;
+    loop {
+        if (i >= 5) {
+            break;
+        }
+        let offset = params.direction * f32(i);
+        color = color + textureSample(t_source, s_source, in.uv + offset).rgb * weights[i];
+        color = color + textureSample(t_source, s_source, in.uv - offset).rgb * weights[i];
+
+        continuing {
+            i = i + 1;
+        }
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// Adds the glow back over the untouched scene
+[[stage(fragment)]]
+fn fs_composite(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let scene = textureSample(t_source, s_source, in.uv).rgb;
+    let bloom = textureSample(t_bloom, s_source, in.uv).rgb;
+    return vec4<f32>(scene + bloom * params.intensity, 1.0);
+}
@@ -1,5 +1,26 @@
 use winit::event::*;
 
+/// The vertical field of view of a perspective camera, in degrees
+pub const DEFAULT_FOVY: f32 = 45.0;
+/// How far from the eye the near clipping plane is unless set otherwise
+pub const DEFAULT_ZNEAR: f32 = 0.1;
+/// How far from the eye the far clipping plane is unless set otherwise
+pub const DEFAULT_ZFAR: f32 = 100.0;
+/// How much room fit_depth_range leaves around the nearest and farthest things in view,
+/// as a fraction of their distance
+const DEPTH_MARGIN: f32 = 0.1;
+
+/// How the camera flattens the scene onto the screen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Distant things look smaller, like a real camera. `fovy` is the vertical
+    /// field of view in degrees
+    Perspective { fovy: f32 },
+    /// Things look the same size however far away they are, which makes orbits easy to
+    /// compare. `height` is how much of the world fits on screen vertically
+    Orthographic { height: f32 },
+}
+
 /// Camera struct to hold our camera's values
 pub struct Camera {
     /// Where the camera is looking "from"
@@ -10,11 +31,17 @@ pub struct Camera {
     pub up: cgmath::Vector3<f32>,
     /// The camera's aspect ratio
     pub aspect: f32,
-    /// The camera's field of view
-    pub fovy: f32,
-    /// Znear and Zfar describe our clipping distance
+    /// Whether the camera has perspective, along with its field of view or size
+    pub projection: Projection,
+    /// Znear and Zfar describe our clipping distance, see set_depth_range
     pub znear: f32,
     pub zfar: f32,
+    /// When on, the renderer fits znear and zfar around the scene every frame,
+    /// see fit_depth_range
+    pub auto_depth_range: bool,
+    /// Maps the near plane to a depth of 1 and the far plane to 0 for better depth precision.
+    /// Pipelines and depth clears need to agree with this, so it's read when they are built.
+    pub reversed_z: bool,
 }
 
 /// Since wgpu and cgmath are built for different cooridinate systems,
@@ -27,37 +54,288 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+/// The same conversion as OPENGL_TO_WGPU_MATRIX, but flips depth so that
+/// the near plane ends up at 1 and the far plane at 0.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_REVERSED_Z_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
 impl Camera {
-    /// Defines a new camera
-    pub fn new(width: f32, height: f32) -> Self {
+    /// Defines a new camera matching the size of the surface it draws to
+    pub fn new(config: &wgpu::SurfaceConfiguration) -> Self {
         // Positioning the camera above and behind the world space origin
         let eye = (0.0, 1.0, 2.0).into();
         // Setting the camera to look at the origin
         let target = (0.0, 0.0, 0.0).into();
         // Determining our up direction
         let up = cgmath::Vector3::unit_y();
-        let aspect = width as f32 / height as f32;
-        let fovy = 45.0;
-        let znear = 0.1;
-        let zfar = 100.0;
+        let aspect = config.width as f32 / config.height as f32;
+        let projection = Projection::Perspective { fovy: DEFAULT_FOVY };
+        let znear = DEFAULT_ZNEAR;
+        let zfar = DEFAULT_ZFAR;
+        let reversed_z = cfg!(feature = "reversed-z");
 
         Self {
             eye,
             target,
             up,
             aspect,
-            fovy,
+            projection,
             znear,
             zfar,
+            auto_depth_range: true,
+            reversed_z,
+        }
+    }
+
+    /// Fits the aspect ratio to a surface of the given size.
+    /// A zero height (e.g. a minimized window) is ignored rather than producing a NaN projection
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    /// Moves the eye back along the current view direction until a sphere of `radius`
+    /// around `center` fits on screen, and looks at `center`. The far plane is pushed out
+    /// if it would cut the sphere off
+    pub fn frame(&mut self, center: cgmath::Point3<f32>, radius: f32) {
+        let distance = match &mut self.projection {
+            Projection::Perspective { fovy } => {
+                // The narrower of the two fields of view decides how far back the eye has to be
+                let half_fovy = cgmath::Rad::from(cgmath::Deg(*fovy)) * 0.5;
+                let half_fovx = cgmath::Rad((half_fovy.tan() * self.aspect).atan());
+                let half_fov = if half_fovx < half_fovy {
+                    half_fovx
+                } else {
+                    half_fovy
+                };
+                radius / half_fov.sin()
+            }
+            Projection::Orthographic { height } => {
+                // Distance doesn't change the size, as long as the eye is clear of the sphere
+                *height = 2.0 * radius * (1.0 / self.aspect).max(1.0);
+                2.0 * radius
+            }
+        };
+
+        let direction = (self.eye - self.target).normalize();
+        self.target = center;
+        self.eye = center + direction * distance;
+        self.zfar = self.zfar.max(distance + radius);
+    }
+
+    /// Sets the clipping planes, failing unless 0 < znear < zfar.
+    ///
+    /// Depth precision depends on zfar / znear rather than either on its own, so the near
+    /// plane is the one to push out: halving znear costs about as much precision as
+    /// doubling zfar. With a standard depth range nearly all of it is spent close to the
+    /// near plane, and distant bodies start z-fighting somewhere past a ratio of 1e4.
+    /// The reversed-z feature spreads it out far more evenly, see max_depth_ratio
+    pub fn set_depth_range(&mut self, znear: f32, zfar: f32) -> anyhow::Result<()> {
+        if znear.is_nan() || znear <= 0.0 {
+            anyhow::bail!(
+                "The near plane must be in front of the camera, not at {}",
+                znear
+            );
+        }
+        if zfar.is_nan() || zfar <= znear {
+            anyhow::bail!(
+                "The far plane must be beyond the near plane at {}, not at {}",
+                znear,
+                zfar
+            );
+        }
+        self.znear = znear;
+        self.zfar = zfar;
+        Ok(())
+    }
+
+    /// The largest zfar / znear fit_depth_range allows before giving up on the nearest
+    /// bodies, trading clipping them for depth precision everywhere else
+    pub fn max_depth_ratio(&self) -> f32 {
+        if self.reversed_z {
+            1e7
+        } else {
+            1e5
+        }
+    }
+
+    /// Moves the clipping planes in around what's in view: the far plane just past the
+    /// farthest corner of the box from `min` to `max`, and the near plane just short of the
+    /// nearest of `spheres`, as (center, radius), in front of the eye. The near plane is
+    /// kept within max_depth_ratio of the far one, so a body right against the eye gets
+    /// clipped rather than ruining the precision of the rest of the scene.
+    /// Nothing changes when everything is behind the eye
+    pub fn fit_depth_range<I>(&mut self, min: Point3<f32>, max: Point3<f32>, spheres: I)
+    where
+        I: IntoIterator<Item = (Point3<f32>, f32)>,
+    {
+        let forward = (self.target - self.eye).normalize();
+        // How far in front of the eye a point is, measured along the view direction like depth
+        let depth = |point: Point3<f32>| (point - self.eye).dot(forward);
+
+        let corners = (0..8).map(|corner| {
+            Point3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            )
+        });
+        let farthest = corners.map(depth).fold(f32::NEG_INFINITY, f32::max);
+        if farthest.is_nan() || farthest <= 0.0 {
+            return;
+        }
+        let zfar = farthest * (1.0 + DEPTH_MARGIN);
+
+        let nearest = spheres
+            .into_iter()
+            .map(|(center, radius)| (depth(center), radius))
+            // Only what's at least partly in front of the eye
+            .filter(|&(distance, radius)| distance + radius > 0.0)
+            .map(|(distance, radius)| distance - radius)
+            .fold(f32::INFINITY, f32::min);
+        let closest = zfar / self.max_depth_ratio();
+        let znear = if nearest.is_finite() {
+            (nearest * (1.0 - DEPTH_MARGIN)).clamp(closest, farthest)
+        } else {
+            // No bodies in front, so nothing says where the near plane should be
+            closest
+        };
+
+        // Can only fail on NaNs from a degenerate view, where keeping the old planes is best
+        let _ = self.set_depth_range(znear, zfar);
+    }
+
+    /// Switches between perspective and orthographic projection. The orthographic view
+    /// starts out showing as much as the perspective one did at the target
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective { fovy } => {
+                let distance = (self.target - self.eye).magnitude();
+                let half_fovy = cgmath::Rad::from(cgmath::Deg(fovy)) * 0.5;
+                Projection::Orthographic {
+                    height: 2.0 * distance * half_fovy.tan(),
+                }
+            }
+            Projection::Orthographic { .. } => Projection::Perspective { fovy: DEFAULT_FOVY },
+        };
+    }
+
+    /// Circles the eye around the target about the up axis by `angle`, keeping its distance
+    /// and height. Positive angles go counterclockwise seen from above
+    pub fn circle_target(&mut self, angle: cgmath::Deg<f32>) {
+        use cgmath::Rotation3;
+        let rotation = cgmath::Quaternion::from_axis_angle(self.up.normalize(), angle);
+        self.eye = self.target + cgmath::Rotation::rotate_vector(&rotation, self.eye - self.target);
+    }
+
+    /// The ray through the pixel at (`x`, `y`) on a surface `width` by `height`, as a point
+    /// on the near plane and a unit direction into the scene. Works back from the screen
+    /// with the inverse view-projection, so it suits either projection
+    pub fn screen_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> Option<(cgmath::Point3<f32>, cgmath::Vector3<f32>)> {
+        let inverse = self.build_view_projection_matrix().invert()?;
+        // Pixels count down from the top left, while clip space counts up from the center
+        let (clip_x, clip_y) = (2.0 * x / width - 1.0, 1.0 - 2.0 * y / height);
+        let (near_depth, far_depth) = if self.reversed_z {
+            (1.0, 0.0)
+        } else {
+            (0.0, 1.0)
+        };
+        let unproject = |depth: f32| {
+            let point = inverse * cgmath::Vector4::new(clip_x, clip_y, depth, 1.0);
+            cgmath::Point3::from_homogeneous(point)
+        };
+        let (near, far) = (unproject(near_depth), unproject(far_depth));
+        Some((near, (far - near).normalize()))
+    }
+
+    /// The space the camera can currently see, bounded by the edges of the screen and the
+    /// clipping planes. Like everything drawn, it's relative to render_origin
+    pub fn frustum(&self) -> crate::frustum::Frustum {
+        crate::frustum::Frustum::from_view_projection(self.build_relative_view_projection_matrix())
+    }
+
+    /// Where everything is drawn relative to, which is the eye. Positions are moved by this
+    /// on the CPU before they reach the GPU, so the GPU only ever sees small coordinates
+    /// near the camera. Otherwise at solar system distances f32 can't place the bodies
+    /// finely enough, and they jitter as the camera moves
+    pub fn render_origin(&self) -> cgmath::Vector3<f32> {
+        self.eye.to_vec()
+    }
+
+    /// The depth comparison our pipelines should use so nearer fragments win
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        if self.reversed_z {
+            wgpu::CompareFunction::Greater
+        } else {
+            wgpu::CompareFunction::Less
+        }
+    }
+
+    /// The value to clear the depth buffer to, i.e. the depth of the far plane
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reversed_z {
+            0.0
+        } else {
+            1.0
         }
     }
+
     fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         // View moves the world to be at the position and rotation of the camera
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+        self.build_projection_matrix() * view
+    }
+
+    /// The same as build_view_projection_matrix, but for points given relative to
+    /// render_origin, so the view only has to turn them
+    fn build_relative_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let direction = self.target - self.eye;
+        let view = cgmath::Matrix4::look_at_rh(
+            cgmath::Point3::origin(),
+            cgmath::Point3::from_vec(direction),
+            self.up,
+        );
+        self.build_projection_matrix() * view
+    }
+
+    fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         // Proj wraps the scene to give depth
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        // Using our conversion matrix to convert our camera cooridinates
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        let proj = match self.projection {
+            Projection::Perspective { fovy } => {
+                cgmath::perspective(cgmath::Deg(fovy), self.aspect, self.znear, self.zfar)
+            }
+            Projection::Orthographic { height } => {
+                let (half_width, half_height) = (height * self.aspect * 0.5, height * 0.5);
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        };
+        // Both projections put depth in OpenGL's -1 to 1 range, which the conversion
+        // matrix fixes up along with our camera cooridinates
+        let correction = if self.reversed_z {
+            OPENGL_TO_WGPU_REVERSED_Z_MATRIX
+        } else {
+            OPENGL_TO_WGPU_MATRIX
+        };
+        correction * proj
     }
 }
 
@@ -72,6 +350,15 @@ pub struct CameraUniform {
     // cgmath & bytemuck don't work together
     // So convert mat4 to a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
+    /// Takes points on the screen back into the world, for working out view directions
+    inv_view_proj: [[f32; 4]; 4],
+    /// Where the camera is, padded to a vec4 for the uniform's alignment. Everything is
+    /// drawn relative to the eye, so this is always the origin
+    eye: [f32; 4],
+    /// Unit vectors pointing right and up across the screen, for turning quads to face the
+    /// camera. Padded to vec4s like eye
+    right: [f32; 4],
+    up: [f32; 4],
 }
 
 use cgmath::*;
@@ -82,18 +369,48 @@ impl CameraUniform {
         Self {
             // This essentially converts a matrix into our view_proj array
             view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            eye: [0.0, 0.0, 0.0, 1.0],
+            right: [1.0, 0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0, 0.0],
         }
     }
 
-    /// Updates the camera's view projection as needed by rebuilding it
+    /// Updates the camera's view projection as needed by rebuilding it, for drawing
+    /// positions given relative to the camera's render_origin
     pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
+        let view_proj = camera.build_relative_view_projection_matrix();
+        self.view_proj = view_proj.into();
+        self.inv_view_proj = view_proj
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+            .into();
+        self.eye = cgmath::Point3::origin().to_homogeneous().into();
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        // Looking straight along the up axis leaves no way to tell which way is right
+        if right.magnitude2().is_finite() {
+            self.right = right.extend(0.0).into();
+            self.up = right.cross(forward).extend(0.0).into();
+        }
     }
 }
 
+/// How the controller moves the camera
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CameraMode {
+    /// Circles the camera around its target, for inspecting the scene
+    Orbit,
+    /// Flies the camera freely, looking around with raw mouse motion and moving
+    /// relative to where it's looking
+    Fly,
+}
+
 /// The struct that defines our keybindings and camera sensitivity
 pub struct CameraController {
-    /// The camera's speed at which it moves
+    /// Whether the camera is orbiting its target or flying freely
+    mode: CameraMode,
+    /// The camera's speed at which it moves, in units per second
     speed: f32,
     // The following are our keybinding bools
     is_up_pressed: bool,
@@ -102,12 +419,93 @@ pub struct CameraController {
     is_backward_pressed: bool,
     is_left_pressed: bool,
     is_right_pressed: bool,
+    /// How far the camera orbits, in radians, for each pixel the mouse is dragged
+    sensitivity: f32,
+    /// Horizontal and vertical mouse drag, in pixels, not yet applied to the camera
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    /// Whether the button that drags the camera around is held down
+    is_dragging: bool,
+    /// Where the cursor was on the last CursorMoved event
+    last_cursor: Option<winit::dpi::PhysicalPosition<f64>>,
+    /// Scroll, in lines, not yet applied to the camera. Positive zooms in
+    scroll: f32,
+    /// How much closer, as a fraction of the distance to the target, each line of scroll zooms
+    zoom_speed: f32,
+    /// The closest the camera can zoom to its target
+    pub min_distance: f32,
+    /// The furthest the camera can zoom from its target
+    pub max_distance: f32,
+    /// Where the flying camera is looking, around the up axis and above the horizon
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    /// Raw mouse motion not yet applied to the flying camera
+    mouse_dx: f32,
+    mouse_dy: f32,
+    /// Where the analog sticks and triggers are held, see apply_analog_input
+    analog: AnalogInput,
+}
+
+/// The camera controls held on a gamepad or similar analog device, each axis from -1 to 1.
+/// Unlike key presses these are positions rather than events, so they're applied every
+/// update for as long as they're held
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnalogInput {
+    /// Turns the camera, around its target when orbiting and in place when flying.
+    /// Positive x goes right and positive y goes up
+    pub look: Vector2<f32>,
+    /// Slides the camera and its target sideways and up without turning
+    pub pan: Vector2<f32>,
+    /// Positive zooms in and negative zooms out
+    pub zoom: f32,
+}
+
+impl Default for AnalogInput {
+    fn default() -> Self {
+        Self {
+            look: Vector2::zero(),
+            pan: Vector2::zero(),
+            zoom: 0.0,
+        }
+    }
 }
 
+/// Ignores stick positions within `dead_zone` of the center, where worn or cheap sticks
+/// rest slightly off center and would otherwise drift the camera. Positions past it are
+/// rescaled so the stick still reaches full strength at the edge
+#[cfg(feature = "gamepad")]
+pub fn apply_dead_zone(stick: Vector2<f32>, dead_zone: f32) -> Vector2<f32> {
+    let magnitude = stick.magnitude();
+    if magnitude <= dead_zone || dead_zone >= 1.0 {
+        return Vector2::zero();
+    }
+    let scaled = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    stick * (scaled / magnitude)
+}
+
+/// How many pixels of mouse movement a fully held look stick is worth each second
+const ANALOG_LOOK_SPEED: f32 = 600.0;
+/// How many lines of scroll a fully held zoom trigger is worth each second
+const ANALOG_ZOOM_SPEED: f32 = 10.0;
+
+/// How many pixels of trackpad scrolling count as one line of mouse wheel scrolling
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// The slowest and fastest the camera can be set to move, in units per second
+pub const MIN_CAMERA_SPEED: f32 = 0.1;
+pub const MAX_CAMERA_SPEED: f32 = 1000.0;
+
+/// How much each press of [ or ] changes the camera's speed by
+const SPEED_STEP: f32 = 1.5;
+
+/// How close to straight up or down the camera can look, to avoid flipping at the poles
+const MAX_PITCH: cgmath::Deg<f32> = cgmath::Deg(89.0);
+
 impl CameraController {
     /// Defines a new camera with the parameterized speed and all key presses set to false
     pub fn new(speed: f32) -> Self {
         Self {
+            mode: CameraMode::Orbit,
             speed,
             is_up_pressed: false,
             is_down_pressed: false,
@@ -115,6 +513,92 @@ impl CameraController {
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            sensitivity: 0.005,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            is_dragging: false,
+            last_cursor: None,
+            scroll: 0.0,
+            zoom_speed: 0.1,
+            min_distance: 0.5,
+            max_distance: 80.0,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            mouse_dx: 0.0,
+            mouse_dy: 0.0,
+            analog: AnalogInput::default(),
+        }
+    }
+
+    /// Sets how fast the camera moves, within MIN_CAMERA_SPEED and MAX_CAMERA_SPEED
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_CAMERA_SPEED, MAX_CAMERA_SPEED);
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches how the camera is controlled. A flying camera starts out looking
+    /// wherever the camera was already looking
+    pub fn set_mode(&mut self, mode: CameraMode, camera: &Camera) {
+        if mode == CameraMode::Fly {
+            let forward = (camera.target - camera.eye).normalize();
+            self.yaw = Rad(forward.x.atan2(forward.z));
+            self.pitch = Rad(forward.y.asin());
+        }
+        self.mode = mode;
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        self.is_dragging = false;
+    }
+
+    /// Takes in raw mouse motion, which the flying camera looks around with.
+    /// Unlike CursorMoved this keeps coming when the cursor is grabbed
+    pub fn process_device_events(&mut self, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::MouseMotion { delta } if self.mode == CameraMode::Fly => {
+                self.mouse_dx += delta.0 as f32;
+                self.mouse_dy += delta.1 as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes in where the analog controls are held. These keep being applied every update
+    /// until they change, and work the same whichever mode the camera is in
+    #[cfg(feature = "gamepad")]
+    pub fn apply_analog_input(&mut self, input: AnalogInput) {
+        self.analog = input;
+    }
+
+    /// Turns the held analog controls into the same movement the mouse and keys make,
+    /// scaled by `dt` so it doesn't depend on the frame rate
+    fn apply_analog(&mut self, camera: &mut Camera, dt: f32) {
+        let AnalogInput { look, pan, zoom } = self.analog;
+        let look = look * ANALOG_LOOK_SPEED * dt;
+        match self.mode {
+            // Dragging right spins the scene right, so circling the camera right drags left
+            CameraMode::Orbit => {
+                self.rotate_horizontal -= look.x;
+                self.rotate_vertical += look.y;
+            }
+            // Moving the mouse right turns right, but moving it down looks down
+            CameraMode::Fly => {
+                self.mouse_dx += look.x;
+                self.mouse_dy -= look.y;
+            }
+        }
+        self.scroll += zoom * ANALOG_ZOOM_SPEED * dt;
+
+        if pan != Vector2::zero() {
+            let forward = (camera.target - camera.eye).normalize();
+            let right = forward.cross(camera.up).normalize();
+            let up = right.cross(forward);
+            let offset = (right * pan.x + up * pan.y) * self.speed * dt;
+            camera.eye += offset;
+            camera.target += offset;
         }
     }
 
@@ -132,11 +616,12 @@ impl CameraController {
             } => {
                 let is_pressed = *state == ElementState::Pressed;
                 match keycode {
-                    VirtualKeyCode::Space => {
+                    // Space pauses the simulation, so up and down live next to WASD
+                    VirtualKeyCode::E => {
                         self.is_up_pressed = is_pressed;
                         true
                     }
-                    VirtualKeyCode::LShift => {
+                    VirtualKeyCode::Q => {
                         self.is_down_pressed = is_pressed;
                         true
                     }
@@ -156,15 +641,63 @@ impl CameraController {
                         self.is_right_pressed = is_pressed;
                         true
                     }
+                    // Slows down or speeds up the camera, for moving between a close orbit
+                    // and the whole system
+                    VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                        if is_pressed {
+                            let factor = if *keycode == VirtualKeyCode::RBracket {
+                                SPEED_STEP
+                            } else {
+                                1.0 / SPEED_STEP
+                            };
+                            self.set_speed(self.speed * factor);
+                            eprintln!("Camera speed: {}", self.speed);
+                        }
+                        true
+                    }
                     _ => false,
                 }
             }
+            // Holding the left button down lets the mouse orbit the camera
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.is_dragging = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(last)) = (self.is_dragging, self.last_cursor) {
+                    self.rotate_horizontal += (position.x - last.x) as f32;
+                    self.rotate_vertical += (position.y - last.y) as f32;
+                }
+                self.last_cursor = Some(*position);
+                self.is_dragging
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / PIXELS_PER_LINE,
+                };
+                true
+            }
             _ => false,
         }
     }
 
-    /// If a key is pressed, will update the camera as necessary
-    pub fn update_camera(&self, camera: &mut Camera) {
+    /// If a key is pressed, will update the camera as necessary.
+    /// `dt` is the real time in seconds since the last update, so the camera moves
+    /// `speed` units per second no matter the frame rate.
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+        self.apply_analog(camera, dt);
+        if self.mode == CameraMode::Fly {
+            self.fly(camera, dt);
+            return;
+        }
+
+        // How far the camera moves this update
+        let step = self.speed * dt;
         // Definding our forward vector
         let forward = camera.target - camera.eye;
         // Normalizing the forward vector
@@ -174,25 +707,221 @@ impl CameraController {
 
         // Prevents glitching when camera gets too close to the
         // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
+        if self.is_forward_pressed && forward_mag > step {
+            camera.eye += forward_norm * step;
         }
         if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
+            camera.eye -= forward_norm * step;
         }
 
-        let right = forward_norm.cross(camera.up);
-
         // Redo the calculations if up/down is pressed
         let forward = camera.target - camera.eye;
         let forward_mag = forward.magnitude();
 
+        // Circling the target covers `step` units of arc, which keeps the distance
+        // between the eye and target consistent
+        let mut angle = 0.0;
+        if self.is_right_pressed {
+            angle -= step / forward_mag;
+        }
+        if self.is_left_pressed {
+            angle += step / forward_mag;
+        }
+        if angle != 0.0 {
+            let rotation = Quaternion::from_axis_angle(camera.up.normalize(), Rad(angle));
+            camera.eye = camera.target - rotation.rotate_vector(forward);
+        }
+
+        self.orbit(camera);
+        self.zoom(camera);
+    }
+
+    /// Moves the camera's eye towards or away from its target by the scroll since the last update.
+    /// Each line of scroll covers the same fraction of the distance, so zooming feels the same
+    /// up close as far away
+    fn zoom(&mut self, camera: &mut Camera) {
+        if self.scroll == 0.0 {
+            return;
+        }
+
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude();
+        let new_distance = (distance * (-self.scroll * self.zoom_speed).exp())
+            .clamp(self.min_distance, self.max_distance);
+        self.scroll = 0.0;
+
+        // Moving closer doesn't make anything bigger without perspective, so shrink the view too
+        if let Projection::Orthographic { height } = &mut camera.projection {
+            *height *= new_distance / distance;
+        }
+        camera.eye = camera.target + offset * (new_distance / distance);
+    }
+
+    /// Rotates the camera's eye around its target by the mouse drag since the last update,
+    /// keeping the same distance from the target
+    fn orbit(&mut self, camera: &mut Camera) {
+        if self.rotate_horizontal == 0.0 && self.rotate_vertical == 0.0 {
+            return;
+        }
+
+        // Working out where on the sphere around the target the eye currently is
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude();
+        let yaw = Rad(offset.x.atan2(offset.z));
+        let pitch = Rad((offset.y / distance).asin());
+
+        // Dragging right spins the scene right, dragging up tilts it up
+        let yaw = yaw - Rad(self.rotate_horizontal * self.sensitivity);
+        let max_pitch: Rad<f32> = MAX_PITCH.into();
+        let pitch =
+            Rad((pitch.0 + self.rotate_vertical * self.sensitivity)
+                .clamp(-max_pitch.0, max_pitch.0));
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+
+        camera.eye = camera.target
+            + Vector3::new(
+                pitch.cos() * yaw.sin(),
+                pitch.sin(),
+                pitch.cos() * yaw.cos(),
+            ) * distance;
+    }
+
+    /// Turns the flying camera by the mouse motion since the last update, then moves it
+    /// with the keys relative to where it's looking. The target is kept the same distance
+    /// in front of the eye, so orbiting picks up from wherever the camera ends up
+    fn fly(&mut self, camera: &mut Camera, dt: f32) {
+        // Moving the mouse right turns right, moving it down looks down
+        let max_pitch: Rad<f32> = MAX_PITCH.into();
+        self.yaw -= Rad(self.mouse_dx * self.sensitivity);
+        self.pitch =
+            Rad((self.pitch.0 - self.mouse_dy * self.sensitivity).clamp(-max_pitch.0, max_pitch.0));
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        // Orbiting and zooming don't apply while flying
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.scroll = 0.0;
+
+        let forward = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        let up = camera.up.normalize();
+        let right = forward.cross(up).normalize();
+
+        let step = self.speed * dt;
+        let mut movement = Vector3::zero();
+        if self.is_forward_pressed {
+            movement += forward;
+        }
+        if self.is_backward_pressed {
+            movement -= forward;
+        }
         if self.is_right_pressed {
-            // Ensures the distance between the eye and target is consistent
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+            movement += right;
         }
         if self.is_left_pressed {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+            movement -= right;
+        }
+        if self.is_up_pressed {
+            movement += up;
+        }
+        if self.is_down_pressed {
+            movement -= up;
         }
+        if movement != Vector3::zero() {
+            camera.eye += movement.normalize() * step;
+        }
+
+        let distance = (camera.target - camera.eye)
+            .magnitude()
+            .max(self.min_distance);
+        camera.target = camera.eye + forward * distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A camera on an 800x600 surface looking down -z from the origin
+    fn camera() -> Camera {
+        let mut camera = Camera::new(&wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 800,
+            height: 600,
+            present_mode: wgpu::PresentMode::Fifo,
+        });
+        camera.eye = Point3::origin();
+        camera.target = Point3::new(0.0, 0.0, -1.0);
+        camera
+    }
+
+    /// The depth a point `distance` in front of `camera` ends up at
+    fn depth(camera: &Camera, distance: f32) -> f32 {
+        let clip = camera.build_view_projection_matrix() * Vector4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reversed_z_puts_farther_things_at_smaller_depths() {
+        let mut camera = camera();
+        camera.reversed_z = true;
+        assert!(depth(&camera, 50.0) < depth(&camera, 1.0));
+        // The near plane is at 1 and the far plane at 0, which the depth buffer clears to
+        assert!((depth(&camera, camera.znear) - 1.0).abs() < 1e-5);
+        assert!(depth(&camera, camera.zfar).abs() < 1e-5);
+        assert_eq!(camera.depth_clear_value(), 0.0);
+        assert_eq!(camera.depth_compare(), wgpu::CompareFunction::Greater);
+
+        camera.reversed_z = false;
+        assert!(depth(&camera, 50.0) > depth(&camera, 1.0));
+        assert_eq!(camera.depth_clear_value(), 1.0);
+    }
+
+    /// Where the eye ends up after updating a controller set up by `press` once per entry
+    /// of `dts`
+    fn eye_after(press: fn(&mut CameraController), dts: &[f32]) -> Point3<f32> {
+        let mut controller = CameraController::new(4.0);
+        press(&mut controller);
+        let mut camera = camera();
+        camera.target = Point3::new(0.0, 0.0, -100.0);
+        for &dt in dts {
+            controller.update_camera(&mut camera, dt);
+        }
+        camera.eye
+    }
+
+    #[test]
+    fn movement_depends_on_time_not_updates() {
+        let presses: [fn(&mut CameraController); 2] = [
+            |controller| controller.is_forward_pressed = true,
+            |controller| controller.is_left_pressed = true,
+        ];
+        for &press in &presses {
+            let halves = eye_after(press, &[0.5, 0.5]);
+            let whole = eye_after(press, &[1.0]);
+            assert!(
+                (halves - whole).magnitude() < 1e-4,
+                "{:?} != {:?}",
+                halves,
+                whole
+            );
+            assert_ne!(whole, camera().eye);
+        }
+    }
+
+    #[test]
+    fn resizing_updates_the_aspect_ratio() {
+        let mut camera = camera();
+        camera.resize(400, 400);
+        camera.resize(800, 600);
+        assert!((camera.aspect - 4.0 / 3.0).abs() < 1e-6);
+        // A minimized window has no height to divide by
+        camera.resize(800, 0);
+        assert!((camera.aspect - 4.0 / 3.0).abs() < 1e-6);
     }
 }
@@ -0,0 +1,264 @@
+use crate::physics;
+use crate::spatial_hash::SpatialHash;
+use crate::sphere::Entity;
+use cgmath::*;
+
+/// How many times the mean spacing between bodies density_radius reaches, so a typical
+/// body has a handful of neighbors to count
+const DENSITY_SPACINGS: f64 = 2.0;
+
+/// A gradient from dark to bright that scalars are mapped onto
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Colormap {
+    /// Dark blue through green to yellow, evenly bright and readable in grayscale
+    Viridis,
+    /// Dark blue through green and yellow to dark red, a smoother rainbow
+    Turbo,
+}
+
+impl Colormap {
+    /// Every colormap, in the order they're cycled through
+    pub const ALL: [Colormap; 2] = [Colormap::Viridis, Colormap::Turbo];
+
+    /// The name the colormap goes by on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colormap::Viridis => "viridis",
+            Colormap::Turbo => "turbo",
+        }
+    }
+
+    /// The colormap called `name`, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|colormap| colormap.name() == name)
+    }
+
+    /// The colormap after this one in ALL, wrapping around
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// The color `t` of the way along the gradient, with 0 the low end and 1 the high end.
+    /// Values past either end are clamped to it.
+    /// Both are polynomial fits to the published tables, close enough to tell apart by eye
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let color = match self {
+            // Matt Zucker's degree six fit
+            Colormap::Viridis => {
+                let c: [[f32; 3]; 7] = [
+                    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+                    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+                    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+                    [-4.634_230_5, -5.799_101, -19.332_441],
+                    [6.228_27, 14.179_933, 56.690_55],
+                    [4.776_385, -13.745_145, -65.353_03],
+                    [-5.435_456, 4.645_852_6, 26.312_435],
+                ];
+                let channel = |k: usize| c.iter().rev().fold(0.0, |sum, c| sum * t + c[k]);
+                [channel(0), channel(1), channel(2)]
+            }
+            // Google's degree five fit, from the colormap's announcement
+            Colormap::Turbo => {
+                let c: [[f32; 3]; 6] = [
+                    [0.135_721_38, 0.091_402_61, 0.106_673_3],
+                    [4.615_392_6, 2.194_188_4, 12.641_946],
+                    [-42.660_323, 4.842_966_6, -60.582_05],
+                    [132.131_08, -14.185_033, 110.362_77],
+                    [-152.942_4, 4.277_298_5, -89.903_11],
+                    [59.286_38, 2.829_566, 27.348_25],
+                ];
+                let channel = |k: usize| c.iter().rev().fold(0.0, |sum, c| sum * t + c[k]);
+                [channel(0), channel(1), channel(2)]
+            }
+        };
+        color.map(|channel| channel.clamp(0.0, 1.0))
+    }
+}
+
+/// What each body's color stands for
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorBy {
+    /// Every body keeps the color it was given
+    Fixed,
+    /// How fast the body is moving
+    Speed,
+    /// How heavy the body is
+    Mass,
+    /// How much mass is packed around the body, see local_density
+    LocalDensity,
+}
+
+impl ColorBy {
+    /// Every option, in the order they're cycled through
+    pub const ALL: [ColorBy; 4] = [
+        ColorBy::Fixed,
+        ColorBy::Speed,
+        ColorBy::Mass,
+        ColorBy::LocalDensity,
+    ];
+
+    /// The name the option goes by on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorBy::Fixed => "fixed",
+            ColorBy::Speed => "speed",
+            ColorBy::Mass => "mass",
+            ColorBy::LocalDensity => "density",
+        }
+    }
+
+    /// The option called `name`, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|color_by| color_by.name() == name)
+    }
+
+    /// The option after this one in ALL, wrapping around
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Whether the values tend to span orders of magnitude, so they're spread over the
+    /// colormap by their logarithm instead
+    pub fn logarithmic(&self) -> bool {
+        matches!(self, ColorBy::Mass | ColorBy::LocalDensity)
+    }
+
+    /// The value each body is colored by, or None when bodies keep their own colors
+    pub fn values(&self, bodies: &[Entity]) -> Option<Vec<f32>> {
+        match self {
+            ColorBy::Fixed => None,
+            ColorBy::Speed => Some(
+                bodies
+                    .iter()
+                    .map(|body| body.velocity.magnitude() as f32)
+                    .collect(),
+            ),
+            ColorBy::Mass => Some(bodies.iter().map(|body| body.mass).collect()),
+            ColorBy::LocalDensity => Some(local_density(bodies, density_radius(bodies))),
+        }
+    }
+}
+
+/// Which values the two ends of the colormap stand for
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorRange {
+    /// The lowest and highest values among the bodies, worked out again every frame
+    Auto,
+    /// Values from the first to the second, with anything outside clamped to the ends
+    Fixed(f32, f32),
+}
+
+/// The colors a ColorScale gives the bodies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tint {
+    /// Each body's color, in the same order as the bodies
+    pub colors: Vec<[f32; 3]>,
+    /// The value the low end of the colormap stood for
+    pub min: f32,
+    /// The value the high end of the colormap stood for
+    pub max: f32,
+}
+
+/// How bodies are tinted by a value: which value, what colors and over what range
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorScale {
+    /// The value bodies are colored by
+    pub color_by: ColorBy,
+    /// The colors values are mapped onto
+    pub colormap: Colormap,
+    /// The values the ends of the colormap stand for
+    pub range: ColorRange,
+}
+
+impl ColorScale {
+    /// Each body's color and the values the colormap spanned, or None when bodies keep
+    /// their own colors. With an automatic range and every value the same, every body gets
+    /// the middle of the colormap
+    pub fn colors(&self, bodies: &[Entity]) -> Option<Tint> {
+        let values = self.color_by.values(bodies)?;
+        let (min, max) = match self.range {
+            ColorRange::Auto => values
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                    (min.min(value), max.max(value))
+                }),
+            ColorRange::Fixed(min, max) => (min, max),
+        };
+        // Masses and densities can't be negative, and the smallest positive value stands in
+        // for zero so it still has a logarithm
+        let scale = |value: f32| {
+            if self.color_by.logarithmic() {
+                value.max(f32::MIN_POSITIVE).log10()
+            } else {
+                value
+            }
+        };
+        let (low, high) = (scale(min), scale(max));
+        let colors = values
+            .iter()
+            .map(|&value| {
+                let t = if high > low {
+                    (scale(value) - low) / (high - low)
+                } else {
+                    0.5
+                };
+                self.colormap.sample(t)
+            })
+            .collect();
+        Some(Tint { colors, min, max })
+    }
+}
+
+impl Default for ColorScale {
+    fn default() -> Self {
+        Self {
+            color_by: ColorBy::Fixed,
+            colormap: Colormap::Viridis,
+            range: ColorRange::Auto,
+        }
+    }
+}
+
+/// The mass around each body per unit volume: the mass of every body whose center is
+/// within `radius` of its own, itself included, over the volume of a sphere that size.
+/// Neighbors are found with a spatial hash of cells `radius` across, so it's close to O(n)
+/// as long as `radius`, which must be above 0, takes in a handful of bodies
+pub fn local_density(bodies: &[Entity], radius: f32) -> Vec<f32> {
+    let hash = SpatialHash::new(bodies, radius);
+    let volume = 4.0 / 3.0 * std::f64::consts::PI * f64::from(radius).powi(3);
+    bodies
+        .iter()
+        .map(|body| {
+            let mass: f64 = hash
+                .within(bodies, body.position, radius)
+                .into_iter()
+                .map(|i| f64::from(bodies[i].mass))
+                .sum();
+            (mass / volume) as f32
+        })
+        .collect()
+}
+
+/// A radius for local_density that suits `bodies`: twice the spacing they'd have if they
+/// were spread evenly through the box around them. Falls back to 1 when there are no
+/// bodies or the box has no size
+pub fn density_radius(bodies: &[Entity]) -> f32 {
+    let (_, min, max) = physics::scene_bounds(bodies);
+    let size = max - min;
+    let spacing = (size.x * size.y * size.z / bodies.len() as f64).cbrt();
+    let radius = (spacing * DENSITY_SPACINGS) as f32;
+    if radius.is_finite() && radius > 0.0 {
+        radius
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,673 @@
+use crate::sphere::Entity;
+use cgmath::*;
+
+/// How many bodies each workgroup handles. Must match TILE_SIZE in gravity.wgsl
+/// and the workgroup sizes in integrate.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+/// The size of each body's position or velocity in the buffers
+const VEC4_SIZE: wgpu::BufferAddress = std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress;
+
+// Mirrors the Params uniform in gravity.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GravityParams {
+    count: u32,
+    g: f32,
+    softening2: f32,
+    min_separation2: f32,
+}
+
+// Mirrors the Params uniform in integrate.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct IntegrateParams {
+    count: u32,
+    dt: f32,
+    instance_stride: u32,
+    instance_count: u32,
+    origin: [f32; 3],
+    _padding: f32,
+}
+
+/// Computes the pull between every pair of bodies on the GPU.
+/// The buffers are kept between frames and only recreated when the number of bodies
+/// outgrows them.
+///
+/// The bodies can also be kept on the GPU and stepped there with velocity Verlet, see
+/// upload, step and download, so nothing has to come back to the CPU every step. Their
+/// positions go straight into the instance buffer with place_instances.
+pub struct GravityCompute {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    /// Positions and masses, packed as vec4s
+    body_buffer: wgpu::Buffer,
+    /// Velocities packed as vec4s, only used while the bodies are kept on the GPU
+    velocity_buffer: wgpu::Buffer,
+    /// Where the shader writes each body's acceleration
+    acceleration_buffer: wgpu::Buffer,
+    /// A CPU-readable buffer with room for a position and a velocity per body
+    staging_buffer: wgpu::Buffer,
+    /// How many bodies the buffers currently have room for
+    capacity: usize,
+    /// How many bodies were last uploaded
+    count: usize,
+    kick_drift_pipeline: wgpu::ComputePipeline,
+    kick_pipeline: wgpu::ComputePipeline,
+    place_instances_pipeline: wgpu::ComputePipeline,
+    integrate_bind_group_layout: wgpu::BindGroupLayout,
+    integrate_bind_group: wgpu::BindGroup,
+    instances_bind_group_layout: wgpu::BindGroupLayout,
+    /// The params of each integration pass, which only change with the step length
+    integrate_params_buffer: wgpu::Buffer,
+    /// Which body each slot of the instance buffer draws, for place_instances
+    order_buffer: wgpu::Buffer,
+    /// How many slots order_buffer has room for
+    order_capacity: usize,
+}
+
+impl GravityCompute {
+    /// Builds the compute pipelines along with buffers big enough for `capacity` bodies
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Gravity Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gravity.wgsl").into()),
+        });
+
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gravity_bind_group_layout"),
+            entries: &[
+                uniform_entry(0),
+                storage_entry(1, true),
+                storage_entry(2, false),
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gravity Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Gravity Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "main",
+        });
+
+        let integrate_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Integrate Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("integrate.wgsl").into()),
+        });
+        let integrate_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("integrate_bind_group_layout"),
+                entries: &[
+                    uniform_entry(0),
+                    storage_entry(1, false),
+                    storage_entry(2, false),
+                    storage_entry(3, true),
+                ],
+            });
+        let instances_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("instances_bind_group_layout"),
+                entries: &[storage_entry(0, true), storage_entry(1, false)],
+            });
+        let integrate_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Integrate Pipeline Layout"),
+            bind_group_layouts: &[&integrate_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let place_instances_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Place Instances Pipeline Layout"),
+                bind_group_layouts: &[&integrate_bind_group_layout, &instances_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let integrate_pipeline = |label, layout, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                module: &integrate_shader,
+                entry_point,
+            })
+        };
+        let kick_drift_pipeline =
+            integrate_pipeline("Kick Drift Pipeline", &integrate_layout, "kick_drift");
+        let kick_pipeline = integrate_pipeline("Kick Pipeline", &integrate_layout, "kick");
+        let place_instances_pipeline = integrate_pipeline(
+            "Place Instances Pipeline",
+            &place_instances_layout,
+            "place_instances",
+        );
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gravity Params Buffer"),
+            size: std::mem::size_of::<GravityParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let integrate_params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Integrate Params Buffer"),
+            size: std::mem::size_of::<IntegrateParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Never allocate an empty buffer, wgpu won't bind one
+        let capacity = capacity.max(1);
+        let buffers = BodyBuffers::new(device, capacity);
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &params_buffer, &buffers);
+        let integrate_bind_group = Self::create_integrate_bind_group(
+            device,
+            &integrate_bind_group_layout,
+            &integrate_params_buffer,
+            &buffers,
+        );
+        let order_buffer = create_order_buffer(device, capacity);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            params_buffer,
+            body_buffer: buffers.body,
+            velocity_buffer: buffers.velocity,
+            acceleration_buffer: buffers.acceleration,
+            staging_buffer: buffers.staging,
+            capacity,
+            count: 0,
+            kick_drift_pipeline,
+            kick_pipeline,
+            place_instances_pipeline,
+            integrate_bind_group_layout,
+            integrate_bind_group,
+            instances_bind_group_layout,
+            integrate_params_buffer,
+            order_buffer,
+            order_capacity: capacity,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        buffers: &BodyBuffers,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gravity_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.body.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.acceleration.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn create_integrate_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        buffers: &BodyBuffers,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("integrate_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.body.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.velocity.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: buffers.acceleration.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Makes sure the buffers have room for `count` bodies, doubling them if they don't.
+    /// Anything kept on the GPU is lost when they grow, so it needs uploading again
+    fn reserve(&mut self, device: &wgpu::Device, count: usize) {
+        if count <= self.capacity {
+            return;
+        }
+        let capacity = count.max(self.capacity * 2);
+        let buffers = BodyBuffers::new(device, capacity);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.params_buffer,
+            &buffers,
+        );
+        self.integrate_bind_group = Self::create_integrate_bind_group(
+            device,
+            &self.integrate_bind_group_layout,
+            &self.integrate_params_buffer,
+            &buffers,
+        );
+        self.body_buffer = buffers.body;
+        self.velocity_buffer = buffers.velocity;
+        self.acceleration_buffer = buffers.acceleration;
+        self.staging_buffer = buffers.staging;
+        self.capacity = capacity;
+    }
+
+    /// Writes the bodies' positions and masses and the gravity settings to the GPU
+    fn write_bodies(
+        &self,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+        g: f32,
+        softening: f32,
+        min_separation: Option<f32>,
+    ) {
+        let bodies: Vec<[f32; 4]> = entities
+            .iter()
+            .map(|body| {
+                let [x, y, z]: [f64; 3] = body.position.into();
+                [x as f32, y as f32, z as f32, body.mass]
+            })
+            .collect();
+        let params = GravityParams {
+            count: entities.len() as u32,
+            g,
+            softening2: softening * softening,
+            min_separation2: min_separation.map_or(0.0, |separation| separation * separation),
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        queue.write_buffer(&self.body_buffer, 0, bytemuck::cast_slice(&bodies));
+    }
+
+    /// Records a pass computing every body's acceleration into acceleration_buffer
+    fn encode_gravity(&self, encoder: &mut wgpu::CommandEncoder, count: usize) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Gravity Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch(workgroups(count), 1, 1);
+    }
+
+    /// Copies the first `size` bytes of `buffer` back to the CPU. Blocks until the GPU
+    /// has caught up
+    fn read_back(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        copies: &[(&wgpu::Buffer, wgpu::BufferAddress)],
+        size: wgpu::BufferAddress,
+    ) -> Vec<[f32; 4]> {
+        let mut offset = 0;
+        for &(buffer, copy_size) in copies {
+            encoder.copy_buffer_to_buffer(buffer, 0, &self.staging_buffer, offset, copy_size);
+            offset += copy_size;
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.staging_buffer.slice(..size);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping).expect("failed to read back bodies from the GPU");
+        let data = bytemuck::cast_slice::<u8, [f32; 4]>(&slice.get_mapped_range()).to_vec();
+        self.staging_buffer.unmap();
+        data
+    }
+
+    /// The net gravitational acceleration on each body, matching
+    /// physics::compute_gravity_clamped with GravityStrategy::BruteForce, though clamped
+    /// pairs aren't counted. Blocks until the GPU has finished.
+    /// This overwrites any bodies kept on the GPU, so they need uploading again after.
+    /// Only used to check the shader against the CPU
+    #[cfg(test)]
+    pub fn compute_gravity(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+        g: f32,
+        softening: f32,
+        min_separation: Option<f32>,
+    ) -> Vec<Vector3<f32>> {
+        if entities.is_empty() {
+            return Vec::new();
+        }
+        self.reserve(device, entities.len());
+        self.write_bodies(queue, entities, g, softening, min_separation);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Gravity Encoder"),
+        });
+        self.encode_gravity(&mut encoder, entities.len());
+        // Waiting on the GPU so the results are ready for this physics step
+        let size = entities.len() as wgpu::BufferAddress * VEC4_SIZE;
+        self.read_back(
+            device,
+            queue,
+            encoder,
+            &[(&self.acceleration_buffer, size)],
+            size,
+        )
+        .iter()
+        .map(|a| Vector3::new(a[0], a[1], a[2]))
+        .collect()
+    }
+
+    /// Puts the bodies on the GPU to be stepped there, and works out the accelerations the
+    /// first step starts from
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+        g: f32,
+        softening: f32,
+        min_separation: Option<f32>,
+    ) {
+        self.count = entities.len();
+        if entities.is_empty() {
+            return;
+        }
+        self.reserve(device, entities.len());
+        self.write_bodies(queue, entities, g, softening, min_separation);
+        let velocities: Vec<[f32; 4]> = entities
+            .iter()
+            .map(|body| {
+                let [x, y, z]: [f64; 3] = body.velocity.into();
+                [x as f32, y as f32, z as f32, 0.0]
+            })
+            .collect();
+        queue.write_buffer(&self.velocity_buffer, 0, bytemuck::cast_slice(&velocities));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Upload Encoder"),
+        });
+        self.encode_gravity(&mut encoder, entities.len());
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Moves the uploaded bodies forward by `dt` with velocity Verlet, without waiting on
+    /// the GPU or reading anything back. Collisions aren't handled until the bodies are
+    /// downloaded
+    pub fn step(&self, device: &wgpu::Device, queue: &wgpu::Queue, dt: f32) {
+        if self.count == 0 {
+            return;
+        }
+        self.write_integrate_params(queue, dt, 0, 0, Vector3::zero());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Integrate Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Kick Drift Pass"),
+            });
+            compute_pass.set_pipeline(&self.kick_drift_pipeline);
+            compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+            compute_pass.dispatch(workgroups(self.count), 1, 1);
+        }
+        self.encode_gravity(&mut encoder, self.count);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Kick Pass"),
+            });
+            compute_pass.set_pipeline(&self.kick_pipeline);
+            compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+            compute_pass.dispatch(workgroups(self.count), 1, 1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Reads the uploaded bodies' positions and velocities back into `entities`, which
+    /// should be the same bodies that were uploaded. Blocks until the GPU has caught up
+    pub fn download(&self, device: &wgpu::Device, queue: &wgpu::Queue, entities: &mut [Entity]) {
+        if self.count == 0 || entities.len() != self.count {
+            return;
+        }
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Download Encoder"),
+        });
+        let size = self.count as wgpu::BufferAddress * VEC4_SIZE;
+        let data = self.read_back(
+            device,
+            queue,
+            encoder,
+            &[(&self.body_buffer, size), (&self.velocity_buffer, size)],
+            size * 2,
+        );
+        let (positions, velocities) = data.split_at(self.count);
+        for ((body, position), velocity) in entities.iter_mut().zip(positions).zip(velocities) {
+            body.position = Vector3::new(position[0], position[1], position[2]).map(f64::from);
+            body.velocity = Vector3::new(velocity[0], velocity[1], velocity[2]).map(f64::from);
+        }
+    }
+
+    /// Moves the instances in `instance_buffer`, which must have STORAGE usage, to where
+    /// their bodies are on the GPU, less `origin` like the instances written on the CPU.
+    /// `order` is the body each slot of the buffer draws, and `instance_stride` the size of
+    /// an instance in bytes
+    pub fn place_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instance_buffer: &wgpu::Buffer,
+        order: &[u32],
+        instance_stride: usize,
+        origin: Vector3<f32>,
+    ) {
+        if self.count == 0 || order.is_empty() {
+            return;
+        }
+        if order.len() > self.order_capacity {
+            self.order_capacity = order.len().max(self.order_capacity * 2);
+            self.order_buffer = create_order_buffer(device, self.order_capacity);
+        }
+        queue.write_buffer(&self.order_buffer, 0, bytemuck::cast_slice(order));
+        self.write_integrate_params(queue, 0.0, instance_stride, order.len(), origin);
+
+        // The instance buffer is swapped out whenever it grows, so this is built each time
+        let instances_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("instances_bind_group"),
+            layout: &self.instances_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.order_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Place Instances Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Place Instances Pass"),
+            });
+            compute_pass.set_pipeline(&self.place_instances_pipeline);
+            compute_pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+            compute_pass.set_bind_group(1, &instances_bind_group, &[]);
+            compute_pass.dispatch(workgroups(order.len()), 1, 1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    fn write_integrate_params(
+        &self,
+        queue: &wgpu::Queue,
+        dt: f32,
+        instance_stride: usize,
+        instance_count: usize,
+        origin: Vector3<f32>,
+    ) {
+        let params = IntegrateParams {
+            count: self.count as u32,
+            dt,
+            // The shader indexes the instances as floats
+            instance_stride: (instance_stride / std::mem::size_of::<f32>()) as u32,
+            instance_count: instance_count as u32,
+            origin: origin.into(),
+            _padding: 0.0,
+        };
+        queue.write_buffer(
+            &self.integrate_params_buffer,
+            0,
+            bytemuck::cast_slice(&[params]),
+        );
+    }
+}
+
+/// The buffers sized by how many bodies there are, recreated together whenever they outgrow
+/// their capacity
+struct BodyBuffers {
+    body: wgpu::Buffer,
+    velocity: wgpu::Buffer,
+    acceleration: wgpu::Buffer,
+    staging: wgpu::Buffer,
+}
+
+impl BodyBuffers {
+    fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let size = capacity as wgpu::BufferAddress * VEC4_SIZE;
+        let body = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gravity Body Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let velocity = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gravity Velocity Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let acceleration = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gravity Acceleration Buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        // Downloads read positions and velocities back together
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gravity Staging Buffer"),
+            size: size * 2,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            body,
+            velocity,
+            acceleration,
+            staging,
+        }
+    }
+}
+
+fn create_order_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Order Buffer"),
+        size: (capacity * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// How many workgroups it takes to cover `count` invocations
+fn workgroups(count: usize) -> u32 {
+    (count as u32).div_ceil(WORKGROUP_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nbodysim::physics::{self, GravityStrategy};
+    use nbodysim::sphere::BodyType;
+
+    #[test]
+    fn gpu_gravity_matches_the_cpu() {
+        let (device, queue) = match pollster::block_on(crate::gpu::headless()) {
+            Some(device) => device,
+            None => {
+                eprintln!("no adapter, skipping");
+                return;
+            }
+        };
+        let entities = [
+            Entity::new(BodyType::Planet, Vector3::new(0.0, 0.0, 0.0)),
+            Entity::new(BodyType::Planet, Vector3::new(1.0, 0.0, 0.0)),
+            Entity::new(BodyType::Planet, Vector3::new(0.0, 2.0, 1.0)),
+        ];
+
+        let mut gravity = GravityCompute::new(&device, entities.len());
+        let gpu = gravity.compute_gravity(
+            &device,
+            &queue,
+            &entities,
+            1.0,
+            physics::DEFAULT_SOFTENING,
+            None,
+        );
+        let cpu = physics::compute_gravity(
+            &entities,
+            1.0,
+            physics::DEFAULT_SOFTENING,
+            GravityStrategy::BruteForce,
+        );
+
+        assert_eq!(gpu.len(), cpu.len());
+        for (gpu, cpu) in gpu.iter().zip(&cpu) {
+            let cpu = cpu.cast::<f32>().unwrap();
+            assert!((gpu - cpu).magnitude() < 1e-4, "{:?} != {:?}", gpu, cpu);
+        }
+    }
+}
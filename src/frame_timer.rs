@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How many frames FrameTimer averages over by default
+pub const DEFAULT_FRAME_WINDOW: usize = 60;
+
+/// How often the frame rate is reported on stderr
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Measures how long each frame takes, averaged over the last few frames so the
+/// reading doesn't jump around with every hitch
+pub struct FrameTimer {
+    /// When the previous frame started
+    last_frame: Instant,
+    /// The lengths of the most recent frames, oldest first
+    frame_times: VecDeque<Duration>,
+    /// The sum of frame_times, kept so the average doesn't need summing every frame
+    total: Duration,
+    /// How many frames are averaged over
+    window: usize,
+    /// When the frame rate was last reported
+    last_report: Instant,
+}
+
+impl FrameTimer {
+    /// A timer averaging over the last `window` frames, at least one
+    pub fn new(window: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            last_frame: now,
+            frame_times: VecDeque::with_capacity(window.max(1)),
+            total: Duration::ZERO,
+            window: window.max(1),
+            last_report: now,
+        }
+    }
+
+    /// Marks the start of a new frame. Returns how many seconds the last one took,
+    /// and reports the frame rate on stderr about once a second
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let frame_time = now - self.last_frame;
+        self.last_frame = now;
+
+        self.frame_times.push_back(frame_time);
+        self.total += frame_time;
+        while self.frame_times.len() > self.window {
+            if let Some(oldest) = self.frame_times.pop_front() {
+                self.total -= oldest;
+            }
+        }
+
+        if now - self.last_report >= REPORT_INTERVAL {
+            self.last_report = now;
+            eprintln!(
+                "{:.1} fps ({:.2} ms per frame)",
+                self.fps(),
+                self.frame_time_ms()
+            );
+        }
+        frame_time.as_secs_f32()
+    }
+
+    /// The average length of the recent frames in milliseconds, or 0 before the first frame
+    pub fn frame_time_ms(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.total.as_secs_f32() * 1000.0 / self.frame_times.len() as f32
+    }
+
+    /// The average frames per second over the recent frames, or 0 before the first frame
+    pub fn fps(&self) -> f32 {
+        let frame_time_ms = self.frame_time_ms();
+        if frame_time_ms > 0.0 {
+            1000.0 / frame_time_ms
+        } else {
+            0.0
+        }
+    }
+}
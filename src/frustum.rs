@@ -0,0 +1,46 @@
+use cgmath::*;
+
+/// The six planes bounding what a camera can see, each as (normal, distance) with the
+/// normal facing inwards, so a point is inside when `normal.dot(point) + distance >= 0`
+/// for every plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Pulls the planes out of a view-projection matrix mapping the visible world to
+    /// wgpu's clip space, where x and y run from -w to w and depth from 0 to w.
+    /// Reversed depth swaps which of the two depth planes is near, but they bound the
+    /// same space
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Self {
+        let row = |i| view_projection.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| {
+            // Scaled so the distances come out in world units, to compare against radii
+            let length = plane.truncate().magnitude();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether any of the sphere of `radius` around `center` could be on screen.
+    /// Only spheres entirely outside one of the planes are ruled out, so a sphere just past
+    /// a corner can still count as visible
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+    }
+}
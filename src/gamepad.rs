@@ -0,0 +1,69 @@
+use crate::camera::{self, AnalogInput};
+use cgmath::Vector2;
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+/// How far a stick has to be pushed, from 0 to 1, before it moves the camera
+pub const DEFAULT_DEAD_ZONE: f32 = 0.15;
+
+/// What the gamepad asked for since it was last polled
+pub struct GamepadInput {
+    /// Where the sticks and triggers are held, for the camera controller
+    pub camera: AnalogInput,
+    /// Whether the pause button was pressed
+    pub toggle_pause: bool,
+}
+
+/// Reads the first connected gamepad. The left stick orbits the camera, the right stick
+/// pans it, the right and left triggers zoom in and out, and South (A on an Xbox pad)
+/// or Start pauses the simulation
+pub struct Gamepad {
+    gilrs: Gilrs,
+    /// How far a stick has to be pushed before it counts, see camera::apply_dead_zone
+    pub dead_zone: f32,
+}
+
+impl Gamepad {
+    /// Starts listening for gamepads. Fails if the platform's gamepad support can't be set up
+    pub fn new() -> anyhow::Result<Self> {
+        let gilrs =
+            Gilrs::new().map_err(|e| anyhow::anyhow!("Failed to set up gamepads: {}", e))?;
+        Ok(Self {
+            gilrs,
+            dead_zone: DEFAULT_DEAD_ZONE,
+        })
+    }
+
+    /// Handles the gamepad events since the last poll and reads where the controls are held
+    pub fn poll(&mut self) -> GamepadInput {
+        let mut toggle_pause = false;
+        while let Some(event) = self.gilrs.next_event() {
+            if let EventType::ButtonPressed(Button::South, _)
+            | EventType::ButtonPressed(Button::Start, _) = event.event
+            {
+                toggle_pause = true;
+            }
+        }
+
+        let camera = match self.gilrs.gamepads().next() {
+            Some((_, gamepad)) => {
+                let stick = |x, y| {
+                    camera::apply_dead_zone(
+                        Vector2::new(gamepad.value(x), gamepad.value(y)),
+                        self.dead_zone,
+                    )
+                };
+                let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+                AnalogInput {
+                    look: stick(Axis::LeftStickX, Axis::LeftStickY),
+                    pan: stick(Axis::RightStickX, Axis::RightStickY),
+                    zoom: trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2),
+                }
+            }
+            None => AnalogInput::default(),
+        };
+        GamepadInput {
+            camera,
+            toggle_pause,
+        }
+    }
+}
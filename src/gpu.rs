@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use winit::window::Window;
+
+/// The device features the renderer can't do without: wireframes need lines
+pub const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE;
+
+/// The ways of finding a GPU connect tries, in order: the modern graphics APIs, then the
+/// older ones, and a software renderer as a last resort
+const ATTEMPTS: [Attempt; 5] = [
+    Attempt {
+        backends: wgpu::Backends::PRIMARY,
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: false,
+    },
+    Attempt {
+        backends: wgpu::Backends::PRIMARY,
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        force_fallback_adapter: false,
+    },
+    Attempt {
+        backends: wgpu::Backends::SECONDARY,
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: false,
+    },
+    Attempt {
+        backends: wgpu::Backends::SECONDARY,
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        force_fallback_adapter: false,
+    },
+    Attempt {
+        backends: wgpu::Backends::all(),
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: true,
+    },
+];
+
+/// A connection to a GPU that can draw to the window
+pub struct Gpu {
+    pub surface: wgpu::Surface,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    /// The format the surface prefers to be drawn in
+    pub format: wgpu::TextureFormat,
+}
+
+/// One way of asking wgpu for an adapter
+struct Attempt {
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    /// Whether to ask for a software adapter instead of a real GPU
+    force_fallback_adapter: bool,
+}
+
+impl Attempt {
+    /// Connects to the adapter this finds, or says why there wasn't one that would do
+    async fn connect(&self, window: &Window) -> Result<Gpu> {
+        let instance = wgpu::Instance::new(self.backends);
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                force_fallback_adapter: self.force_fallback_adapter,
+                compatible_surface: Some(&surface),
+            })
+            .await
+            .ok_or_else(|| anyhow!("no adapter found"))?;
+        let info = adapter.get_info();
+        let name = format!("{} ({:?})", info.name, info.backend);
+        let missing = REQUIRED_FEATURES - adapter.features();
+        if !missing.is_empty() {
+            return Err(anyhow!("{} doesn't support {:?}", name, missing));
+        }
+        let format = surface
+            .get_preferred_format(&adapter)
+            .ok_or_else(|| anyhow!("{} can't draw to this window", name))?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: REQUIRED_FEATURES,
+                    // default() will support all modern backends
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .map_err(|e| anyhow!("{} wouldn't give a device: {}", name, e))?;
+        log::info!("Drawing with {}", name);
+        Ok(Gpu {
+            surface,
+            device,
+            queue,
+            format,
+        })
+    }
+
+    /// What this tries, e.g. "low power Vulkan/Metal/DX12/WebGPU"
+    fn describe(&self) -> String {
+        let power = match self.power_preference {
+            wgpu::PowerPreference::LowPower => "low power",
+            wgpu::PowerPreference::HighPerformance => "high performance",
+        };
+        let backends = [
+            (wgpu::Backends::VULKAN, "Vulkan"),
+            (wgpu::Backends::METAL, "Metal"),
+            (wgpu::Backends::DX12, "DX12"),
+            (wgpu::Backends::DX11, "DX11"),
+            (wgpu::Backends::GL, "OpenGL"),
+            (wgpu::Backends::BROWSER_WEBGPU, "WebGPU"),
+        ]
+        .iter()
+        .filter(|(backend, _)| self.backends.contains(*backend))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join("/");
+        if self.force_fallback_adapter {
+            format!("software {}", backends)
+        } else {
+            format!("{} {}", power, backends)
+        }
+    }
+}
+
+/// Connects to the first GPU that can draw to `window`, trying the modern graphics APIs
+/// before older ones and software rendering. When none of them work the error lists
+/// everything that was tried and why it failed
+pub async fn connect(window: &Window) -> Result<Gpu> {
+    let mut failures = Vec::new();
+    for attempt in &ATTEMPTS {
+        match attempt.connect(window).await {
+            Ok(gpu) => return Ok(gpu),
+            Err(e) => {
+                log::debug!("No GPU from {}: {}", attempt.describe(), e);
+                failures.push(format!("  {}: {}", attempt.describe(), e));
+            }
+        }
+    }
+    Err(anyhow!(
+        "couldn't find a GPU to draw with. Tried:\n{}\n\
+         Check the graphics drivers are installed, or use --headless to simulate without a window",
+        failures.join("\n")
+    ))
+}
+
+/// A GPU with no window to draw to, for rendering offscreen in tests. None when there's no
+/// adapter with the features the renderer needs
+#[cfg(test)]
+pub async fn headless() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::Backends::all());
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        })
+        .await?;
+    if !adapter.features().contains(REQUIRED_FEATURES) {
+        return None;
+    }
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: REQUIRED_FEATURES,
+                limits: wgpu::Limits::default(),
+                label: None,
+            },
+            None,
+        )
+        .await
+        .ok()
+}
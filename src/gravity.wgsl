@@ -0,0 +1,92 @@
+// Compute shader
+
+[[block]]
+struct Params {
+    count: u32;
+    g: f32;
+    softening2: f32;
+    // 0 when the separation clamp is off
+    min_separation2: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+// Each body is packed as xyz = position, w = mass
+[[block]]
+struct Bodies {
+    data: array<vec4<f32>>;
+};
+[[group(0), binding(1)]]
+var<storage, read> bodies: Bodies;
+
+[[block]]
+struct Accelerations {
+    data: array<vec4<f32>>;
+};
+[[group(0), binding(2)]]
+var<storage, read_write> accelerations: Accelerations;
+
+// Must match WORKGROUP_SIZE in compute.rs
+let TILE_SIZE: u32 = 64u;
+
+// The bodies the whole workgroup is currently summing over, loaded once from the
+// storage buffer and then read by every invocation
+var<workgroup> tile: array<vec4<f32>, 64>;
+
+[[stage(compute), workgroup_size(64)]]
+fn main(
+    [[builtin(global_invocation_id)]] global_id: vec3<u32>,
+    [[builtin(local_invocation_id)]] local_id: vec3<u32>,
+) {
+    let index = global_id.x;
+    let in_range = index < params.count;
+    var position = vec3<f32>(0.0, 0.0, 0.0);
+    if (in_range) {
+        position = bodies.data[index].xyz;
+    }
+
+    var acceleration = vec3<f32>(0.0, 0.0, 0.0);
+    let tile_count = (params.count + TILE_SIZE - 1u) / TILE_SIZE;
+    var t: u32 = 0u;
+    loop {
+        if (t >= tile_count) {
+            break;
+        }
+
+        // Every invocation loads one body into the tile, padding past the end with no mass
+        let source = t * TILE_SIZE + local_id.x;
+        if (source < params.count) {
+            tile[local_id.x] = bodies.data[source];
+        } else {
+            tile[local_id.x] = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        }
+        workgroupBarrier();
+
+        var j: u32 = 0u;
+        loop {
+            if (j >= TILE_SIZE) {
+                break;
+            }
+            let other = tile[j];
+            let offset = other.xyz - position;
+            let distance2 = max(dot(offset, offset), params.min_separation2) + params.softening2;
+            // Skips ourselves and the padding, which both leave nothing to divide by
+            if (distance2 > 0.0 && t * TILE_SIZE + j != index) {
+                acceleration = acceleration + offset * (params.g * other.w / (distance2 * sqrt(distance2)));
+            }
+            continuing {
+                j = j + 1u;
+            }
+        }
+        // Don't let the next tile overwrite bodies others are still reading
+        workgroupBarrier();
+
+        continuing {
+            t = t + 1u;
+        }
+    }
+
+    if (in_range) {
+        accelerations.data[index] = vec4<f32>(acceleration, 0.0);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::trail::TrailVertex;
+
+/// How far the grid reaches from the origin unless told otherwise
+pub const DEFAULT_GRID_EXTENT: f32 = 40.0;
+/// How far apart the grid lines are unless told otherwise
+pub const DEFAULT_GRID_SPACING: f32 = 5.0;
+
+/// Faint enough that the grid doesn't compete with the bodies
+const GRID_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.25];
+
+/// A line list along the x (red), y (green) and z (blue) axes, each reaching `length`
+/// from the origin in both directions
+pub fn axis_vertices(length: f32) -> Vec<TrailVertex> {
+    let axes = [
+        ([1.0, 0.0, 0.0], [1.0, 0.2, 0.2, 1.0]),
+        ([0.0, 1.0, 0.0], [0.2, 1.0, 0.2, 1.0]),
+        ([0.0, 0.0, 1.0], [0.2, 0.4, 1.0, 1.0]),
+    ];
+    let mut vertices = Vec::with_capacity(axes.len() * 2);
+    for (axis, color) in axes {
+        let end = [axis[0] * length, axis[1] * length, axis[2] * length];
+        let start = [-end[0], -end[1], -end[2]];
+        vertices.push(TrailVertex::new(start, color));
+        vertices.push(TrailVertex::new(end, color));
+    }
+    vertices
+}
+
+/// A line list of a square grid in the xz plane, the plane the scenes' orbits lie in.
+/// Lines are `spacing` apart and reach `extent` from the origin. A spacing that isn't
+/// positive gives no lines at all
+pub fn grid_vertices(extent: f32, spacing: f32) -> Vec<TrailVertex> {
+    if spacing <= 0.0 || extent <= 0.0 {
+        return Vec::new();
+    }
+    let lines = (extent / spacing).floor() as i32;
+    let mut vertices = Vec::with_capacity((2 * lines as usize + 1) * 4);
+    for i in -lines..=lines {
+        let offset = i as f32 * spacing;
+        // One line running along x and one along z
+        vertices.push(TrailVertex::new([-extent, 0.0, offset], GRID_COLOR));
+        vertices.push(TrailVertex::new([extent, 0.0, offset], GRID_COLOR));
+        vertices.push(TrailVertex::new([offset, 0.0, -extent], GRID_COLOR));
+        vertices.push(TrailVertex::new([offset, 0.0, extent], GRID_COLOR));
+    }
+    vertices
+}
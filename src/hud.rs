@@ -0,0 +1,179 @@
+use crate::colormap::ColorScale;
+use crate::integrator::Integrator;
+use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
+use egui_winit_platform::{Platform, PlatformDescriptor};
+use std::time::Instant;
+use winit::dpi::PhysicalSize;
+use winit::event::{Event, WindowEvent};
+
+/// What the HUD shows for the current frame
+pub struct HudStats {
+    /// Frames per second, averaged over the last few frames
+    pub fps: f32,
+    pub body_count: usize,
+    /// How many of the bodies were drawn, the rest being out of view
+    pub drawn_count: usize,
+    pub time_scale: f32,
+    /// Whether the simulation is running slower than time_scale because it can't keep up
+    pub slow_motion: bool,
+    pub paused: bool,
+    /// Kinetic plus potential energy of every body
+    pub energy: f64,
+    /// How far the energy has moved over the last few seconds, as a fraction of where it
+    /// was, if there's enough history to tell
+    pub energy_drift: Option<f64>,
+    /// Total linear momentum of every body
+    pub momentum: cgmath::Vector3<f64>,
+    /// The softening length gravity is computed with
+    pub softening: f32,
+    /// The minimum separation pairs are clamped to, if the clamp is on
+    pub min_separation: Option<f32>,
+    /// How many pairs were clamped in the last step, if they're being counted
+    pub clamped_pairs: Option<usize>,
+    /// What bodies are tinted by and the values the ends of the colormap stand for,
+    /// unless they keep their own colors
+    pub color_scale: Option<(ColorScale, (f32, f32))>,
+    /// The comparison run's integrator and how far its bodies have drifted from the
+    /// simulation's, while there is one
+    pub comparison: Option<(Integrator, f64)>,
+}
+
+/// An on-screen overlay showing diagnostics, drawn with egui over the finished scene
+pub struct Hud {
+    platform: Platform,
+    render_pass: RenderPass,
+    /// The window's HiDPI scale, kept up to date from its events
+    scale_factor: f64,
+    /// When the HUD was created, which egui measures its animations from
+    start: Instant,
+}
+
+impl Hud {
+    /// An overlay for a window `size` big with the HiDPI scale `scale_factor`
+    pub fn new(
+        size: PhysicalSize<u32>,
+        scale_factor: f64,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let platform = Platform::new(PlatformDescriptor {
+            physical_width: size.width,
+            physical_height: size.height,
+            scale_factor,
+            font_definitions: egui::FontDefinitions::default(),
+            style: egui::Style::default(),
+        });
+        Self {
+            platform,
+            render_pass: RenderPass::new(device, format, 1),
+            scale_factor,
+            start: Instant::now(),
+        }
+    }
+
+    /// Passes a window event on to egui. Every event should go through here,
+    /// even ones egui doesn't end up using
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        if let Event::WindowEvent {
+            event: WindowEvent::ScaleFactorChanged { scale_factor, .. },
+            ..
+        } = event
+        {
+            self.scale_factor = *scale_factor;
+        }
+        self.platform.handle_event(event);
+    }
+
+    /// Whether egui used `event`, in which case nothing else should react to it
+    pub fn captures_event<T>(&self, event: &Event<T>) -> bool {
+        self.platform.captures_event(event)
+    }
+
+    /// Draws the overlay on top of whatever is already in `view`
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        config: &wgpu::SurfaceConfiguration,
+        stats: &HudStats,
+    ) {
+        self.platform
+            .update_time(self.start.elapsed().as_secs_f64());
+        self.platform.begin_frame();
+        egui::Window::new("Diagnostics")
+            .anchor(egui::Align2::LEFT_TOP, [8.0, 8.0])
+            .resizable(false)
+            .collapsible(false)
+            .show(&self.platform.context(), |ui| {
+                ui.monospace(format!("FPS:        {:.0}", stats.fps));
+                ui.monospace(format!(
+                    "Bodies:     {} ({} drawn)",
+                    stats.body_count, stats.drawn_count
+                ));
+                if stats.paused {
+                    ui.monospace("Time scale: paused");
+                } else if stats.slow_motion {
+                    ui.monospace(format!("Time scale: {}x (slow motion)", stats.time_scale));
+                } else {
+                    ui.monospace(format!("Time scale: {}x", stats.time_scale));
+                }
+                ui.monospace(format!("Energy:     {:.6e}", stats.energy));
+                if let Some(drift) = stats.energy_drift {
+                    ui.monospace(format!("Drift:      {:+.2e}", drift));
+                }
+                ui.monospace(format!(
+                    "Momentum:   ({:.3e}, {:.3e}, {:.3e})",
+                    stats.momentum.x, stats.momentum.y, stats.momentum.z
+                ));
+                ui.monospace(format!("Softening:  {}", stats.softening));
+                match stats.min_separation {
+                    Some(separation) => {
+                        ui.monospace(format!("Min sep:    {}", separation));
+                        if let Some(clamped) = stats.clamped_pairs {
+                            ui.monospace(format!("Clamped:    {} pairs", clamped));
+                        }
+                    }
+                    None => {
+                        ui.monospace("Min sep:    off");
+                    }
+                }
+                if let Some((scale, (min, max))) = stats.color_scale {
+                    ui.monospace(format!(
+                        "Color:      {} ({}) {:.3e} to {:.3e}",
+                        scale.color_by.name(),
+                        scale.colormap.name(),
+                        min,
+                        max
+                    ));
+                }
+                if let Some((integrator, divergence)) = stats.comparison {
+                    ui.monospace(format!(
+                        "Ghost:      {:?}, {:.3e} apart",
+                        integrator, divergence
+                    ));
+                }
+            });
+        let (_output, shapes) = self.platform.end_frame(None);
+        let meshes = self.platform.context().tessellate(shapes);
+
+        let screen_descriptor = ScreenDescriptor {
+            physical_width: config.width,
+            physical_height: config.height,
+            scale_factor: self.scale_factor as f32,
+        };
+        self.render_pass
+            .update_texture(device, queue, &self.platform.context().texture());
+        self.render_pass.update_user_textures(device, queue);
+        self.render_pass
+            .update_buffers(device, queue, &meshes, &screen_descriptor);
+        // Loading rather than clearing, so the scene stays underneath
+        if let Err(e) = self
+            .render_pass
+            .execute(encoder, view, &meshes, &screen_descriptor, None)
+        {
+            eprintln!("{:?}", e);
+        }
+    }
+}
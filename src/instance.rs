@@ -1,8 +1,18 @@
-use cgmath::{InnerSpace, Rotation3, Vector3, Zero};
+use crate::sphere::Entity;
+use cgmath::{InnerSpace, Matrix, One, SquareMatrix, Vector3};
 
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    /// How fast the instance is moving, used to stretch it for motion blur
+    pub velocity: cgmath::Vector3<f32>,
+    /// How much the unit sphere mesh is scaled by, i.e. the radius it's drawn with
+    pub radius: f32,
+    pub color: [f32; 3],
+    /// Emissive instances glow at full brightness instead of being lit
+    pub emissive: bool,
+    /// Which of Render::body_textures the instance is drawn with. 0 is plain white
+    pub texture: usize,
 }
 
 // Deriving the following traits for instances
@@ -13,6 +23,12 @@ pub struct Instance {
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     model: [[f32; 4]; 4],
+    color: [f32; 3],
+    /// 1 for emissive instances and 0 otherwise
+    emissive: f32,
+    /// The inverse transpose of the model matrix's rotation and scale, which keeps normals
+    /// at right angles to the surface when motion blur stretches the instance unevenly
+    normal: [[f32; 3]; 3],
 }
 
 impl InstanceRaw {
@@ -42,29 +58,178 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 20]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 23]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 26]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
 }
 
 impl Instance {
-    pub fn new(new_position: Vector3<f32>) -> Self {
-        let position = new_position;
+    /// The instance drawn for a simulated body
+    pub fn from_entity(entity: &Entity) -> Self {
+        Self {
+            // The bodies are simulated in f64, but drawn in f32
+            position: entity.position.map(|x| x as f32),
+            rotation: cgmath::Quaternion::one(),
+            velocity: entity.velocity.map(|x| x as f32),
+            radius: entity.radius,
+            color: entity.color,
+            emissive: entity.emissive,
+            texture: 0,
+        }
+    }
+
+    /// Whether `point` lies inside the sphere drawn for this instance
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        (point - self.position).magnitude() < self.radius
+    }
+
+    /// The instance as it's laid out in the instance buffer, elongated along its velocity to
+    /// fake motion blur. A strength of 0 disables the effect.
+    /// The model matrix is translation * stretch * rotation * scale, so the mesh is scaled
+    /// and turned about its own center before being stretched and moved into place, which
+    /// is its position less `origin`.
+    pub fn to_raw_blurred(&self, origin: Vector3<f32>, dt: f32, strength: f32) -> InstanceRaw {
+        let transform = motion_blur_stretch(self.velocity, dt, strength)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_scale(self.radius);
+        InstanceRaw {
+            model: (cgmath::Matrix4::from_translation(self.position - origin) * transform).into(),
+            color: self.color,
+            emissive: if self.emissive { 1.0 } else { 0.0 },
+            normal: normal_matrix(transform).into(),
+        }
+    }
+}
 
-        let rotation = if position.is_zero() {
-            cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-        } else {
-            cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+/// The matrix that carries normals through `model`: the inverse transpose of its upper 3x3.
+/// A degenerate model, e.g. a zero radius, has nothing to light, so it gets the identity
+pub fn normal_matrix(model: cgmath::Matrix4<f32>) -> cgmath::Matrix3<f32> {
+    let upper =
+        cgmath::Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+    upper
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or_else(cgmath::Matrix3::identity)
+}
+
+/// How big to draw a body of the given mass if every body had the same density,
+/// so the drawn size grows with the cube root of the mass.
+/// A body of `reference_mass` is drawn with `reference_radius`.
+pub fn radius_for_mass(mass: f32, reference_mass: f32, reference_radius: f32) -> f32 {
+    reference_radius * (mass / reference_mass).max(0.0).cbrt()
+}
+
+/// Builds a matrix that scales by `1 + speed * dt * strength` along the direction of `velocity`
+/// while leaving the perpendicular axes untouched.
+pub fn motion_blur_stretch(velocity: Vector3<f32>, dt: f32, strength: f32) -> cgmath::Matrix4<f32> {
+    let speed = velocity.magnitude();
+    if speed == 0.0 || strength <= 0.0 {
+        return cgmath::Matrix4::identity();
+    }
+
+    let dir = velocity / speed;
+    let stretch = speed * dt * strength;
+    // I + stretch * (dir * dir^T)
+    let outer = cgmath::Matrix3::from_cols(dir * dir.x, dir * dir.y, dir * dir.z);
+    (cgmath::Matrix3::identity() + outer * stretch).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation3, Zero};
+
+    /// The length `model` stretches `direction` to
+    fn stretched_length(model: [[f32; 4]; 4], direction: Vector3<f32>) -> f32 {
+        (cgmath::Matrix4::from(model) * direction.extend(0.0))
+            .truncate()
+            .magnitude()
+    }
+
+    #[test]
+    fn fast_body_is_stretched_along_its_velocity() {
+        let instance = Instance {
+            position: Vector3::zero(),
+            rotation: cgmath::Quaternion::one(),
+            velocity: Vector3::new(0.0, 30.0, 40.0),
+            radius: 2.0,
+            color: [1.0; 3],
+            emissive: false,
+            texture: 0,
         };
+        let (dt, strength) = (0.1, 0.5);
+        let raw = instance.to_raw_blurred(Vector3::zero(), dt, strength);
 
-        Self { position, rotation }
+        // 1 + speed * dt * strength = 1 + 50 * 0.1 * 0.5
+        let along = stretched_length(raw.model, instance.velocity.normalize());
+        assert!((along - 2.0 * 3.5).abs() < 1e-4, "stretched to {}", along);
+        let across = stretched_length(raw.model, Vector3::unit_x());
+        assert!((across - 2.0).abs() < 1e-4, "stretched to {}", across);
+
+        // Without blur it's just the radius every way
+        let still = instance.to_raw_blurred(Vector3::zero(), dt, 0.0);
+        let along = stretched_length(still.model, instance.velocity.normalize());
+        assert!((along - 2.0).abs() < 1e-4);
     }
 
-    pub fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
+    #[test]
+    fn rotation_turns_the_surface_and_its_normals() {
+        let instance = Instance {
+            position: Vector3::new(1.0, 2.0, 3.0),
+            rotation: cgmath::Quaternion::from_angle_y(Deg(90.0)),
+            velocity: Vector3::zero(),
+            radius: 2.0,
+            color: [1.0; 3],
+            emissive: false,
+            texture: 1,
+        };
+        let raw = instance.to_raw_blurred(Vector3::zero(), 0.0, 0.0);
+        let model = cgmath::Matrix4::from(raw.model);
+        let normal = cgmath::Matrix3::from(raw.normal);
+
+        // A quarter turn about y takes x to -z and z to x, and leaves y alone
+        let turns = [
+            (Vector3::unit_x(), -Vector3::unit_z()),
+            (Vector3::unit_y(), Vector3::unit_y()),
+            (Vector3::unit_z(), Vector3::unit_x()),
+        ];
+        for &(from, to) in &turns {
+            // The point of the mesh facing `from` ends up on the side facing `to`
+            let point = (model * from.extend(1.0)).truncate();
+            assert!((point - (instance.position + to * 2.0)).magnitude() < 1e-5);
+            // And its normal still points straight out of the surface there
+            let turned = (normal * from).normalize();
+            assert!(
+                (turned - to).magnitude() < 1e-5,
+                "{:?} -> {:?}",
+                from,
+                turned
+            );
         }
     }
 }
@@ -0,0 +1,97 @@
+// Compute shader that moves the bodies forward in time without leaving the GPU
+
+[[block]]
+struct Params {
+    count: u32;
+    dt: f32;
+    // How many floats apart instances are in the instance buffer
+    instance_stride: u32;
+    // How many instances are in the instance buffer
+    instance_count: u32;
+    // Taken off every instance's position, see Camera::render_origin
+    origin: vec3<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+// Each body is packed as xyz = position, w = mass, shared with gravity.wgsl
+[[block]]
+struct Bodies {
+    data: array<vec4<f32>>;
+};
+[[group(0), binding(1)]]
+var<storage, read_write> bodies: Bodies;
+
+// xyz = velocity, w is unused
+[[block]]
+struct Velocities {
+    data: array<vec4<f32>>;
+};
+[[group(0), binding(2)]]
+var<storage, read_write> velocities: Velocities;
+
+// Written by gravity.wgsl
+[[block]]
+struct Accelerations {
+    data: array<vec4<f32>>;
+};
+[[group(0), binding(3)]]
+var<storage, read> accelerations: Accelerations;
+
+// Which body each instance in the instance buffer draws
+[[block]]
+struct Order {
+    data: array<u32>;
+};
+[[group(1), binding(0)]]
+var<storage, read> order: Order;
+
+// The instance buffer seen as plain floats, since InstanceRaw isn't laid out
+// the way WGSL would lay out a struct
+[[block]]
+struct Instances {
+    data: array<f32>;
+};
+[[group(1), binding(1)]]
+var<storage, read_write> instances: Instances;
+
+// Velocity Verlet is split around the gravity pass: half a kick and a drift here,
+// then the other half kick once the new accelerations are in
+[[stage(compute), workgroup_size(64)]]
+fn kick_drift([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.count) {
+        return;
+    }
+    let velocity = velocities.data[index].xyz + accelerations.data[index].xyz * (params.dt * 0.5);
+    velocities.data[index] = vec4<f32>(velocity, 0.0);
+    let body = bodies.data[index];
+    bodies.data[index] = vec4<f32>(body.xyz + velocity * params.dt, body.w);
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn kick([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.count) {
+        return;
+    }
+    let velocity = velocities.data[index].xyz + accelerations.data[index].xyz * (params.dt * 0.5);
+    velocities.data[index] = vec4<f32>(velocity, 0.0);
+}
+
+// Moves each instance to where its body is now, relative to the origin. The translation is
+// the last column of the model matrix, which starts 12 floats into the instance
+[[stage(compute), workgroup_size(64)]]
+fn place_instances([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
+    let slot = global_id.x;
+    if (slot >= params.instance_count) {
+        return;
+    }
+    // ]] would read as the end of an attribute, so the index gets its own line
+    let body = order.data[slot];
+    let position = bodies.data[body].xyz - params.origin;
+    let start = slot * params.instance_stride + 12u;
+    instances.data[start] = position.x;
+    instances.data[start + 1u] = position.y;
+    instances.data[start + 2u] = position.z;
+}
@@ -0,0 +1,156 @@
+use crate::sphere::Entity;
+use cgmath::*;
+
+/// The ways we can advance bodies through time
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Integrator {
+    /// Moves then kicks using the accelerations at the start of the step.
+    /// Cheap, but orbits spiral outwards as energy builds up
+    Euler,
+    /// Moves using the starting accelerations, then averages the old and new accelerations
+    /// for the velocity update. Keeps orbits stable over long runs
+    VelocityVerlet,
+    /// Drifts half a step, kicks using the accelerations at the midpoint, then drifts again
+    Leapfrog,
+    /// Classic fourth-order Runge-Kutta, sampling gravity at the start, twice at the
+    /// midpoint and at the end of the step. Far more accurate per step than the others,
+    /// but computes gravity four times a step and slowly loses energy over very long runs
+    RungeKutta4,
+}
+
+impl Integrator {
+    /// Every integrator, in the order they're cycled through
+    pub const ALL: [Integrator; 4] = [
+        Integrator::Euler,
+        Integrator::VelocityVerlet,
+        Integrator::Leapfrog,
+        Integrator::RungeKutta4,
+    ];
+
+    /// The integrator after this one in ALL, wrapping around
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|i| i == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Advances the bodies by `dt` using `method`.
+/// `accelerations` are the accelerations at the bodies' current positions, and `gravity`
+/// recomputes them for a new set of positions. Returns the last accelerations computed,
+/// which for every method but Leapfrog are the ones at the new positions and can be passed
+/// straight back in on the next step, so Euler and VelocityVerlet only compute gravity once
+/// a step.
+/// The bodies are moved in f64, so rounding doesn't build up over long runs.
+pub fn step<F>(
+    entities: &mut [Entity],
+    accelerations: &[Vector3<f64>],
+    dt: f64,
+    method: Integrator,
+    mut gravity: F,
+) -> Vec<Vector3<f64>>
+where
+    F: FnMut(&[Entity]) -> Vec<Vector3<f64>>,
+{
+    match method {
+        Integrator::Euler => {
+            for (entity, acceleration) in entities.iter_mut().zip(accelerations) {
+                entity.position += entity.velocity * dt;
+                entity.velocity += acceleration * dt;
+            }
+            gravity(entities)
+        }
+        Integrator::VelocityVerlet => {
+            for (entity, acceleration) in entities.iter_mut().zip(accelerations) {
+                entity.position += entity.velocity * dt + acceleration * (0.5 * dt * dt);
+            }
+            let new_accelerations = gravity(entities);
+            for ((entity, old), new) in entities
+                .iter_mut()
+                .zip(accelerations)
+                .zip(&new_accelerations)
+            {
+                entity.velocity += (old + new) * (0.5 * dt);
+            }
+            new_accelerations
+        }
+        Integrator::Leapfrog => {
+            for entity in entities.iter_mut() {
+                entity.position += entity.velocity * (0.5 * dt);
+            }
+            let midpoint_accelerations = gravity(entities);
+            for (entity, acceleration) in entities.iter_mut().zip(&midpoint_accelerations) {
+                entity.velocity += acceleration * dt;
+                entity.position += entity.velocity * (0.5 * dt);
+            }
+            midpoint_accelerations
+        }
+        Integrator::RungeKutta4 => {
+            let start = entities
+                .iter()
+                .map(|entity| (entity.position, entity.velocity))
+                .collect::<Vec<_>>();
+            // The first slope is the starting velocities and accelerations. Each later one
+            // is sampled from a copy of the bodies moved along the slope before it
+            let mut velocities = start.iter().map(|&(_, v)| v).collect::<Vec<_>>();
+            let mut slopes = accelerations.to_vec();
+            let mut position_sum = velocities.clone();
+            let mut velocity_sum = slopes.clone();
+            let mut stage = entities.to_vec();
+            for &(fraction, weight) in &[(0.5, 2.0), (0.5, 2.0), (1.0, 1.0)] {
+                for (i, body) in stage.iter_mut().enumerate() {
+                    body.position = start[i].0 + velocities[i] * (fraction * dt);
+                    body.velocity = start[i].1 + slopes[i] * (fraction * dt);
+                }
+                velocities = stage.iter().map(|body| body.velocity).collect();
+                slopes = gravity(&stage);
+                for i in 0..stage.len() {
+                    position_sum[i] += velocities[i] * weight;
+                    velocity_sum[i] += slopes[i] * weight;
+                }
+            }
+            for (i, entity) in entities.iter_mut().enumerate() {
+                entity.position = start[i].0 + position_sum[i] * (dt / 6.0);
+                entity.velocity = start[i].1 + velocity_sum[i] * (dt / 6.0);
+            }
+            gravity(entities)
+        }
+    }
+}
+
+/// Picks the length of each physics step from how hard the bodies are being pulled,
+/// so close encounters get small, accurate steps and quiet stretches get large, cheap ones
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveTimestep {
+    /// How much of the shortest encounter time a step may cover.
+    /// Smaller values are more accurate and take more steps
+    pub eta: f32,
+    /// The shortest step that will be taken, however close the bodies get
+    pub min_dt: f64,
+    /// The longest step that will be taken, however far apart the bodies are
+    pub max_dt: f64,
+}
+
+impl Default for AdaptiveTimestep {
+    fn default() -> Self {
+        Self {
+            eta: 0.1,
+            min_dt: 1.0 / 7680.0,
+            max_dt: 1.0 / 60.0,
+        }
+    }
+}
+
+impl AdaptiveTimestep {
+    /// The step to take next: eta * min(sqrt(softening / |a|)) over every body,
+    /// clamped between min_dt and max_dt. Bodies feeling no pull don't limit the step
+    pub fn next_dt(&self, accelerations: &[Vector3<f64>], softening: f32) -> f64 {
+        let (eta, softening) = (f64::from(self.eta), f64::from(softening));
+        let dt = accelerations
+            .iter()
+            .map(|acceleration| acceleration.magnitude())
+            .filter(|&magnitude| magnitude > 0.0)
+            .map(|magnitude| eta * (softening / magnitude).sqrt())
+            .fold(self.max_dt, f64::min);
+        dt.clamp(self.min_dt, self.max_dt)
+    }
+}
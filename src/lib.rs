@@ -0,0 +1,32 @@
+//! The simulation behind nbodysim. None of it needs a window or GPU, so it can be
+//! driven headless, the way `--headless` and the tests do.
+#![warn(missing_docs)]
+
+/// Tinting bodies by their speed, mass or surroundings
+pub mod colormap;
+/// Working out which bodies a camera can see
+pub mod frustum;
+/// Moving bodies forward in time
+pub mod integrator;
+/// The octree Barnes-Hut gravity groups distant bodies with
+pub mod octree;
+/// Gravity, collisions, energy and the orbital formulas
+pub mod physics;
+/// Working out which body is under the cursor
+pub mod picking;
+/// The starting conditions of a simulation, loaded from RON files or generated
+pub mod scene;
+/// The bodies and physics settings, stepped together
+pub mod simulation;
+/// Saving and restoring a simulation mid-run
+pub mod snapshot;
+/// Finding bodies that are close together by bucketing them into a grid
+pub mod spatial_hash;
+/// The bodies, and the sphere mesh they're drawn with
+pub mod sphere;
+/// Turning the time that passes each frame into physics steps
+pub mod stepper;
+/// Recording bodies' paths and writing them out as CSV or NPY
+pub mod trajectory;
+/// The units scenes are described in
+pub mod units;
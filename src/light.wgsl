@@ -7,13 +7,21 @@ struct Camera {
 [[group(0), binding(0)]]
 var<uniform> camera: Camera;
 
-[[block]]
 struct Light {
     position: vec3<f32>;
+    constant: f32;
     color: vec3<f32>;
+    linear: f32;
+    quadratic: f32;
+};
+
+// The orbiting light is always the first
+[[block]]
+struct Lights {
+    data: array<Light, 8>;
 };
-[[group(1), binding(0)]]
-var<uniform> light: Light;
+[[group(1), binding(1)]]
+var<storage, read> lights: Lights;
 
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
@@ -29,6 +37,7 @@ fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     let scale = 0.25;
+    let light = lights.data[0];
     var out: VertexOutput;
     out.clip_position = camera.view_proj * vec4<f32>(model.position * scale + light.position, 1.0);
     out.color = light.color;
@@ -1,58 +1,781 @@
+//! A gravitational n-body simulator rendered with wgpu.
 #![warn(missing_docs)]
 
-use crate::sphere::{DrawSphere, Vertex};
+use crate::integrator::Integrator;
+use crate::simulation::Simulation;
+use crate::sphere::{BodyType, DrawSphere, Vertex};
 use crate::state::State;
-use cgmath::*;
-use image::GenericImageView;
-use wgpu::util::DeviceExt;
+use anyhow::Context;
+use clap::Parser;
+// The simulation lives in the library so it can be tested without a window
+use nbodysim::{
+    colormap, frustum, integrator, physics, picking, scene, simulation, snapshot, sphere, stepper,
+    trajectory,
+};
+use std::path::PathBuf;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
     window::*,
 };
 
+mod bloom;
 mod camera;
+mod compute;
+mod frame_timer;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gpu;
+mod grid;
+mod hud;
 mod instance;
+mod record;
 mod render;
-mod sphere;
+#[cfg(feature = "dev")]
+mod shader_watch;
 mod state;
 mod texture;
+mod trail;
+
+/// Where F5 saves the simulation to and F9 loads it from
+const SNAPSHOT_PATH: &str = "snapshot.ron";
+
+/// The command line. Anything left out falls back to the scene's own settings
+#[derive(clap::Parser)]
+#[clap(about = "A gravitational n-body simulator")]
+struct Args {
+    /// The scene file to simulate. Without one the resonance demo is simulated
+    #[clap(long, value_name = "PATH")]
+    scene: Option<PathBuf>,
+    /// An equirectangular image to wrap around every planet that doesn't have a texture
+    /// of its own
+    #[clap(long, value_name = "PATH", conflicts_with = "headless")]
+    planet_texture: Option<PathBuf>,
+    /// Simulate a random cloud of this many bodies instead of a scene
+    #[clap(long, alias = "spawn", value_name = "N", conflicts_with = "scene")]
+    bodies: Option<usize>,
+    /// Simulate a built-in scene instead of a scene file
+    /// [possible values: binary, figure-eight, pythagorean, solar-system]
+    #[clap(
+        long,
+        value_name = "NAME",
+        value_parser = parse_preset,
+        conflicts_with_all = &["scene", "bodies"]
+    )]
+    preset: Option<scene::Preset>,
+    /// Simulate a star and two planets whose periods are in this ratio, like 2:1, instead
+    /// of a scene file. This resonance demo is also what runs when no scene is given
+    #[clap(
+        long,
+        value_name = "OUTER:INNER",
+        value_parser = parse_ratio,
+        conflicts_with_all = &["scene", "bodies", "preset"]
+    )]
+    resonance: Option<(u32, u32)>,
+    /// The seed of the random cloud. The same seed always gives the same cloud
+    #[clap(long, default_value_t = 0, requires = "bodies")]
+    seed: u64,
+    /// Spin the random cloud until its total angular momentum is this. Large values
+    /// flatten it into a disk as it collapses
+    #[clap(
+        long,
+        value_name = "X,Y,Z",
+        value_parser = parse_vector,
+        allow_hyphen_values = true,
+        requires = "bodies"
+    )]
+    angular_momentum: Option<cgmath::Vector3<f32>>,
+    /// What to do with bodies that start out overlapping in a scene file or random cloud:
+    /// nudge pushes them apart until they just touch, merge combines them into one body
+    #[clap(
+        long,
+        value_name = "POLICY",
+        default_value = "nudge",
+        value_parser = parse_overlap_policy
+    )]
+    overlaps: scene::OverlapPolicy,
+    /// How much simulated time each physics step covers
+    #[clap(
+        long,
+        default_value_t = state::DEFAULT_DT,
+        value_parser = parse_dt,
+        allow_hyphen_values = true
+    )]
+    dt: f64,
+    /// How the bodies are moved forward in time [possible values: euler, verlet, leapfrog,
+    /// rk4]. J cycles through them in the window
+    #[clap(long, value_name = "NAME", value_parser = parse_integrator)]
+    integrator: Option<Integrator>,
+    /// The strength of gravity, replacing the one picked by the scene's units
+    #[clap(long, value_parser = parse_positive, allow_hyphen_values = true)]
+    g: Option<f32>,
+    /// The most physics steps a frame can take. A frame needing more drops the rest of its
+    /// time, so the simulation runs in slow motion instead of falling further and further
+    /// behind
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = stepper::DEFAULT_MAX_SUBSTEPS_PER_FRAME,
+        value_parser = parse_max_substeps,
+        conflicts_with = "headless"
+    )]
+    max_substeps: usize,
+    /// Keeps the pull between bodies finite as they pass through each other
+    #[clap(long, value_parser = parse_non_negative, allow_hyphen_values = true)]
+    softening: Option<f32>,
+    /// Works out gravity with a Barnes-Hut octree, treating groups of bodies that look
+    /// smaller than this, their size over their distance, as a single mass. Much faster for
+    /// thousands of bodies; 0.5 is a common choice, and 0 is exact. Not used when gravity is
+    /// computed on the GPU
+    #[clap(long, value_parser = parse_non_negative, allow_hyphen_values = true)]
+    theta: Option<f32>,
+    /// Treats bodies closer than this as being this far apart, so close passes can't fling
+    /// them off. Turns the separation clamp on, which N toggles in the window
+    #[clap(
+        long,
+        value_name = "LENGTH",
+        value_parser = parse_positive,
+        allow_hyphen_values = true
+    )]
+    min_separation: Option<f32>,
+    /// Take out the bodies' net momentum before starting, so the system as a whole stays
+    /// put instead of drifting. Y looks at the center of mass in the window
+    #[clap(long)]
+    zero_momentum: bool,
+    /// Let bodies pass through each other instead of merging when they touch.
+    /// X toggles merging in the window
+    #[clap(long)]
+    no_merge: bool,
+    /// How fast Shift+X kicks every body away from their center of mass in the window
+    #[clap(
+        long,
+        value_name = "SPEED",
+        default_value_t = state::DEFAULT_EXPLODE_SPEED,
+        value_parser = parse_positive,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    explode_speed: f32,
+    /// Simulate as fast as possible without a window, printing the energy as it goes
+    #[clap(long)]
+    headless: bool,
+    /// How many steps a headless run takes
+    #[clap(long, default_value_t = 1000, requires = "headless")]
+    steps: usize,
+    /// How many steps apart a headless run takes snapshots
+    #[clap(long, default_value_t = 100, requires = "headless")]
+    interval: usize,
+    /// Where a headless run writes every snapshot's bodies as CSV
+    #[clap(long, value_name = "PATH", requires = "headless")]
+    csv: Option<PathBuf>,
+    /// Record the paths of the bodies picked with --track and write them here when the run
+    /// ends, as NPY if the file ends in .npy and CSV otherwise
+    #[clap(long, value_name = "PATH")]
+    trajectory: Option<PathBuf>,
+    /// Which bodies --trajectory follows, by their index when the run starts, like 0,2.
+    /// Without it every body is followed
+    #[clap(
+        long,
+        value_name = "INDICES",
+        use_value_delimiter = true,
+        requires = "trajectory"
+    )]
+    track: Vec<usize>,
+    /// How many steps apart --trajectory records the bodies
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = parse_max_substeps,
+        requires = "trajectory"
+    )]
+    trajectory_interval: usize,
+    /// Record the bodies' velocities along with their positions
+    #[clap(long, requires = "trajectory")]
+    trajectory_velocity: bool,
+    /// A flat background color, replacing whatever the scene asks for
+    #[clap(
+        long,
+        value_name = "RRGGBB",
+        value_parser = parse_background,
+        conflicts_with = "headless"
+    )]
+    bg: Option<render::Background>,
+    /// How close to the camera things can be drawn. Setting this or --zfar fixes the
+    /// clipping planes instead of fitting them around the scene every frame
+    #[clap(
+        long,
+        value_name = "DISTANCE",
+        value_parser = parse_positive,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    znear: Option<f32>,
+    /// How far from the camera things can be drawn, see --znear
+    #[clap(
+        long,
+        value_name = "DISTANCE",
+        value_parser = parse_positive,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    zfar: Option<f32>,
+    /// Draw a fixed number of frames offscreen as fast as possible and write them to this
+    /// directory as frame_00001.png, frame_00002.png and so on, then quit
+    #[clap(long, value_name = "DIR", conflicts_with = "headless")]
+    record: Option<PathBuf>,
+    /// How many frames a recording writes
+    #[clap(long, default_value_t = 600, requires = "record")]
+    frames: usize,
+    /// How many recorded frames make up a second of simulated time. Without it each frame
+    /// moves the simulation on by a single step
+    #[clap(
+        long,
+        value_parser = parse_positive,
+        allow_hyphen_values = true,
+        requires = "record"
+    )]
+    fps: Option<f32>,
+    /// The size of the recorded frames, whatever size the window is
+    #[clap(
+        long,
+        value_name = "WIDTHxHEIGHT",
+        value_parser = parse_resolution,
+        requires = "record"
+    )]
+    resolution: Option<(u32, u32)>,
+    /// Stretch fast bodies along their velocity, by their speed times the frame time times
+    /// this, so they read as moving in still frames. 0, the default, turns it off
+    #[clap(
+        long,
+        value_name = "STRENGTH",
+        value_parser = parse_non_negative,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    motion_blur: Option<f32>,
+    /// Tint bodies redder the deeper they sit in the others' gravity wells, this strongly.
+    /// A made-up stand-in for time dilation, not a physical redshift. Ignored while
+    /// --color-by tints bodies by something else
+    #[clap(
+        long,
+        value_name = "STRENGTH",
+        value_parser = parse_non_negative,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    redshift: Option<f32>,
+    /// Draw a wireframe shell around each body at its Hill radius, inside which its own
+    /// gravity wins out over the heavier body it orbits. Ctrl+H toggles them in the window
+    #[clap(long, conflicts_with = "headless")]
+    hill_spheres: bool,
+    /// How many of each body's past positions its trail holds, up to 1024
+    #[clap(long, value_name = "N", conflicts_with = "headless")]
+    trail_length: Option<usize>,
+    /// How far apart the lines of the grid H shows are. Starts the grid showing
+    #[clap(
+        long,
+        value_name = "SPACING",
+        value_parser = parse_positive,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    grid_spacing: Option<f32>,
+    /// Draw bodies as flat round sprites instead of spheres, for scenes with far too many
+    /// bodies to draw as spheres. M switches between the two in the window
+    #[clap(long, conflicts_with = "headless")]
+    points: bool,
+    /// How many vertices along each edge of the cube faces bodies are built from, replacing
+    /// whatever the scene asks for. Lower is faster and blockier, and 2 draws cubes
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = parse_sphere_resolution,
+        conflicts_with = "headless"
+    )]
+    sphere_res: Option<u32>,
+    /// Tint bodies by a value instead of their own colors: fixed, speed, mass or density.
+    /// U cycles through them in the window
+    #[clap(
+        long,
+        value_name = "VALUE",
+        value_parser = parse_color_by,
+        conflicts_with = "headless"
+    )]
+    color_by: Option<colormap::ColorBy>,
+    /// The colors --color-by tints bodies with, viridis or turbo. Ctrl+U switches between
+    /// them in the window
+    #[clap(long, value_parser = parse_colormap, conflicts_with = "headless")]
+    colormap: Option<colormap::Colormap>,
+    /// The values the ends of the colormap stand for. Without it they're the lowest and
+    /// highest among the bodies each frame
+    #[clap(
+        long,
+        value_name = "MIN,MAX",
+        value_parser = parse_color_range,
+        allow_hyphen_values = true,
+        conflicts_with = "headless"
+    )]
+    color_range: Option<(f32, f32)>,
+}
+
+impl Args {
+    /// The scene to start from: a random cloud, preset or resonance if asked for,
+    /// otherwise the scene file, otherwise the resonance demo
+    fn scene(&self) -> anyhow::Result<scene::Scene> {
+        let mut scene = if let Some(preset) = self.preset {
+            scene::Scene::preset(preset)
+        } else if let Some(ratio) = self.resonance {
+            scene::Scene::resonance_demo(ratio)
+        } else {
+            match (self.bodies, &self.scene) {
+                (Some(n), _) => {
+                    let params = scene::CloudParams {
+                        angular_momentum: self.angular_momentum,
+                        overlaps: self.overlaps,
+                        ..Default::default()
+                    };
+                    scene::Scene::random_cloud(n, self.seed, params)
+                }
+                (None, Some(path)) => {
+                    let mut scene = scene::Scene::load(path)?;
+                    scene.resolve_initial_overlaps(self.overlaps);
+                    scene
+                }
+                (None, None) => scene::Scene::resonance_demo((2, 1)),
+            }
+        };
+        if let Some(texture) = &self.planet_texture {
+            for body in &mut scene.bodies {
+                if body.body_type == BodyType::Planet && body.texture.is_none() {
+                    body.texture = Some(texture.clone());
+                }
+            }
+        }
+        Ok(scene)
+    }
+
+    /// Sets up `scene` with the physics settings given on the command line
+    fn simulation(&self, scene: &scene::Scene) -> Simulation {
+        let mut simulation = Simulation::new(scene, self.dt);
+        if let Some(integrator) = self.integrator {
+            simulation.integrator = integrator;
+        }
+        if let Some(g) = self.g {
+            simulation.gravitational_constant = g;
+        }
+        if let Some(softening) = self.softening {
+            simulation.softening = softening;
+        }
+        if let Some(theta) = self.theta {
+            simulation.gravity_strategy = physics::GravityStrategy::BarnesHut { theta };
+        }
+        simulation.merge_on_collision = !self.no_merge;
+        if self.zero_momentum {
+            physics::remove_net_momentum(&mut simulation.entities);
+        }
+        if let Some(separation) = self.min_separation {
+            simulation.clamp_separation = true;
+            simulation.min_separation = separation;
+        }
+        if self.trajectory.is_some() {
+            let bodies = if self.track.is_empty() {
+                (0..simulation.entities.len()).collect()
+            } else {
+                self.track.clone()
+            };
+            let mut recorder =
+                trajectory::TrajectoryRecorder::new(bodies, self.trajectory_interval);
+            recorder.record_velocity = self.trajectory_velocity;
+            simulation.trajectory = Some(recorder);
+        }
+        simulation.refresh_accelerations();
+        simulation
+    }
+
+    /// The fixed clipping planes asked for, filling in whichever wasn't given with its
+    /// default, or None to fit them around the scene
+    fn depth_range(&self) -> anyhow::Result<Option<(f32, f32)>> {
+        if self.znear.is_none() && self.zfar.is_none() {
+            return Ok(None);
+        }
+        let znear = self.znear.unwrap_or(camera::DEFAULT_ZNEAR);
+        let zfar = self.zfar.unwrap_or(camera::DEFAULT_ZFAR);
+        if zfar <= znear {
+            anyhow::bail!("--zfar ({}) must be beyond --znear ({})", zfar, znear);
+        }
+        Ok(Some((znear, zfar)))
+    }
+}
+
+fn parse_integrator(name: &str) -> Result<Integrator, String> {
+    match name {
+        "euler" => Ok(Integrator::Euler),
+        "verlet" => Ok(Integrator::VelocityVerlet),
+        "leapfrog" => Ok(Integrator::Leapfrog),
+        "rk4" => Ok(Integrator::RungeKutta4),
+        _ => Err("expected one of euler, verlet, leapfrog or rk4".to_string()),
+    }
+}
+
+fn parse_overlap_policy(name: &str) -> Result<scene::OverlapPolicy, String> {
+    match name {
+        "nudge" => Ok(scene::OverlapPolicy::Nudge),
+        "merge" => Ok(scene::OverlapPolicy::Merge),
+        _ => Err("expected nudge or merge".to_string()),
+    }
+}
+
+fn parse_preset(name: &str) -> Result<scene::Preset, String> {
+    scene::Preset::from_name(name).ok_or_else(|| {
+        let names = scene::Preset::ALL.map(|preset| preset.name());
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn parse_color_by(name: &str) -> Result<colormap::ColorBy, String> {
+    colormap::ColorBy::from_name(name).ok_or_else(|| {
+        let names = colormap::ColorBy::ALL.map(|color_by| color_by.name());
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn parse_colormap(name: &str) -> Result<colormap::Colormap, String> {
+    colormap::Colormap::from_name(name).ok_or_else(|| {
+        let names = colormap::Colormap::ALL.map(|colormap| colormap.name());
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number > 0.0 => Ok(number),
+        _ => Err("expected a number above 0".to_string()),
+    }
+}
+
+/// Step lengths are kept in f64 like the rest of the simulated time
+fn parse_dt(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok(number),
+        _ => Err("expected a number above 0".to_string()),
+    }
+}
+
+fn parse_non_negative(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number >= 0.0 => Ok(number),
+        _ => Err("expected a number of at least 0".to_string()),
+    }
+}
+
+fn parse_max_substeps(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(steps) if steps > 0 => Ok(steps),
+        _ => Err("expected a whole number above 0".to_string()),
+    }
+}
+
+fn parse_sphere_resolution(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(resolution) if resolution >= sphere::MIN_SPHERE_RESOLUTION => Ok(resolution),
+        _ => Err(format!(
+            "expected a whole number of at least {}",
+            sphere::MIN_SPHERE_RESOLUTION
+        )),
+    }
+}
 
-fn main() {
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let size = value
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    match size {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err("expected a size like 1920x1080".to_string()),
+    }
+}
+
+fn parse_ratio(value: &str) -> Result<(u32, u32), String> {
+    let ratio = value
+        .split_once(':')
+        .and_then(|(outer, inner)| Some((outer.parse().ok()?, inner.parse().ok()?)));
+    match ratio {
+        Some((outer, inner)) if outer > 0 && inner > 0 => Ok((outer, inner)),
+        _ => Err("expected a ratio of whole numbers like 2:1".to_string()),
+    }
+}
+
+fn parse_color_range(value: &str) -> Result<(f32, f32), String> {
+    let range = value
+        .split_once(',')
+        .and_then(|(min, max)| Some((min.parse::<f32>().ok()?, max.parse::<f32>().ok()?)));
+    match range {
+        Some((min, max)) if min < max => Ok((min, max)),
+        _ => Err("expected two numbers like 0,10, the first below the second".to_string()),
+    }
+}
+
+fn parse_vector(value: &str) -> Result<cgmath::Vector3<f32>, String> {
+    let components = value
+        .split(',')
+        .map(|component| component.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    match components.as_deref() {
+        Ok(&[x, y, z]) => Ok(cgmath::Vector3::new(x, y, z)),
+        _ => Err("expected three numbers like 0,100,0".to_string()),
+    }
+}
+
+fn parse_background(hex: &str) -> Result<render::Background, String> {
+    render::Background::from_hex(hex).map_err(|e| e.to_string())
+}
+
+/// Runs the simulation as fast as possible with no window or GPU,
+/// printing the time, body count and energy of each snapshot
+/// and writing its bodies to a CSV file if one was asked for
+fn headless(args: &Args, mut simulation: Simulation) -> anyhow::Result<()> {
+    let mut csv = match &args.csv {
+        Some(path) => Some(trajectory::CsvWriter::create(path)?),
+        None => None,
+    };
+    let snapshots = simulation::headless_snapshots(&mut simulation, args.steps, args.interval);
+    for snapshot in snapshots {
+        if let Some(csv) = csv.as_mut() {
+            csv.write_bodies(snapshot.time, &snapshot.bodies)?;
+        }
+        let energy = physics::total_energy(
+            &snapshot.bodies,
+            snapshot.gravitational_constant,
+            snapshot.softening,
+        );
+        println!(
+            "time {} bodies {} energy {}",
+            snapshot.time,
+            snapshot.bodies.len(),
+            energy
+        );
+    }
+    if let Some(csv) = csv {
+        csv.finish()?;
+    }
+    save_trajectory(args, &simulation)
+}
+
+/// Writes the paths recorded for --trajectory, if it was given
+fn save_trajectory(args: &Args, simulation: &Simulation) -> anyhow::Result<()> {
+    match (&args.trajectory, &simulation.trajectory) {
+        (Some(path), Some(recorder)) => recorder.save(path),
+        _ => Ok(()),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
     env_logger::init();
+
+    let args = Args::parse();
+    let mut scene = args.scene()?;
+    if let Some(resolution) = args.sphere_res {
+        scene.sphere_resolution = Some(resolution);
+    }
+    let simulation = args.simulation(&scene);
+    if args.headless {
+        return headless(&args, simulation);
+    }
+
+    let depth_range = args.depth_range()?;
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    // Recordings are drawn offscreen, so there's nothing to show in the window
+    let window = WindowBuilder::new()
+        .with_visible(args.record.is_none())
+        .build(&event_loop)
+        .context("couldn't open a window")?;
 
-    let mut state = pollster::block_on(State::new(&window));
+    let mut state = pollster::block_on(State::new(&window, &scene, simulation))?;
+    if let Some(background) = args.bg {
+        state.renderer.background = background;
+    }
+    state.stepper.max_substeps_per_frame = args.max_substeps;
+    state.explode_speed = args.explode_speed;
+    if let Some(strength) = args.motion_blur {
+        state.renderer.motion_blur = strength;
+    }
+    if let Some(strength) = args.redshift {
+        state.renderer.redshift_tint = strength;
+    }
+    state.renderer.show_hill_spheres = args.hill_spheres;
+    if let Some(length) = args.trail_length {
+        state.set_trail_length(length);
+    }
+    if let Some(spacing) = args.grid_spacing {
+        let renderer = &mut state.renderer;
+        renderer.set_grid(&state.device, renderer.grid_extent, spacing);
+        renderer.show_grid = true;
+    }
+    if args.points {
+        state.renderer.style = render::RenderStyle::Points;
+    }
+    let color_scale = &mut state.renderer.color_scale;
+    if let Some(color_by) = args.color_by {
+        color_scale.color_by = color_by;
+    }
+    if let Some(colormap) = args.colormap {
+        color_scale.colormap = colormap;
+    }
+    if let Some((min, max)) = args.color_range {
+        color_scale.range = colormap::ColorRange::Fixed(min, max);
+    }
+    if let Some((znear, zfar)) = depth_range {
+        let camera = &mut state.renderer.camera;
+        camera.auto_depth_range = false;
+        camera.set_depth_range(znear, zfar)?;
+    }
+    let mut recorder = match &args.record {
+        Some(directory) => {
+            if let Some((width, height)) = args.resolution {
+                state.set_render_size(width, height)?;
+            }
+            let frame_time = args.fps.map_or(state.simulation.dt as f32, |fps| 1.0 / fps);
+            Some(record::Recorder::new(directory, args.frames, frame_time)?)
+        }
+        None => None,
+    };
+    // Carrying on without a gamepad if they can't be read, since the keyboard still works
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = gamepad::Gamepad::new()
+        .map_err(|e| eprintln!("{:?}", e))
+        .ok();
+
+    // Shaders still work from the compiled-in copy if the file can't be watched
+    #[cfg(feature = "dev")]
+    let mut shader_watcher =
+        shader_watch::ShaderWatcher::new(std::path::Path::new(shader_watch::SHADER_PATH))
+            .map_err(|e| eprintln!("{:?}", e))
+            .ok();
 
     event_loop.run(move |event, _, control_flow| {
+        // However the window was closed, the recorded paths are written out on the way
+        if let Event::LoopDestroyed = event {
+            state.sync_from_gpu();
+            if let Err(e) = save_trajectory(&args, &state.simulation) {
+                eprintln!("{:?}", e);
+            }
+            return;
+        }
+        // Nothing but closing the window gets in the way of a recording, so it comes out the
+        // same every time
+        if let Some(recorder) = recorder.as_mut() {
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                Event::MainEventsCleared => match recorder.record_frame(&mut state) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        eprintln!("Recorded {} frames", recorder.written());
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    Err(e) => {
+                        eprintln!("{:?}", e);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                },
+                _ => {}
+            }
+            return;
+        }
+        // The HUD sees every event, and keeps the ones it uses from reaching the camera
+        state.hud.handle_event(&event);
+        let captured = state.hud.captures_event(&event);
         match event {
+            // state event take priority over window events
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == window.id() => {
-                // state event take priority over window events
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
+            } if window_id == window.id() && !captured && !state.input(event) => match event {
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F5),
                             ..
-                        } => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            state.resize(**new_inner_size);
-                        }
-                        _ => {}
+                        },
+                    ..
+                } => {
+                    if let Err(e) = state.save_snapshot(std::path::Path::new(SNAPSHOT_PATH)) {
+                        eprintln!("{:?}", e);
                     }
                 }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F9),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Err(e) = state.load_snapshot(std::path::Path::new(SNAPSHOT_PATH)) {
+                        eprintln!("{:?}", e);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F12),
+                            ..
+                        },
+                    ..
+                } => {
+                    // Named after the time it was taken so earlier screenshots aren't overwritten
+                    let seconds = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|time| time.as_secs())
+                        .unwrap_or_default();
+                    let path = format!("screenshot-{}.png", seconds);
+                    if let Err(e) = state.capture_screenshot(std::path::Path::new(&path)) {
+                        eprintln!("{:?}", e);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::V),
+                            ..
+                        },
+                    ..
+                } => {
+                    // The flying camera looks around with raw mouse motion, so the cursor
+                    // is kept in the window and out of sight while flying
+                    let flying = state.toggle_camera_mode();
+                    if let Err(e) = window.set_cursor_grab(flying) {
+                        eprintln!("{:?}", e);
+                    }
+                    window.set_cursor_visible(!flying);
+                }
+                WindowEvent::Resized(physical_size) => {
+                    state.resize(*physical_size);
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    state.resize(**new_inner_size);
+                }
+                _ => {}
+            },
+            Event::DeviceEvent { ref event, .. } => {
+                state.device_input(event);
             }
             Event::RedrawRequested(_) => {
                 state.update();
@@ -68,6 +791,27 @@ fn main() {
                 }
             }
             Event::MainEventsCleared => {
+                // A bad shader is only reported, leaving the last good one drawing
+                #[cfg(feature = "dev")]
+                match shader_watcher.as_mut().and_then(|watcher| watcher.poll()) {
+                    Some(Ok(source)) => {
+                        state.set_body_shader(&source);
+                        eprintln!("Reloaded {}", shader_watch::SHADER_PATH);
+                    }
+                    Some(Err(e)) => eprintln!("{:?}", e),
+                    None => {}
+                }
+                #[cfg(feature = "gamepad")]
+                if let Some(gamepad) = gamepad.as_mut() {
+                    let input = gamepad.poll();
+                    state
+                        .renderer
+                        .camera_controller
+                        .apply_analog_input(input.camera);
+                    if input.toggle_pause {
+                        state.toggle_pause();
+                    }
+                }
                 // RedrawRequested will only trigger once, unless manually requested
                 window.request_redraw();
             }
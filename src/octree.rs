@@ -0,0 +1,227 @@
+use crate::physics;
+use crate::sphere::Entity;
+use cgmath::*;
+
+/// How deep the tree can go. Bodies sharing a position would otherwise split forever,
+/// so once this deep they're kept together in the same leaf
+const MAX_DEPTH: u32 = 32;
+
+/// A cube of space holding either up to one body or eight smaller cubes
+struct Node {
+    /// The geometric center of the cube
+    center: Vector3<f64>,
+    /// Half the length of the cube's sides
+    half_size: f64,
+    /// The total mass of every body in the cube
+    mass: f64,
+    /// Where that mass is centered
+    center_of_mass: Vector3<f64>,
+    /// The index of the first of eight consecutive children, if the node has been split
+    children: Option<usize>,
+    /// The bodies in a leaf node
+    bodies: Vec<usize>,
+}
+
+impl Node {
+    fn new(center: Vector3<f64>, half_size: f64) -> Self {
+        Self {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: Vector3::zero(),
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    /// Which of the eight children `position` falls into
+    fn octant(&self, position: Vector3<f64>) -> usize {
+        (position.x >= self.center.x) as usize
+            | ((position.y >= self.center.y) as usize) << 1
+            | ((position.z >= self.center.z) as usize) << 2
+    }
+}
+
+/// A Barnes-Hut octree, which brings computing gravity from O(n^2) down to O(n log n)
+/// by treating far away groups of bodies as a single mass at their center of mass
+pub struct Octree {
+    nodes: Vec<Node>,
+    positions: Vec<Vector3<f64>>,
+    masses: Vec<f64>,
+}
+
+impl Octree {
+    /// Builds the tree around the current positions and masses of the bodies.
+    /// Meant to be rebuilt every step as the bodies move
+    pub fn new(entities: &[Entity]) -> Self {
+        let positions = entities.iter().map(|e| e.position).collect::<Vec<_>>();
+        let masses = entities
+            .iter()
+            .map(|e| f64::from(e.mass))
+            .collect::<Vec<_>>();
+
+        // The root is the smallest cube around every body
+        let (min, max) = positions.iter().fold(
+            (
+                Vector3::from_value(f64::INFINITY),
+                Vector3::from_value(f64::NEG_INFINITY),
+            ),
+            |(min, max), p| {
+                (
+                    Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                    Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                )
+            },
+        );
+        let (center, half_size) = if positions.is_empty() {
+            (Vector3::zero(), 0.0)
+        } else {
+            let extent = max - min;
+            (
+                (min + max) * 0.5,
+                extent.x.max(extent.y).max(extent.z) * 0.5,
+            )
+        };
+
+        let mut tree = Self {
+            nodes: vec![Node::new(center, half_size)],
+            positions,
+            masses,
+        };
+        for body in 0..tree.positions.len() {
+            tree.insert(0, body, 0);
+        }
+        tree.compute_mass(0);
+        tree
+    }
+
+    fn insert(&mut self, node: usize, body: usize, depth: u32) {
+        if let Some(first_child) = self.nodes[node].children {
+            let octant = self.nodes[node].octant(self.positions[body]);
+            self.insert(first_child + octant, body, depth + 1);
+            return;
+        }
+
+        if self.nodes[node].bodies.is_empty() || depth >= MAX_DEPTH {
+            self.nodes[node].bodies.push(body);
+            return;
+        }
+
+        // The leaf is taken, so split it and push both bodies further down
+        self.split(node);
+        let existing = std::mem::take(&mut self.nodes[node].bodies);
+        for other in existing {
+            self.insert(node, other, depth);
+        }
+        self.insert(node, body, depth);
+    }
+
+    fn split(&mut self, node: usize) {
+        let Node {
+            center, half_size, ..
+        } = self.nodes[node];
+        let quarter = half_size * 0.5;
+        let first_child = self.nodes.len();
+        for octant in 0..8 {
+            let offset = |bit: usize| if octant & bit != 0 { quarter } else { -quarter };
+            let child_center = center + Vector3::new(offset(1), offset(2), offset(4));
+            self.nodes.push(Node::new(child_center, quarter));
+        }
+        self.nodes[node].children = Some(first_child);
+    }
+
+    /// Fills in the total mass and center of mass of every node, from the leaves up
+    fn compute_mass(&mut self, node: usize) -> (f64, Vector3<f64>) {
+        let (mass, weighted) = match self.nodes[node].children {
+            Some(first_child) => (first_child..first_child + 8).fold(
+                (0.0, Vector3::zero()),
+                |(mass, weighted), child| {
+                    let (child_mass, child_center) = self.compute_mass(child);
+                    (mass + child_mass, weighted + child_center * child_mass)
+                },
+            ),
+            None => self.nodes[node].bodies.iter().fold(
+                (0.0, Vector3::zero()),
+                |(mass, weighted), &body| {
+                    (
+                        mass + self.masses[body],
+                        weighted + self.positions[body] * self.masses[body],
+                    )
+                },
+            ),
+        };
+        let center_of_mass = if mass > 0.0 {
+            weighted / mass
+        } else {
+            self.nodes[node].center
+        };
+        self.nodes[node].mass = mass;
+        self.nodes[node].center_of_mass = center_of_mass;
+        (mass, center_of_mass)
+    }
+
+    /// The gravitational acceleration at `position` from every body in the tree, and how many
+    /// pulls were clamped to the minimum separation, see physics::compute_gravity_clamped.
+    /// Takes the softening length and minimum separation squared.
+    /// A node is treated as a single mass when its size over its distance is below `theta`,
+    /// so 0 opens every node and gives the exact brute-force result.
+    pub fn acceleration(
+        &self,
+        position: Vector3<f64>,
+        theta: f64,
+        g: f64,
+        softening2: f64,
+        min_separation2: f64,
+    ) -> (Vector3<f64>, usize) {
+        let mut total = Vector3::zero();
+        let mut clamped = 0;
+        self.visit(position, theta, |mass, from| {
+            let (acceleration, was_clamped) =
+                physics::pull(from - position, mass, g, softening2, min_separation2);
+            total += acceleration;
+            clamped += was_clamped as usize;
+        });
+        (total, clamped)
+    }
+
+    /// How deep `position` sits in the gravity well of every body in the tree, the sum of
+    /// m / r, see physics::potential_depth. Bodies right at `position` are left out.
+    /// Nodes are opened the same way as for acceleration, so 0 is exact
+    pub fn potential_depth(&self, position: Vector3<f64>, theta: f64) -> f64 {
+        let mut depth = 0.0;
+        self.visit(position, theta, |mass, from| {
+            let distance = (from - position).magnitude();
+            if distance > 0.0 {
+                depth += mass / distance;
+            }
+        });
+        depth
+    }
+
+    /// Calls `visit` with the mass and position of every body, or of every node far enough
+    /// from `position` to stand in for the bodies inside it
+    fn visit(&self, position: Vector3<f64>, theta: f64, mut visit: impl FnMut(f64, Vector3<f64>)) {
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 {
+                continue;
+            }
+            match node.children {
+                None => {
+                    for &body in &node.bodies {
+                        visit(self.masses[body], self.positions[body]);
+                    }
+                }
+                Some(first_child) => {
+                    let distance = (node.center_of_mass - position).magnitude();
+                    if node.half_size * 2.0 < theta * distance {
+                        visit(node.mass, node.center_of_mass);
+                    } else {
+                        stack.extend(first_child..first_child + 8);
+                    }
+                }
+            }
+        }
+    }
+}
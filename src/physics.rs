@@ -0,0 +1,452 @@
+use crate::octree::Octree;
+use crate::spatial_hash;
+use crate::sphere::Entity;
+use cgmath::*;
+
+/// The default softening length, which keeps close passes from producing huge accelerations
+pub const DEFAULT_SOFTENING: f32 = 0.05;
+/// The default closest two bodies are treated as being when the separation clamp is on
+pub const DEFAULT_MIN_SEPARATION: f32 = 0.1;
+
+/// The opening angle the octree is switched on with, a common balance of speed and accuracy
+pub const DEFAULT_THETA: f32 = 0.5;
+
+/// How the pull of every body on every other body is worked out
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GravityStrategy {
+    /// Sums every pair directly. Exact, but O(n^2)
+    BruteForce,
+    /// Treats distant groups of bodies as one mass using an octree, in O(n log n).
+    /// Smaller values of theta are more accurate, and 0 matches BruteForce
+    BarnesHut {
+        /// How small a group has to look, its size over its distance, to be treated as one mass
+        theta: f32,
+    },
+}
+
+/// The net gravitational acceleration on each body from every other body.
+/// Each pair contributes G * m / (r^2 + softening^2) along the line between them,
+/// so bodies passing through each other get a large but finite pull instead of NaN.
+/// Worked out in f64 like the positions, whatever precision the settings are given in
+pub fn compute_gravity(
+    entities: &[Entity],
+    g: f32,
+    softening: f32,
+    strategy: GravityStrategy,
+) -> Vec<Vector3<f64>> {
+    compute_gravity_clamped(entities, g, softening, strategy, None).accelerations
+}
+
+/// The accelerations from compute_gravity_clamped, and how many pairs were clamped
+pub struct ClampedGravity {
+    /// The net gravitational acceleration on each body
+    pub accelerations: Vec<Vector3<f64>>,
+    /// How many pairs of bodies were closer than the minimum separation
+    pub clamped_pairs: usize,
+}
+
+/// Same as compute_gravity, but pairs closer than `min_separation` pull as if they were that
+/// far apart. Softening alone only caps the pull at G * m / softening^2, which a small
+/// softening length leaves big enough to fling a close pass off to infinity; the clamp keeps
+/// it bounded whatever the softening. Lots of clamped pairs is a sign softening is too small.
+/// With Barnes-Hut, close pairs are always summed body by body, so each is counted once from
+/// either end
+pub fn compute_gravity_clamped(
+    entities: &[Entity],
+    g: f32,
+    softening: f32,
+    strategy: GravityStrategy,
+    min_separation: Option<f32>,
+) -> ClampedGravity {
+    let g = f64::from(g);
+    let softening2 = f64::from(softening).powi(2);
+    let min_separation2 = min_separation.map_or(0.0, |separation| f64::from(separation).powi(2));
+    match strategy {
+        GravityStrategy::BruteForce => {
+            brute_force_gravity(entities, g, softening2, min_separation2)
+        }
+        GravityStrategy::BarnesHut { theta } => {
+            let tree = Octree::new(entities);
+            let theta = f64::from(theta);
+            let mut clamped_pulls = 0;
+            let accelerations = entities
+                .iter()
+                .map(|body| {
+                    let (acceleration, clamped) =
+                        tree.acceleration(body.position, theta, g, softening2, min_separation2);
+                    clamped_pulls += clamped;
+                    acceleration
+                })
+                .collect();
+            ClampedGravity {
+                accelerations,
+                // Both bodies of a clamped pair felt it
+                clamped_pairs: clamped_pulls / 2,
+            }
+        }
+    }
+}
+
+/// The pull of `mass` at `offset` from a body, and whether it was clamped to
+/// the minimum separation. Takes the softening length and minimum separation squared,
+/// where a minimum of 0 means no clamp
+pub(crate) fn pull(
+    offset: Vector3<f64>,
+    mass: f64,
+    g: f64,
+    softening2: f64,
+    min_separation2: f64,
+) -> (Vector3<f64>, bool) {
+    let separation2 = offset.magnitude2();
+    // A body on top of another, or itself, has no direction to be pulled in anyway
+    if separation2 == 0.0 {
+        return (Vector3::zero(), false);
+    }
+    let distance2 = separation2.max(min_separation2) + softening2;
+    // offset / |r| gives the direction, G * m / r^2 the strength
+    (
+        offset * (g * mass / (distance2 * distance2.sqrt())),
+        separation2 < min_separation2,
+    )
+}
+
+fn brute_force_gravity(
+    entities: &[Entity],
+    g: f64,
+    softening2: f64,
+    min_separation2: f64,
+) -> ClampedGravity {
+    let mut clamped_pairs = 0;
+    let accelerations = entities
+        .iter()
+        .enumerate()
+        .map(|(i, body)| {
+            let mut sum = Vector3::zero();
+            for (j, other) in entities.iter().enumerate().filter(|(j, _)| *j != i) {
+                let (acceleration, clamped) = pull(
+                    other.position - body.position,
+                    f64::from(other.mass),
+                    g,
+                    softening2,
+                    min_separation2,
+                );
+                sum += acceleration;
+                // Each pair is visited from both ends
+                if clamped && j > i {
+                    clamped_pairs += 1;
+                }
+            }
+            sum
+        })
+        .collect();
+    ClampedGravity {
+        accelerations,
+        clamped_pairs,
+    }
+}
+
+/// The speed needed for a light body to orbit `central_mass` in a circle of the given radius
+pub fn circular_orbit_speed(central_mass: f32, radius: f32, g: f32) -> f32 {
+    (g * central_mass / radius).sqrt()
+}
+
+/// Kepler's third law: the period of an orbit with the given semi-major axis around `central_mass`
+pub fn orbital_period(central_mass: f32, semi_major_axis: f32, g: f32) -> f32 {
+    2.0 * std::f32::consts::PI * (semi_major_axis.powi(3) / (g * central_mass)).sqrt()
+}
+
+/// The semi-major axis of an orbit around `central_mass` with the given period.
+/// The inverse of orbital_period.
+pub fn semi_major_axis(central_mass: f32, period: f32, g: f32) -> f32 {
+    let angular_frequency = 2.0 * std::f32::consts::PI / period;
+    (g * central_mass / (angular_frequency * angular_frequency)).cbrt()
+}
+
+/// The mass-weighted average position of the bodies
+pub fn center_of_mass(bodies: &[Entity]) -> Vector3<f64> {
+    let total_mass: f64 = bodies.iter().map(|body| f64::from(body.mass)).sum();
+    if total_mass == 0.0 {
+        return Vector3::zero();
+    }
+    bodies.iter().fold(Vector3::zero(), |sum, body| {
+        sum + body.position * f64::from(body.mass)
+    }) / total_mass
+}
+
+/// The center of mass of the bodies and the corners of the smallest axis-aligned box
+/// that holds all of them, radii included, as (center_of_mass, aabb_min, aabb_max).
+/// With no bodies everything is at the origin
+pub fn scene_bounds(bodies: &[Entity]) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
+    if bodies.is_empty() {
+        return (Vector3::zero(), Vector3::zero(), Vector3::zero());
+    }
+    let (min, max) = bodies.iter().fold(
+        (
+            Vector3::from_value(f64::INFINITY),
+            Vector3::from_value(f64::NEG_INFINITY),
+        ),
+        |(min, max), body| {
+            let reach = Vector3::from_value(f64::from(body.radius));
+            let (low, high) = (body.position - reach, body.position + reach);
+            (
+                Vector3::new(min.x.min(low.x), min.y.min(low.y), min.z.min(low.z)),
+                Vector3::new(max.x.max(high.x), max.y.max(high.y), max.z.max(high.z)),
+            )
+        },
+    );
+    (center_of_mass(bodies), min, max)
+}
+
+/// The radius of the Hill sphere of a body of `mass` orbiting `primary_mass` at `separation`,
+/// the region in which its own gravity dominates over the primary's: a * cbrt(m / 3M)
+pub fn hill_radius(mass: f32, primary_mass: f32, separation: f32) -> f32 {
+    separation * (mass / (3.0 * primary_mass)).cbrt()
+}
+
+/// The body a Hill sphere is measured against when none was picked: whichever body heavier
+/// than body `index` pulls hardest on it. None for the heaviest body, which has nothing to
+/// orbit
+pub fn hill_primary(bodies: &[Entity], index: usize) -> Option<usize> {
+    let body = &bodies[index];
+    bodies
+        .iter()
+        .enumerate()
+        .filter(|&(i, other)| i != index && other.mass > body.mass)
+        .filter_map(|(i, other)| {
+            let distance2 = (other.position - body.position).magnitude2();
+            (distance2 > 0.0).then(|| (i, f64::from(other.mass) / distance2))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// The Hill radius of body `index` measured against the primary it's configured with,
+/// or the one hill_primary picks if it has none. None when there's no primary
+pub fn body_hill_radius(bodies: &[Entity], index: usize) -> Option<f32> {
+    let body = &bodies[index];
+    let primary = body
+        .hill_primary
+        .filter(|&primary| primary != index)
+        .or_else(|| hill_primary(bodies, index))?;
+    let primary = bodies.get(primary)?;
+    let separation = (primary.position - body.position).magnitude() as f32;
+    Some(hill_radius(body.mass, primary.mass, separation))
+}
+
+/// How deep body `index` sits in the gravity well of every other body, i.e. the sum of m / r.
+/// This is the magnitude of its potential with G left out.
+pub fn potential_depth(bodies: &[Entity], index: usize) -> f32 {
+    let position = bodies[index].position;
+    bodies
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != index)
+        .map(|(_, other)| {
+            let distance = (other.position - position).magnitude() as f32;
+            if distance > 0.0 {
+                other.mass / distance
+            } else {
+                0.0
+            }
+        })
+        .sum()
+}
+
+/// potential_depth for every body at once, from a Barnes-Hut octree opened with `theta`.
+/// O(n log n) rather than O(n^2), and with `theta` at 0 the same as potential_depth
+pub fn potential_depths(bodies: &[Entity], theta: f32) -> Vec<f32> {
+    let tree = Octree::new(bodies);
+    bodies
+        .iter()
+        .map(|body| tree.potential_depth(body.position, f64::from(theta)) as f32)
+        .collect()
+}
+
+/// The index of the body pulling hardest on a point at `position`, the one with the largest
+/// m / r^2. Bodies right at the point are skipped, and None means there's nothing else
+pub fn strongest_pull(bodies: &[Entity], position: Vector3<f64>) -> Option<usize> {
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, body)| {
+            let distance2 = (body.position - position).magnitude2();
+            (distance2 > 0.0).then(|| (i, f64::from(body.mass) / distance2))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// The total kinetic energy of the bodies, the sum of 1/2 m v^2
+pub fn kinetic_energy(bodies: &[Entity]) -> f64 {
+    bodies
+        .iter()
+        .map(|body| 0.5 * f64::from(body.mass) * body.velocity.magnitude2())
+        .sum()
+}
+
+/// The total gravitational potential energy of the bodies, the sum over every pair of
+/// -G m_i m_j / r. Softened the same way as compute_gravity, so it's the potential
+/// those forces actually conserve
+pub fn potential_energy(bodies: &[Entity], g: f32, softening: f32) -> f64 {
+    let g = f64::from(g);
+    let softening2 = f64::from(softening).powi(2);
+    let mut energy = 0.0;
+    for (i, body) in bodies.iter().enumerate() {
+        for other in &bodies[i + 1..] {
+            let distance2 = (other.position - body.position).magnitude2() + softening2;
+            if distance2 > 0.0 {
+                let masses = f64::from(body.mass) * f64::from(other.mass);
+                energy -= g * masses / distance2.sqrt();
+            }
+        }
+    }
+    energy
+}
+
+/// The kinetic plus potential energy of the bodies, which stays constant in an exact simulation
+pub fn total_energy(bodies: &[Entity], g: f32, softening: f32) -> f64 {
+    kinetic_energy(bodies) + potential_energy(bodies, g, softening)
+}
+
+/// The total linear momentum of the bodies, the sum of m v.
+/// Gravity between the bodies never changes it, so it should stay where it started
+pub fn momentum(bodies: &[Entity]) -> Vector3<f64> {
+    bodies.iter().fold(Vector3::zero(), |sum, body| {
+        sum + body.velocity * f64::from(body.mass)
+    })
+}
+
+/// Shifts every body's velocity by the same amount so their total momentum is zero,
+/// which stops the group as a whole drifting off. How the bodies move relative to each
+/// other is unchanged. Bodies with no mass between them are left alone
+pub fn remove_net_momentum(bodies: &mut [Entity]) {
+    let total_mass: f64 = bodies.iter().map(|body| f64::from(body.mass)).sum();
+    if total_mass == 0.0 {
+        return;
+    }
+    let drift = momentum(bodies) / total_mass;
+    for body in bodies {
+        body.velocity -= drift;
+    }
+}
+
+/// The total angular momentum of the bodies about their center of mass
+pub fn angular_momentum(bodies: &[Entity]) -> Vector3<f64> {
+    let center = center_of_mass(bodies);
+    bodies.iter().fold(Vector3::zero(), |sum, body| {
+        sum + (body.position - center).cross(body.velocity * f64::from(body.mass))
+    })
+}
+
+/// Spins the bodies up (or down) so their total angular momentum becomes `target`.
+/// This adds a rigid rotation about the center of mass on top of the existing velocities,
+/// so the random motion of a cluster is kept and only its net rotation changes.
+pub fn set_angular_momentum(bodies: &mut [Entity], target: Vector3<f64>) {
+    let center = center_of_mass(bodies);
+
+    // Inertia tensor about the center of mass: sum of m * (|r|^2 * I - r * r^T)
+    let inertia = bodies.iter().fold(Matrix3::zero(), |sum, body| {
+        let r = body.position - center;
+        let outer = Matrix3::from_cols(r * r.x, r * r.y, r * r.z);
+        sum + (Matrix3::identity() * r.magnitude2() - outer) * f64::from(body.mass)
+    });
+
+    // Bodies that all lie on a line (or a single body) can't be spun about every axis
+    let inverse = match inertia.invert() {
+        Some(inverse) => inverse,
+        None => {
+            log::warn!("Can't set the angular momentum of a degenerate set of bodies");
+            return;
+        }
+    };
+
+    // The angular velocity that makes up the difference, applied as v += w x r
+    let omega = inverse * (target - angular_momentum(bodies));
+    for body in bodies.iter_mut() {
+        body.velocity += omega.cross(body.position - center);
+    }
+}
+
+/// Kicks every body straight away from `center` with a speed of `magnitude`,
+/// blowing a cluster apart so it can be watched dispersing and falling back together.
+/// Bodies sitting exactly on the center have no outward direction and are left alone.
+pub fn explode(bodies: &mut [Entity], center: Vector3<f64>, magnitude: f64) {
+    for body in bodies.iter_mut() {
+        let offset = body.position - center;
+        if offset.magnitude2() > 0.0 {
+            body.velocity += offset.normalize() * magnitude;
+        }
+    }
+}
+
+/// Merges pairs of bodies whose spheres overlap into a single body. Each body takes part
+/// in at most one merge per call, so where three or more overlap the first disjoint pairs
+/// found are merged and the rest are left for the next call. The merged body keeps the
+/// combined mass and momentum, sits at the pair's center of mass, and has the combined
+/// volume of both spheres. It takes the place of the lower-indexed body and keeps the look
+/// of the heavier one.
+/// Returns the index removed by each merge, in the order they were removed, so anything
+/// kept alongside the bodies (like trails) can be removed to match.
+/// Overlaps are found with a spatial hash with cells of `cell_size`, see spatial_hash.
+pub fn handle_collisions(entities: &mut Vec<Entity>, cell_size: f32) -> Vec<usize> {
+    let mut merging = vec![false; entities.len()];
+    let mut pairs = Vec::new();
+    for (i, j) in spatial_hash::overlapping_pairs(entities, cell_size) {
+        if !merging[i] && !merging[j] {
+            merging[i] = true;
+            merging[j] = true;
+            pairs.push((i, j));
+        }
+    }
+    for &(i, j) in &pairs {
+        entities[i] = merged(&entities[i], &entities[j]);
+    }
+
+    // Removing from the back, so the bodies still to be removed or fixed up keep their indices
+    pairs.sort_by_key(|&(_, j)| std::cmp::Reverse(j));
+    let mut removed = Vec::new();
+    for (i, j) in pairs {
+        entities.remove(j);
+        // Every body after the removed one moved down a place
+        for entity in entities.iter_mut() {
+            entity.hill_primary = match entity.hill_primary {
+                Some(primary) if primary == j => Some(i),
+                Some(primary) if primary > j => Some(primary - 1),
+                primary => primary,
+            };
+        }
+        // A body can't be measured against itself
+        if entities[i].hill_primary == Some(i) {
+            entities[i].hill_primary = None;
+        }
+        removed.push(j);
+    }
+    removed
+}
+
+/// The single body `body` and `other` merge into, see handle_collisions
+fn merged(body: &Entity, other: &Entity) -> Entity {
+    let mass = body.mass + other.mass;
+    let (position, velocity) = if mass > 0.0 {
+        let (body_mass, other_mass) = (f64::from(body.mass), f64::from(other.mass));
+        let total = body_mass + other_mass;
+        (
+            (body.position * body_mass + other.position * other_mass) / total,
+            (body.velocity * body_mass + other.velocity * other_mass) / total,
+        )
+    } else {
+        (
+            (body.position + other.position) * 0.5,
+            (body.velocity + other.velocity) * 0.5,
+        )
+    };
+    let look = if other.mass > body.mass { other } else { body };
+    Entity {
+        position,
+        velocity,
+        mass,
+        radius: (body.radius.powi(3) + other.radius.powi(3)).cbrt(),
+        hill_primary: body.hill_primary,
+        ..look.clone()
+    }
+}
@@ -0,0 +1,41 @@
+use cgmath::*;
+
+/// How far along the ray from `origin` in the unit `direction` it first meets the sphere
+/// of `radius` around `center`, if it does at all. A ray starting inside the sphere meets
+/// it on the way out
+pub fn ray_sphere_distance(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    center: Point3<f32>,
+    radius: f32,
+) -> Option<f32> {
+    // Solving |origin + t * direction - center| = radius for t
+    let offset = origin - center;
+    let half_b = offset.dot(direction);
+    let c = offset.magnitude2() - radius * radius;
+    let discriminant = half_b * half_b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [-half_b - root, -half_b + root]
+        .iter()
+        .copied()
+        .find(|&t| t >= 0.0)
+}
+
+/// The index of the nearest of `spheres`, given as centers and radii, that the ray from
+/// `origin` in the unit `direction` hits
+pub fn pick<I>(origin: Point3<f32>, direction: Vector3<f32>, spheres: I) -> Option<usize>
+where
+    I: IntoIterator<Item = (Point3<f32>, f32)>,
+{
+    spheres
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (center, radius))| {
+            ray_sphere_distance(origin, direction, center, radius).map(|t| (index, t))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(index, _)| index)
+}
@@ -0,0 +1,83 @@
+// Draws each body as a soft round sprite facing the camera, for scenes with too many bodies
+// to draw as spheres. Reads the same instances the sphere shader does
+
+// Vertex shader
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    eye: vec4<f32>;
+    right: vec4<f32>;
+    up: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+// Only what a sprite needs of InstanceRaw in instance.rs
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+    [[location(9)]] color: vec3<f32>;
+    [[location(10)]] emissive: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec3<f32>;
+    // Where in the sprite this is, from -1 to 1 across and up
+    [[location(1)]] offset: vec2<f32>;
+    [[location(2)]] emissive: f32;
+};
+
+// A quad drawn as a four vertex triangle strip, so no vertex buffer is needed
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let offset = vec2<f32>(f32(index & 1u) * 2.0 - 1.0, f32(index >> 1u) * 2.0 - 1.0);
+    // The model matrix scales the unit sphere up to the body's radius, and motion blur
+    // stretches one axis further, so the longest axis covers the whole body
+    let radius = max(
+        length(instance.model_matrix_0.xyz),
+        max(length(instance.model_matrix_1.xyz), length(instance.model_matrix_2.xyz)),
+    );
+    let center = instance.model_matrix_3.xyz;
+    // Sized in the world rather than on screen, so sprites shrink with distance like spheres
+    let corner = center + (camera.right.xyz * offset.x + camera.up.xyz * offset.y) * radius;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(corner, 1.0);
+    out.color = instance.color;
+    out.offset = offset;
+    out.emissive = instance.emissive;
+    return out;
+}
+
+// Fragment shader
+
+// How far out from the middle, as a fraction of the radius, the edge starts fading
+let FADE_START: f32 = 0.6;
+// Fainter than this is left out entirely, so it doesn't hide what's behind it in the
+// depth buffer
+let MIN_ALPHA: f32 = 0.05;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let distance = length(in.offset);
+    // A smoothstep from FADE_START to the edge
+    let t = clamp((distance - FADE_START) / (1.0 - FADE_START), 0.0, 1.0);
+    let alpha = 1.0 - t * t * (3.0 - 2.0 * t);
+    if (alpha < MIN_ALPHA) {
+        discard;
+    }
+    // Glowing bodies are flat, the rest darken towards the edge to look a little round
+    var shade = 1.0;
+    if (in.emissive < 0.5) {
+        shade = 0.4 + 0.6 * sqrt(max(1.0 - distance * distance, 0.0));
+    }
+    return vec4<f32>(in.color * shade, alpha);
+}
@@ -0,0 +1,64 @@
+use crate::state::State;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// Writes every frame to a numbered PNG in a directory, stepping the scene by the same
+/// amount each frame so a recording comes out the same every time it's made.
+/// The frames can be made into a video with something like
+/// `ffmpeg -framerate 60 -i frame_%05d.png out.mp4`
+pub struct Recorder {
+    directory: PathBuf,
+    /// How many frames are recorded in all
+    frames: usize,
+    /// How many frames have been written so far
+    written: usize,
+    /// How many seconds of simulated time each frame moves the scene on by
+    frame_time: f32,
+}
+
+impl Recorder {
+    /// Records `frames` frames into `directory`, creating it if it isn't there,
+    /// moving the scene on by `frame_time` seconds between them
+    pub fn new(directory: &Path, frames: usize, frame_time: f32) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create {}", directory.display()))?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            frames,
+            written: 0,
+            frame_time,
+        })
+    }
+
+    /// Where the frame numbered `number`, counting from 1, is written
+    pub fn frame_path(&self, number: usize) -> PathBuf {
+        self.directory.join(format!("frame_{:05}.png", number))
+    }
+
+    /// Moves the scene on by a frame, draws it and writes it out.
+    /// Returns whether there are still frames left to record
+    pub fn record_frame(&mut self, state: &mut State) -> anyhow::Result<bool> {
+        if self.written == self.frames {
+            return Ok(false);
+        }
+        // The first frame shows the scene as it starts
+        let frame_time = if self.written == 0 {
+            0.0
+        } else {
+            self.frame_time
+        };
+        state.update_by(frame_time);
+        let path = self.frame_path(self.written + 1);
+        state
+            .capture_frame()?
+            .save(&path)
+            .with_context(|| format!("Failed to save frame {}", path.display()))?;
+        self.written += 1;
+        Ok(self.written < self.frames)
+    }
+
+    /// How many frames have been written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
@@ -1,55 +1,591 @@
-use crate::sphere;
-use crate::sphere::Entity;
+use crate::bloom;
+use crate::colormap;
+use crate::grid;
+use crate::physics;
+use crate::sphere::{self, Entity};
 use crate::texture;
+use crate::trail::{self, TrailBuffer};
 use crate::{camera, instance, Vertex};
+use anyhow::{Context, Result};
+use bytemuck::Zeroable;
 use cgmath::*;
-use std::detect::__is_feature_detected::sha;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use wgpu::util::DeviceExt;
-use wgpu::*;
+use winit::event::VirtualKeyCode;
 
+/// A single point light, laid out the way the shaders' Light struct expects
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
     pub position: [f32; 3],
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    _padding: u32,
+    // The attenuation coefficients are tucked in after each vec3, where there'd otherwise be padding
+    constant: f32,
     pub color: [f32; 3],
+    linear: f32,
+    quadratic: f32,
+    // Pads the struct out to a multiple of 16 bytes so it can sit in an array
+    _padding: [f32; 3],
+}
+
+impl LightUniform {
+    pub fn new(position: [f32; 3], color: [f32; 3], attenuation: Attenuation) -> Self {
+        Self {
+            position,
+            constant: attenuation.constant,
+            color,
+            linear: attenuation.linear,
+            quadratic: attenuation.quadratic,
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// The light a glowing body gives off
+    pub fn from_emitter(entity: &Entity, attenuation: Attenuation) -> Self {
+        Self::new(
+            entity.position.map(|x| x as f32).into(),
+            entity.color,
+            attenuation,
+        )
+    }
+
+    pub fn set_attenuation(&mut self, attenuation: Attenuation) {
+        self.constant = attenuation.constant;
+        self.linear = attenuation.linear;
+        self.quadratic = attenuation.quadratic;
+    }
+}
+
+/// How quickly a light fades with distance.
+/// At distance d its brightness is scaled by 1 / (constant + linear * d + quadratic * d * d)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    /// Falls to under half brightness 20 units from the light
+    fn default() -> Self {
+        Self {
+            constant: 1.0,
+            linear: 0.022,
+            quadratic: 0.0019,
+        }
+    }
+}
+
+/// How many lights the shaders can be given at once.
+/// Must match the size of the light array in shader.wgsl and light.wgsl
+pub const MAX_LIGHTS: usize = 8;
+
+// Mirrors the LightCount uniform in shader.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightCount {
+    num_lights: u32,
+    // Uniforms are 16 byte aligned
+    _padding: [u32; 3],
+}
+
+/// The groups instances are packed into, in the order they sit in the instance buffer.
+/// Each group is drawn with a single ranged draw call
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstanceCategory {
+    /// Regular bodies, drawn with back-face culling
+    Opaque,
+    /// Bodies the camera is inside of, drawn without back-face culling
+    Inside,
+}
+
+impl InstanceCategory {
+    /// Whether instances of this category have their back faces culled, given whether
+    /// culling is on at all
+    pub fn culled(&self, culling: bool) -> bool {
+        culling && *self == InstanceCategory::Opaque
+    }
+}
+
+/// How many sphere meshes of decreasing detail bodies can be drawn with
+pub const LOD_LEVELS: usize = 3;
+
+/// How bodies are drawn
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderStyle {
+    /// Lit, textured sphere meshes
+    Spheres,
+    /// Flat round sprites facing the camera, cheap enough for hundreds of thousands of bodies
+    Points,
+}
+
+/// The resolution of each sphere mesh bodies can be drawn with, finest first, starting at
+/// `finest` and dividing it by 2 and 3 for the coarser ones. None go below
+/// sphere::MIN_SPHERE_RESOLUTION, so coarse meshes can end up the same as finer ones
+pub fn lod_resolutions(finest: u32) -> [u32; LOD_LEVELS] {
+    [1, 2, 3].map(|divisor| (finest / divisor).max(sphere::MIN_SPHERE_RESOLUTION))
+}
+
+/// The default for Render::lod_thresholds
+pub const DEFAULT_LOD_THRESHOLDS: [f32; 2] = [40.0, 150.0];
+
+/// Which of the lod_resolutions meshes to draw a body with. `thresholds` are how many of
+/// its own radii away a body has to be to drop to each coarser mesh, so a body's mesh
+/// follows how big it looks on screen rather than how far away it is
+pub fn select_lod(distance: f32, radius: f32, thresholds: &[f32]) -> usize {
+    let radii_away = if radius > 0.0 {
+        distance / radius
+    } else {
+        f32::INFINITY
+    };
+    thresholds
+        .iter()
+        .take_while(|&&threshold| radii_away > threshold)
+        .count()
+        .min(LOD_LEVELS - 1)
+}
+
+/// A contiguous run of the instance buffer holding instances of one category,
+/// all drawn with the same sphere mesh and texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceRange {
+    pub category: InstanceCategory,
+    /// Which of Render::spheres the instances are drawn with
+    pub lod: usize,
+    /// Which of Render::body_textures the instances are drawn with
+    pub texture: usize,
+    pub range: Range<u32>,
+}
+
+/// Works out how each instance needs to be drawn this frame and in what order.
+/// Instances entirely outside the camera's frustum are dropped, and the rest are grouped by category,
+/// level of detail and texture, then sorted front to back within each group so the depth test can
+/// skip hidden fragments. Returns the instance indices in upload order along with the range
+/// each group occupies.
+pub fn pack_instances(
+    instances: &[instance::Instance],
+    camera: &camera::Camera,
+    cutaway: bool,
+    lod_thresholds: &[f32],
+) -> (Vec<usize>, Vec<InstanceRange>) {
+    let eye = camera.eye.to_vec();
+    let frustum = camera.frustum();
+
+    let mut visible = instances
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instance)| {
+            // Off screen, behind the camera or past the clipping planes. The frustum is
+            // relative to the eye, like the offset
+            let offset = instance.position - eye;
+            if !frustum.intersects_sphere(Point3::from_vec(offset), instance.radius) {
+                return None;
+            }
+            let category = if cutaway && instance.contains(eye) {
+                InstanceCategory::Inside
+            } else {
+                InstanceCategory::Opaque
+            };
+            let distance = offset.magnitude();
+            let lod = select_lod(distance, instance.radius, lod_thresholds);
+            Some(((category, lod, instance.texture), distance, i))
+        })
+        .collect::<Vec<_>>();
+    visible.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let mut ranges: Vec<InstanceRange> = Vec::new();
+    for (slot, ((category, lod, texture), _, _)) in visible.iter().enumerate() {
+        let slot = slot as u32;
+        match ranges.last_mut() {
+            Some(last)
+                if last.category == *category && last.lod == *lod && last.texture == *texture =>
+            {
+                last.range.end = slot + 1
+            }
+            _ => ranges.push(InstanceRange {
+                category: *category,
+                lod: *lod,
+                texture: *texture,
+                range: slot..slot + 1,
+            }),
+        }
+    }
+
+    let order = visible.into_iter().map(|(_, _, i)| i).collect();
+    (order, ranges)
+}
+
+/// What's drawn behind the bodies
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Background {
+    /// A single flat color
+    Flat(wgpu::Color),
+    /// Procedurally placed stars over black
+    Starfield,
+}
+
+impl Background {
+    /// A flat background of red, green and blue from 0 to 1
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        Background::Flat(wgpu::Color {
+            r: rgb[0] as f64,
+            g: rgb[1] as f64,
+            b: rgb[2] as f64,
+            a: 1.0,
+        })
+    }
+
+    /// A flat background from a hex color like `000000` or `#1a334d`
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        anyhow::ensure!(
+            digits.len() == 6 && digits.is_ascii(),
+            "Expected a color like RRGGBB, got {}",
+            hex
+        );
+        let channel = |i: usize| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map(|value| value as f32 / 255.0)
+                .with_context(|| format!("Expected a color like RRGGBB, got {}", hex))
+        };
+        Ok(Self::from_rgb([channel(0)?, channel(2)?, channel(4)?]))
+    }
+
+    /// The color the frame is cleared to before anything is drawn
+    pub fn clear_color(&self) -> wgpu::Color {
+        match self {
+            Background::Flat(color) => *color,
+            Background::Starfield => wgpu::Color::BLACK,
+        }
+    }
+}
+
+/// A set of render passes, used to switch individual passes on and off while debugging
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PassFlags(u32);
+
+impl PassFlags {
+    /// The lit, opaque bodies
+    pub const BODIES: PassFlags = PassFlags(1 << 0);
+    /// The model drawn at the light's position
+    pub const LIGHT: PassFlags = PassFlags(1 << 1);
+    /// The fading trails behind each body
+    pub const TRAILS: PassFlags = PassFlags(1 << 2);
+    /// The glow around bright bodies
+    pub const BLOOM: PassFlags = PassFlags(1 << 3);
+    /// The axes and the grid in the plane of the orbits
+    pub const GRID: PassFlags = PassFlags(1 << 4);
+    /// The starfield behind everything
+    pub const SKYBOX: PassFlags = PassFlags(1 << 5);
+    /// The diagnostics overlay
+    pub const UI: PassFlags = PassFlags(1 << 6);
+    /// The see-through bodies of the comparison run
+    pub const GHOSTS: PassFlags = PassFlags(1 << 7);
+    /// The wireframe Hill spheres around each body
+    pub const HILL_SPHERES: PassFlags = PassFlags(1 << 8);
+
+    /// Every pass, the default
+    pub const ALL: PassFlags = PassFlags(
+        Self::BODIES.0
+            | Self::LIGHT.0
+            | Self::TRAILS.0
+            | Self::BLOOM.0
+            | Self::GRID.0
+            | Self::SKYBOX.0
+            | Self::UI.0
+            | Self::GHOSTS.0
+            | Self::HILL_SPHERES.0,
+    );
+
+    /// Returns true if every pass in `other` is enabled
+    pub fn contains(&self, other: PassFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Flips the passes in `other` on or off
+    pub fn toggle(&mut self, other: PassFlags) {
+        self.0 ^= other.0;
+    }
+
+    /// Switches the passes in `other` on when `on` is set, and off otherwise
+    pub fn set(&mut self, other: PassFlags, on: bool) {
+        if on {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    /// The passes that are both enabled and have something to draw, given what the
+    /// renderer is showing. Only these get their pipelines bound
+    pub fn drawn(&self, content: PassContent) -> PassFlags {
+        let mut drawn = *self;
+        drawn.set(
+            PassFlags::SKYBOX,
+            self.contains(PassFlags::SKYBOX) && content.starfield,
+        );
+        drawn.set(
+            PassFlags::GRID,
+            self.contains(PassFlags::GRID) && content.guides,
+        );
+        drawn.set(
+            PassFlags::TRAILS,
+            self.contains(PassFlags::TRAILS) && content.trails,
+        );
+        drawn.set(
+            PassFlags::BLOOM,
+            self.contains(PassFlags::BLOOM) && content.bloom,
+        );
+        drawn.set(
+            PassFlags::GHOSTS,
+            self.contains(PassFlags::GHOSTS) && content.ghosts,
+        );
+        drawn.set(
+            PassFlags::HILL_SPHERES,
+            self.contains(PassFlags::HILL_SPHERES) && content.hill_spheres,
+        );
+        drawn
+    }
+
+    /// The passes draw_scene records for these drawn passes, in the order it records them
+    pub fn scene_passes(self) -> impl Iterator<Item = ScenePass> {
+        ScenePass::ORDER
+            .iter()
+            .copied()
+            .filter(move |pass| self.contains(pass.flag()))
+    }
+
+    /// The pass toggled by a number key, if any
+    pub fn from_key(keycode: VirtualKeyCode) -> Option<PassFlags> {
+        match keycode {
+            VirtualKeyCode::Key1 => Some(Self::BODIES),
+            VirtualKeyCode::Key2 => Some(Self::LIGHT),
+            VirtualKeyCode::Key3 => Some(Self::TRAILS),
+            VirtualKeyCode::Key4 => Some(Self::BLOOM),
+            VirtualKeyCode::Key5 => Some(Self::GRID),
+            VirtualKeyCode::Key6 => Some(Self::SKYBOX),
+            VirtualKeyCode::Key7 => Some(Self::UI),
+            VirtualKeyCode::Key8 => Some(Self::GHOSTS),
+            VirtualKeyCode::Key9 => Some(Self::HILL_SPHERES),
+            _ => None,
+        }
+    }
+}
+
+/// Which of the optional passes the renderer has anything to draw for, see PassFlags::drawn.
+/// The bodies, light and overlay are always there to draw
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PassContent {
+    /// The background is the starfield rather than a flat color
+    pub starfield: bool,
+    /// The axes or the grid are shown
+    pub guides: bool,
+    /// There are trail vertices to draw
+    pub trails: bool,
+    /// Bloom is turned up enough to show
+    pub bloom: bool,
+    /// A comparison run has bodies to draw
+    pub ghosts: bool,
+    /// The Hill spheres are shown and there are some to draw
+    pub hill_spheres: bool,
+}
+
+/// The passes draw_scene records into the scene's render pass
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScenePass {
+    /// The starfield, drawn first without depth so everything covers it
+    Skybox,
+    /// The axes and grid lines
+    Guides,
+    /// The model at the orbiting light's position
+    Light,
+    /// The bodies, as spheres or points
+    Bodies,
+    /// The comparison run's bodies, blended over the simulation's
+    Ghosts,
+    /// The Hill spheres' wireframes
+    HillSpheres,
+    /// The trails, blended over the bodies
+    Trails,
+}
+
+impl ScenePass {
+    /// Every scene pass, in the order they're drawn
+    pub const ORDER: [ScenePass; 7] = [
+        ScenePass::Skybox,
+        ScenePass::Guides,
+        ScenePass::Light,
+        ScenePass::Bodies,
+        ScenePass::Ghosts,
+        ScenePass::HillSpheres,
+        ScenePass::Trails,
+    ];
+
+    /// The flag that switches this pass on and off
+    pub fn flag(self) -> PassFlags {
+        match self {
+            ScenePass::Skybox => PassFlags::SKYBOX,
+            ScenePass::Guides => PassFlags::GRID,
+            ScenePass::Light => PassFlags::LIGHT,
+            ScenePass::Bodies => PassFlags::BODIES,
+            ScenePass::Ghosts => PassFlags::GHOSTS,
+            ScenePass::HillSpheres => PassFlags::HILL_SPHERES,
+            ScenePass::Trails => PassFlags::TRAILS,
+        }
+    }
 }
 
 pub struct Render {
+    /// What the body pipelines are built with, kept to rebuild them with a new shader
+    #[cfg(feature = "dev")]
     pub render_pipeline_layout: wgpu::PipelineLayout,
     pub render_pipeline: wgpu::RenderPipeline,
+    pub no_cull_render_pipeline: wgpu::RenderPipeline,
+    pub wireframe_render_pipeline: wgpu::RenderPipeline,
+    pub wireframe_no_cull_render_pipeline: wgpu::RenderPipeline,
+    /// Whether bodies are drawn as spheres or points
+    pub style: RenderStyle,
+    /// Draws every body as a point sprite, straight from the instance buffer
+    pub point_render_pipeline: wgpu::RenderPipeline,
+    /// When on, bodies are drawn as wireframes instead of filled triangles
+    pub wireframe: bool,
+    /// When on, bodies the camera is inside of are drawn without back-face culling
+    pub cutaway: bool,
+    /// When off, every body is drawn without back-face culling. Handy for spotting
+    /// triangles wound the wrong way, which vanish with culling on
+    pub culling: bool,
+    /// One instance of the shared sphere mesh per body, rebuilt by write_instances
     pub instances: Vec<instance::Instance>,
     pub instance_buffer: wgpu::Buffer,
+    /// How many instances the instance buffer has room for
+    pub instance_capacity: usize,
+    /// Where each category of instance sits in the instance buffer, filled in by write_instances
+    pub instance_ranges: Vec<InstanceRange>,
+    /// Which body each slot of the instance buffer draws, filled in by write_instances
+    pub instance_order: Vec<u32>,
+    /// How strongly fast instances are stretched along their velocity. 0 disables motion blur
+    pub motion_blur: f32,
+    /// When on, bodies are drawn with a radius worked out from their mass instead of their own
+    /// radius, as if every body were as dense as a planet
+    pub scale_by_mass: bool,
+    /// How strongly bodies deep in a gravity well are tinted red. This is a non-physical
+    /// visualization of time dilation, and 0 (the default) turns it off
+    pub redshift_tint: f32,
+    /// What bodies are tinted by, in place of their own colors and the redshift tint
+    pub color_scale: colormap::ColorScale,
+    /// The values the ends of the colormap stood for the last time write_instances ran,
+    /// or None if bodies kept their own colors
+    pub color_range: Option<(f32, f32)>,
     pub depth_texture: texture::Texture,
     pub camera: camera::Camera,
     pub camera_controller: camera::CameraController,
-    pub camera_bind_group_layout: wgpu::BindGroupLayout,
     pub camera_bind_group: wgpu::BindGroup,
     pub camera_uniform: camera::CameraUniform,
     pub camera_buffer: wgpu::Buffer,
-    pub sphere: sphere::Sphere,
+    pub texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// Wraps around the date line and stops at the poles, for equirectangular maps
+    pub texture_sampler: wgpu::Sampler,
+    /// Every texture a body has been drawn with, loaded as they're first needed.
+    /// The first is plain white, for bodies with no texture of their own
+    pub body_textures: Vec<BodyTexture>,
+    /// Where each texture in body_textures was loaded from
+    pub texture_indices: HashMap<PathBuf, usize>,
+    /// A unit sphere mesh for each of lod_resolutions, finest first.
+    /// Each body is drawn with one of them depending on how big it looks
+    pub spheres: Vec<sphere::Sphere>,
+    /// How many of its own radii away a body has to be to drop to each coarser sphere.
+    /// Lower values trade smoothness for speed
+    pub lod_thresholds: Vec<f32>,
+    /// How every light in the scene fades with distance
+    pub attenuation: Attenuation,
+    /// The light orbiting the scene. It's always the first light, followed by any glowing bodies
     pub light_uniform: LightUniform,
+    /// Every light in the scene, filled in by write_lights
     pub light_buffer: wgpu::Buffer,
-    pub light_bind_group_layout: wgpu::BindGroupLayout,
+    /// How many of the lights in light_buffer are in use
+    pub light_count_buffer: wgpu::Buffer,
     pub light_bind_group: wgpu::BindGroup,
     pub light_render_pipeline: wgpu::RenderPipeline,
+    /// Draws the starfield over the whole screen, behind everything else
+    pub sky_render_pipeline: wgpu::RenderPipeline,
+    /// What's drawn behind the bodies
+    pub background: Background,
+    /// Draws the trails as lines that fade out behind each body
+    pub trail_render_pipeline: wgpu::RenderPipeline,
+    /// The trail line list, rewritten every frame by write_trails
+    pub trail_buffer: wgpu::Buffer,
+    /// How many vertices the trail buffer has room for
+    pub trail_capacity: usize,
+    /// How many vertices of the trail buffer were written this frame
+    pub trail_vertex_count: u32,
+    /// Whether the x, y and z axes are drawn through the origin
+    pub show_axes: bool,
+    /// Whether a grid is drawn in the plane the orbits lie in
+    pub show_grid: bool,
+    /// Whether a wireframe shell is drawn around each body at its Hill radius, the region
+    /// where its own gravity wins out over its primary's, see physics::body_hill_radius
+    pub show_hill_spheres: bool,
+    /// A shell of the coarsest sphere for each Hill sphere, rewritten by write_hill_spheres
+    pub hill_sphere_buffer: wgpu::Buffer,
+    /// How many shells the Hill sphere buffer has room for
+    pub hill_sphere_capacity: usize,
+    /// How many shells of the Hill sphere buffer were written this frame
+    pub hill_sphere_count: u32,
+    /// Draws the comparison run's bodies see-through, GHOST_OPACITY of the way over
+    /// whatever is behind them
+    pub ghost_render_pipeline: wgpu::RenderPipeline,
+    /// One instance of the coarsest sphere per body of the comparison run, rewritten by
+    /// write_ghosts
+    pub ghost_buffer: wgpu::Buffer,
+    /// How many instances the ghost buffer has room for
+    pub ghost_capacity: usize,
+    /// How many instances of the ghost buffer were written this frame, 0 without a
+    /// comparison run
+    pub ghost_count: u32,
+    /// How far the axes and grid reach from the origin, set with set_grid
+    pub grid_extent: f32,
+    /// The axes' line list followed by the grid's, drawn with the trail pipeline
+    pub guide_buffer: wgpu::Buffer,
+    /// What guide_buffer holds, but relative to the world's origin rather than the
+    /// camera's render origin, see write_guides
+    pub guide_vertices: Vec<trail::TrailVertex>,
+    /// How many vertices of the guide buffer belong to the grid, after the axes
+    pub grid_vertex_count: u32,
+    /// The bodies' center of mass and bounding box as of the last write_instances,
+    /// see scene_bounds
+    pub bounds: (Vector3<f32>, Vector3<f32>, Vector3<f32>),
+    /// The glow drawn around bright bodies, with its tunables
+    pub bloom: bloom::Bloom,
+    /// Which passes get drawn each frame
+    pub passes: PassFlags,
+}
+
+/// A surface texture bodies can be drawn with, ready to bind as group 2 of the body shader
+pub struct BodyTexture {
+    /// Binds the texture's view, which keeps the texture alive, and the shared sampler
+    pub bind_group: wgpu::BindGroup,
 }
 
-// Temporary values until we Render more objects
-const NUM_INSTANCES_PER_ROW: u32 = 1;
-const NUM_INSTANCES: u32 = NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW;
-const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
-    NUM_INSTANCES_PER_ROW as f32 * 0.5,
-    0.0,
-    NUM_INSTANCES_PER_ROW as f32 * 0.5,
-);
+/// How many instances the instance buffer starts with room for
+const INITIAL_INSTANCE_CAPACITY: usize = 64;
+/// How many trail vertices the trail buffer starts with room for
+const INITIAL_TRAIL_CAPACITY: usize = 4096;
+/// How much of the comparison run's bodies shows over what's behind them, from 0 to 1
+pub const GHOST_OPACITY: f64 = 0.35;
 
 impl Render {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
-        let camera = camera::Camera::new(config.width as f32, config.height as f32);
+    /// Sets up everything needed to draw, with the finest body mesh `sphere_resolution`
+    /// vertices along each cube face edge. Fails if that's too few to make a mesh
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        sphere_resolution: u32,
+    ) -> Result<Self> {
+        let camera = camera::Camera::new(config);
 
-        let camera_controller = camera::CameraController::new(0.2);
+        let camera_controller = camera::CameraController::new(12.0);
 
         let mut camera_uniform = camera::CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
@@ -64,7 +600,8 @@ impl Render {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // The sky works out view directions per pixel, so fragments read the camera too
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -84,70 +621,132 @@ impl Render {
             label: Some("camera_bind_group"),
         });
 
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+        let depth_texture = texture::Texture::create_depth_texture(device, config, "depth_texture");
 
-        let light_uniform = LightUniform {
-            position: [2.0, 2.0, 2.0],
-            _padding: 0,
-            color: [1.0, 1.0, 1.0],
-        };
+        let attenuation = Attenuation::default();
+        let light_uniform = LightUniform::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0], attenuation);
 
-        // We'll want to update our lights position, so we use COPY_DST
+        // We'll want to update our lights every frame, so we use COPY_DST
+        let mut lights = [LightUniform::zeroed(); MAX_LIGHTS];
+        lights[0] = light_uniform;
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Light VB"),
-            contents: bytemuck::cast_slice(&[light_uniform]),
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&lights),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_count_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Count Buffer"),
+            contents: bytemuck::cast_slice(&[LightCount {
+                num_lights: 1,
+                _padding: [0; 3],
+            }]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         let light_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: None,
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
             });
 
         let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_count_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+            ],
             label: None,
         });
 
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("texture_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler {
+                            comparison: false,
+                            filtering: true,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Body Texture Sampler"),
+            // u runs past 1 on triangles across the date line, see sphere::split_uv_seam
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let white = texture::Texture::from_color(device, queue, [255; 4], "White Texture")?;
+        let body_textures = vec![create_body_texture(
+            device,
+            &texture_bind_group_layout,
+            &texture_sampler,
+            white,
+        )];
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
+                bind_group_layouts: &[
+                    &camera_bind_group_layout,
+                    &light_bind_group_layout,
+                    &texture_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-            };
-            create_render_pipeline(
-                &device,
-                &render_pipeline_layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[
-                    sphere::SphereMeshVertex::desc(),
-                    instance::InstanceRaw::desc(),
-                ],
-                shader,
-            )
-        };
+        let body_pipelines = create_body_pipelines(
+            device,
+            &render_pipeline_layout,
+            config.format,
+            camera.depth_compare(),
+            include_str!("shader.wgsl"),
+        );
 
         let light_render_pipeline = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -160,80 +759,789 @@ impl Render {
                 source: wgpu::ShaderSource::Wgsl(include_str!("light.wgsl").into()),
             };
             create_render_pipeline(
-                &device,
+                device,
                 &layout,
                 config.format,
                 Some(texture::Texture::DEPTH_FORMAT),
+                camera.depth_compare(),
+                Some(wgpu::Face::Back),
+                wgpu::PolygonMode::Line,
+                false,
                 &[sphere::SphereMeshVertex::desc()],
                 shader,
             )
         };
 
-        let sphere = sphere::Sphere::new(10, &device);
-
-        const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..NUM_INSTANCES_PER_ROW)
-            .flat_map(|z| {
-                (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                    let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                    let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                    let position = cgmath::Vector3 { x, y: 0.0, z };
-
-                    let rotation = if position.is_zero() {
-                        cgmath::Quaternion::from_axis_angle(
-                            cgmath::Vector3::unit_z(),
-                            cgmath::Deg(0.0),
-                        )
-                    } else {
-                        cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                    };
-                    instance::Instance { position, rotation }
-                })
-            })
-            .collect::<Vec<_>>();
+        let sky_render_pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Sky Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Sky Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("sky.wgsl").into()),
+            };
+            create_sky_pipeline(
+                device,
+                &layout,
+                config.format,
+                texture::Texture::DEPTH_FORMAT,
+                shader,
+            )
+        };
+
+        let point_render_pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Point Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Point Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("points.wgsl").into()),
+            };
+            create_point_pipeline(
+                device,
+                &layout,
+                config.format,
+                texture::Texture::DEPTH_FORMAT,
+                camera.depth_compare(),
+                shader,
+            )
+        };
 
-        let instance_data = instances
+        let trail_render_pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Trail Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Trail Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("trail.wgsl").into()),
+            };
+            create_trail_pipeline(
+                device,
+                &layout,
+                config.format,
+                texture::Texture::DEPTH_FORMAT,
+                camera.depth_compare(),
+                shader,
+            )
+        };
+        let grid_extent = grid::DEFAULT_GRID_EXTENT;
+        let grid_spacing = grid::DEFAULT_GRID_SPACING;
+        let (guide_buffer, guide_vertices, grid_vertex_count) =
+            create_guide_buffer(device, grid_extent, grid_spacing);
+
+        let trail_capacity = INITIAL_TRAIL_CAPACITY;
+        let trail_buffer = create_trail_buffer(device, trail_capacity);
+
+        let spheres = lod_resolutions(sphere_resolution)
             .iter()
-            .map(instance::Instance::to_raw)
-            .collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+            .map(|&resolution| sphere::Sphere::new(resolution, device))
+            .collect::<Result<Vec<_>>>()?;
 
-        Self {
+        let instance_capacity = INITIAL_INSTANCE_CAPACITY;
+        let instance_buffer = create_instance_buffer(device, instance_capacity);
+        let hill_sphere_buffer = create_instance_buffer(device, instance_capacity);
+        let ghost_buffer = create_instance_buffer(device, instance_capacity);
+
+        Ok(Self {
+            #[cfg(feature = "dev")]
             render_pipeline_layout,
-            render_pipeline,
-            instances,
+            render_pipeline: body_pipelines.render,
+            no_cull_render_pipeline: body_pipelines.no_cull,
+            wireframe_render_pipeline: body_pipelines.wireframe,
+            wireframe_no_cull_render_pipeline: body_pipelines.wireframe_no_cull,
+            ghost_render_pipeline: body_pipelines.ghost,
+            style: RenderStyle::Spheres,
+            point_render_pipeline,
+            wireframe: true,
+            cutaway: true,
+            culling: true,
+            instances: Vec::new(),
+            instance_ranges: Vec::new(),
+            instance_order: Vec::new(),
             instance_buffer,
+            instance_capacity,
+            motion_blur: 0.0,
+            scale_by_mass: false,
+            redshift_tint: 0.0,
+            color_scale: colormap::ColorScale::default(),
+            color_range: None,
             depth_texture,
             camera,
             camera_controller,
-            camera_bind_group_layout,
             camera_bind_group,
             camera_uniform,
             camera_buffer,
-            sphere,
+            texture_bind_group_layout,
+            texture_sampler,
+            body_textures,
+            texture_indices: HashMap::new(),
+            spheres,
+            lod_thresholds: DEFAULT_LOD_THRESHOLDS.to_vec(),
+            attenuation,
             light_uniform,
             light_buffer,
-            light_bind_group_layout,
+            light_count_buffer,
             light_bind_group,
             light_render_pipeline,
+            sky_render_pipeline,
+            background: Background::Starfield,
+            trail_render_pipeline,
+            trail_buffer,
+            trail_capacity,
+            trail_vertex_count: 0,
+            show_axes: false,
+            show_grid: false,
+            show_hill_spheres: false,
+            hill_sphere_buffer,
+            hill_sphere_capacity: instance_capacity,
+            hill_sphere_count: 0,
+            ghost_buffer,
+            ghost_capacity: instance_capacity,
+            ghost_count: 0,
+            grid_extent,
+            guide_buffer,
+            guide_vertices,
+            grid_vertex_count,
+            bounds: (Vector3::zero(), Vector3::zero(), Vector3::zero()),
+            bloom: bloom::Bloom::new(device, config),
+            passes: PassFlags::ALL,
+        })
+    }
+
+    /// The pipeline to draw a category of instances with, given the current culling and
+    /// wireframe settings
+    pub fn body_pipeline(&self, category: InstanceCategory) -> &wgpu::RenderPipeline {
+        match (self.wireframe, category.culled(self.culling)) {
+            (false, true) => &self.render_pipeline,
+            (false, false) => &self.no_cull_render_pipeline,
+            (true, true) => &self.wireframe_render_pipeline,
+            (true, false) => &self.wireframe_no_cull_render_pipeline,
+        }
+    }
+
+    /// Rebuilds the instances from the bodies being simulated and packs them into the
+    /// instance buffer, grouped by how they need to be drawn. Moving instances are stretched
+    /// by how far they travel in `dt`, bodies are tinted by color_scale unless it leaves
+    /// them their own colors, and the `selected` body is highlighted. The clipping
+    /// planes are fitted to the bodies first, then bodies the camera can't see are left out.
+    /// The buffer is doubled whenever the bodies outgrow it
+    pub fn write_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+        selected: Option<usize>,
+        dt: f32,
+    ) {
+        let textures = entities
+            .iter()
+            .map(|entity| match &entity.texture {
+                Some(path) => self.texture_index(device, queue, path),
+                None => 0,
+            })
+            .collect::<Vec<_>>();
+        let tint = self.color_scale.colors(entities);
+        self.color_range = tint.as_ref().map(|tint| (tint.min, tint.max));
+        // Summing every pair each frame is far too slow for big scenes, so the redshift
+        // depths come from an octree
+        let depths = (tint.is_none() && self.redshift_tint > 0.0)
+            .then(|| physics::potential_depths(entities, physics::DEFAULT_THETA));
+        self.instances = entities
+            .iter()
+            .enumerate()
+            .map(|(i, entity)| {
+                let mut instance = instance::Instance::from_entity(entity);
+                instance.texture = textures[i];
+                if self.scale_by_mass {
+                    let planet = sphere::BodyType::Planet;
+                    instance.radius =
+                        instance::radius_for_mass(entity.mass, planet.mass(), planet.radius());
+                }
+                if let Some(tint) = &tint {
+                    instance.color = tint.colors[i];
+                } else if let Some(depths) = &depths {
+                    instance.color = sphere::redshift(entity.color, depths[i], self.redshift_tint);
+                }
+                if selected == Some(i) {
+                    instance.color = highlight(instance.color);
+                }
+                instance
+            })
+            .collect();
+
+        // Grown in powers of two so adding bodies one at a time rarely reallocates.
+        // Nothing needs copying over, since every instance is packed into it below
+        if self.instances.len() > self.instance_capacity {
+            self.instance_capacity = self.instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.instance_capacity);
+        }
+
+        let (center, min, max) = physics::scene_bounds(entities);
+        let narrow = |v: Vector3<f64>| v.map(|x| x as f32);
+        self.bounds = (narrow(center), narrow(min), narrow(max));
+        // Before culling, so bodies that have moved past the old clipping planes aren't dropped
+        self.fit_depth_range();
+        let (order, ranges) = pack_instances(
+            &self.instances,
+            &self.camera,
+            self.cutaway,
+            &self.lod_thresholds,
+        );
+        let origin = self.camera.render_origin();
+        let instance_data = order
+            .iter()
+            .map(|&i| self.instances[i].to_raw_blurred(origin, dt, self.motion_blur))
+            .collect::<Vec<_>>();
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&instance_data),
+        );
+        self.instance_order = order.iter().map(|&i| i as u32).collect();
+        self.instance_ranges = ranges;
+    }
+
+    /// Where in body_textures the texture at `path` is, loading it the first time it's asked
+    /// for. A texture that fails to load is reported once and drawn as plain white
+    pub fn texture_index(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+    ) -> usize {
+        if let Some(&index) = self.texture_indices.get(path) {
+            return index;
+        }
+        let index = match texture::Texture::load(device, queue, path) {
+            Ok(texture) => {
+                self.body_textures.push(create_body_texture(
+                    device,
+                    &self.texture_bind_group_layout,
+                    &self.texture_sampler,
+                    texture,
+                ));
+                self.body_textures.len() - 1
+            }
+            Err(e) => {
+                log::warn!("Failed to load texture {}: {:?}", path.display(), e);
+                0
+            }
+        };
+        self.texture_indices.insert(path.to_path_buf(), index);
+        index
+    }
+
+    /// The center of mass of the bodies last written to the instance buffer, and the
+    /// corners of the box around them, as (center_of_mass, aabb_min, aabb_max)
+    pub fn scene_bounds(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        self.bounds
+    }
+
+    /// Rebuilds the body pipelines from `source`, a new version of shader.wgsl.
+    /// `source` must already be known to be valid, since wgpu panics on a bad shader
+    #[cfg(feature = "dev")]
+    pub fn set_body_shader(
+        &mut self,
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        source: &str,
+    ) {
+        let body_pipelines = create_body_pipelines(
+            device,
+            &self.render_pipeline_layout,
+            color_format,
+            self.camera.depth_compare(),
+            source,
+        );
+        self.render_pipeline = body_pipelines.render;
+        self.no_cull_render_pipeline = body_pipelines.no_cull;
+        self.wireframe_render_pipeline = body_pipelines.wireframe;
+        self.wireframe_no_cull_render_pipeline = body_pipelines.wireframe_no_cull;
+        self.ghost_render_pipeline = body_pipelines.ghost;
+    }
+
+    /// Fits the camera's clipping planes around the bodies last written to the instance
+    /// buffer, and the axes and grid when they're shown. Does nothing unless the camera's
+    /// auto_depth_range is on
+    pub fn fit_depth_range(&mut self) {
+        if !self.camera.auto_depth_range {
+            return;
+        }
+        let (_, mut min, mut max) = self.bounds;
+        if self.show_axes || self.show_grid {
+            let extent = Vector3::from_value(self.grid_extent);
+            min = Vector3::new(
+                min.x.min(-extent.x),
+                min.y.min(-extent.y),
+                min.z.min(-extent.z),
+            );
+            max = Vector3::new(
+                max.x.max(extent.x),
+                max.y.max(extent.y),
+                max.z.max(extent.z),
+            );
+        }
+        let spheres = self
+            .instances
+            .iter()
+            .map(|instance| (Point3::from_vec(instance.position), instance.radius));
+        self.camera
+            .fit_depth_range(Point3::from_vec(min), Point3::from_vec(max), spheres);
+    }
+
+    /// The passes drawn this frame, see PassFlags::drawn
+    pub fn drawn_passes(&self) -> PassFlags {
+        self.passes.drawn(PassContent {
+            starfield: self.background == Background::Starfield,
+            guides: self.show_axes || self.show_grid,
+            trails: self.trail_vertex_count > 0,
+            bloom: self.bloom.enabled(),
+            ghosts: self.ghost_count > 0,
+            hill_spheres: self.show_hill_spheres && self.hill_sphere_count > 0,
+        })
+    }
+
+    /// Uploads the orbiting light followed by a light for every glowing body,
+    /// up to MAX_LIGHTS in total
+    pub fn write_lights(&mut self, queue: &wgpu::Queue, entities: &[Entity]) {
+        let attenuation = self.attenuation;
+        self.light_uniform.set_attenuation(attenuation);
+        let origin = self.camera.render_origin();
+        let lights = std::iter::once(self.light_uniform)
+            .chain(
+                entities
+                    .iter()
+                    .filter(|entity| entity.light_source)
+                    .map(|entity| LightUniform::from_emitter(entity, attenuation)),
+            )
+            .take(MAX_LIGHTS)
+            .map(|mut light| {
+                light.position = (Vector3::from(light.position) - origin).into();
+                light
+            })
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&lights));
+        queue.write_buffer(
+            &self.light_count_buffer,
+            0,
+            bytemuck::cast_slice(&[LightCount {
+                num_lights: lights.len() as u32,
+                _padding: [0; 3],
+            }]),
+        );
+    }
+
+    /// Rebuilds the axes and grid to reach `extent` from the origin, with grid lines
+    /// `spacing` apart
+    pub fn set_grid(&mut self, device: &wgpu::Device, extent: f32, spacing: f32) {
+        let (guide_buffer, guide_vertices, grid_vertex_count) =
+            create_guide_buffer(device, extent, spacing);
+        self.guide_buffer = guide_buffer;
+        self.guide_vertices = guide_vertices;
+        self.grid_vertex_count = grid_vertex_count;
+        self.grid_extent = extent;
+    }
+
+    /// Moves the axes and grid in guide_buffer to where they are relative to the camera's
+    /// render origin
+    pub fn write_guides(&self, queue: &wgpu::Queue) {
+        let offset = -self.camera.render_origin();
+        let vertices = self
+            .guide_vertices
+            .iter()
+            .map(|vertex| vertex.translated(offset))
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.guide_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    /// Uploads a shell in each body's color at its Hill radius. Bodies with nothing to orbit
+    /// get none. The buffer is doubled whenever the shells outgrow it
+    pub fn write_hill_spheres(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+    ) {
+        let origin = self.camera.render_origin();
+        let shells = (0..entities.len())
+            .filter_map(|i| {
+                let mut shell = instance::Instance::from_entity(&entities[i]);
+                shell.radius = physics::body_hill_radius(entities, i)?;
+                // At full brightness, so the lines show whichever way the light falls
+                shell.emissive = true;
+                Some(shell.to_raw_blurred(origin, 0.0, 0.0))
+            })
+            .collect::<Vec<_>>();
+        if shells.len() > self.hill_sphere_capacity {
+            self.hill_sphere_capacity = shells.len().next_power_of_two();
+            self.hill_sphere_buffer = create_instance_buffer(device, self.hill_sphere_capacity);
+        }
+        queue.write_buffer(&self.hill_sphere_buffer, 0, bytemuck::cast_slice(&shells));
+        self.hill_sphere_count = shells.len() as u32;
+    }
+
+    /// Uploads an instance for each body of the comparison run, in its own color but without
+    /// motion blur or tints. The buffer is doubled whenever the bodies outgrow it
+    pub fn write_ghosts(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+    ) {
+        let origin = self.camera.render_origin();
+        let ghosts = entities
+            .iter()
+            .map(|entity| instance::Instance::from_entity(entity).to_raw_blurred(origin, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        if ghosts.len() > self.ghost_capacity {
+            self.ghost_capacity = ghosts.len().next_power_of_two();
+            self.ghost_buffer = create_instance_buffer(device, self.ghost_capacity);
         }
+        queue.write_buffer(&self.ghost_buffer, 0, bytemuck::cast_slice(&ghosts));
+        self.ghost_count = ghosts.len() as u32;
+    }
+
+    /// Uploads the trail behind each body as a line list, each in its own color.
+    /// The buffer is doubled whenever the trails outgrow it
+    pub fn write_trails(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        trails: &[TrailBuffer],
+    ) {
+        let vertices = trail::trail_vertices(trails, self.camera.render_origin());
+        if vertices.len() > self.trail_capacity {
+            self.trail_capacity = vertices.len().max(self.trail_capacity * 2);
+            self.trail_buffer = create_trail_buffer(device, self.trail_capacity);
+        }
+        queue.write_buffer(&self.trail_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.trail_vertex_count = vertices.len() as u32;
+    }
+}
+
+/// The vertices of the guide buffer taken up by the axes
+pub const AXIS_VERTEX_COUNT: u32 = 6;
+
+/// Builds the axes and grid reaching `extent` from the origin, with grid lines `spacing` apart.
+/// Returns the buffer, the vertices in it and how many of them make up the grid
+fn create_guide_buffer(
+    device: &wgpu::Device,
+    extent: f32,
+    spacing: f32,
+) -> (wgpu::Buffer, Vec<trail::TrailVertex>, u32) {
+    let mut vertices = grid::axis_vertices(extent);
+    let grid = grid::grid_vertices(extent, spacing);
+    let grid_vertex_count = grid.len() as u32;
+    vertices.extend(grid);
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Guide Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        // COPY_DST so write_guides can move it to the render origin
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    (buffer, vertices, grid_vertex_count)
+}
+
+fn create_trail_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Trail Buffer"),
+        size: (capacity * std::mem::size_of::<trail::TrailVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Draws each instance as a camera-facing quad, four vertices of a triangle strip made up
+/// by the shader. The soft edges are alpha blended but still write depth, so sprites hide
+/// the bodies behind them. The shader leaves out the faintest edges so they don't
+fn create_point_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    depth_compare: wgpu::CompareFunction,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Point Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[instance::InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+/// Like create_render_pipeline, but draws alpha blended lines that are depth tested
+/// without writing depth, so trails fade out over whatever is behind them
+fn create_trail_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    depth_compare: wgpu::CompareFunction,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Trail Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[trail::TrailVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: false,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+/// Builds the pipeline for the full screen sky. It has no vertex buffers, and neither tests
+/// nor writes depth, so drawing it first leaves it behind everything drawn after
+fn create_sky_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Sky Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            clamp_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+    })
+}
+
+/// The color the selected body is pulled towards, so it stands out whatever its own color
+const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+/// How far the selected body's color is pulled towards HIGHLIGHT_COLOR
+const HIGHLIGHT_STRENGTH: f32 = 0.6;
+
+/// `color` shifted towards HIGHLIGHT_COLOR to mark the selected body
+fn highlight(color: [f32; 3]) -> [f32; 3] {
+    let mix = |from: f32, to: f32| from + (to - from) * HIGHLIGHT_STRENGTH;
+    [
+        mix(color[0], HIGHLIGHT_COLOR[0]),
+        mix(color[1], HIGHLIGHT_COLOR[1]),
+        mix(color[2], HIGHLIGHT_COLOR[2]),
+    ]
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: (capacity * std::mem::size_of::<instance::InstanceRaw>()) as wgpu::BufferAddress,
+        // STORAGE so the gpu-gravity compute pass can move the instances in place
+        usage: wgpu::BufferUsages::VERTEX
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
+
+/// The pipelines every body is drawn with, all from the same shader. They only differ
+/// in culling and whether triangles are filled
+struct BodyPipelines {
+    render: wgpu::RenderPipeline,
+    /// Draws back faces too, so a body the camera has flown into is seen from the inside
+    /// instead of vanishing
+    no_cull: wgpu::RenderPipeline,
+    /// Wireframe versions of the two above, for inspecting the mesh
+    wireframe: wgpu::RenderPipeline,
+    wireframe_no_cull: wgpu::RenderPipeline,
+    /// Blends the bodies over what's behind them, for the comparison run
+    ghost: wgpu::RenderPipeline,
+}
+
+fn create_body_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_compare: wgpu::CompareFunction,
+    source: &str,
+) -> BodyPipelines {
+    let body_pipeline = |label, cull_mode, polygon_mode, translucent| {
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        };
+        create_render_pipeline(
+            device,
+            layout,
+            color_format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            depth_compare,
+            cull_mode,
+            polygon_mode,
+            translucent,
+            &[
+                sphere::SphereMeshVertex::desc(),
+                instance::InstanceRaw::desc(),
+            ],
+            shader,
+        )
+    };
+    BodyPipelines {
+        render: body_pipeline(
+            "Normal Shader",
+            Some(wgpu::Face::Back),
+            wgpu::PolygonMode::Fill,
+            false,
+        ),
+        no_cull: body_pipeline("No Cull Shader", None, wgpu::PolygonMode::Fill, false),
+        wireframe: body_pipeline(
+            "Wireframe Shader",
+            Some(wgpu::Face::Back),
+            wgpu::PolygonMode::Line,
+            false,
+        ),
+        wireframe_no_cull: body_pipeline(
+            "Wireframe No Cull Shader",
+            None,
+            wgpu::PolygonMode::Line,
+            false,
+        ),
+        ghost: body_pipeline(
+            "Ghost Shader",
+            Some(wgpu::Face::Back),
+            wgpu::PolygonMode::Fill,
+            true,
+        ),
     }
 }
 
+/// Translucent pipelines blend with the render pass's blend constant and leave the depth
+/// buffer alone, so whatever is behind them still shows through
+#[allow(clippy::too_many_arguments)]
 fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
     color_format: wgpu::TextureFormat,
     depth_format: Option<wgpu::TextureFormat>,
+    depth_compare: wgpu::CompareFunction,
+    cull_mode: Option<wgpu::Face>,
+    polygon_mode: wgpu::PolygonMode,
+    translucent: bool,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(&shader);
+    let blend = if translucent {
+        wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Constant,
+                dst_factor: wgpu::BlendFactor::OneMinusConstant,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        }
+    } else {
+        wgpu::BlendState::REPLACE
+    };
 
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
@@ -248,26 +1556,25 @@ fn create_render_pipeline(
             entry_point: "fs_main",
             targets: &[wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
+                blend: Some(blend),
                 write_mask: wgpu::ColorWrites::ALL,
             }],
         }),
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
             strip_index_format: None,
-            front_face: wgpu::FrontFace::Cw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Line,
+            // face_geometry in sphere.rs winds its triangles counterclockwise
+            // when seen from outside the sphere
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode,
+            polygon_mode,
             clamp_depth: false,
             conservative: false,
         },
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_write_enabled: !translucent,
+            depth_compare,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
@@ -278,3 +1585,184 @@ fn create_render_pipeline(
         },
     })
 }
+
+fn create_body_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    texture: texture::Texture,
+) -> BodyTexture {
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("body_texture_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    BodyTexture { bind_group }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame with something to draw in every pass
+    const EVERYTHING: PassContent = PassContent {
+        starfield: true,
+        guides: true,
+        trails: true,
+        bloom: true,
+        ghosts: true,
+        hill_spheres: true,
+    };
+
+    /// A camera at the origin looking down -z
+    fn camera() -> camera::Camera {
+        let mut camera = camera::Camera::new(&wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 800,
+            height: 600,
+            present_mode: wgpu::PresentMode::Fifo,
+        });
+        camera.eye = Point3::origin();
+        camera.target = Point3::new(0.0, 0.0, -1.0);
+        camera
+    }
+
+    /// A plain white body of `radius` at `position`
+    fn body(position: Vector3<f32>, radius: f32) -> instance::Instance {
+        instance::Instance {
+            position,
+            rotation: cgmath::Quaternion::one(),
+            velocity: Vector3::zero(),
+            radius,
+            color: [1.0; 3],
+            emissive: false,
+            texture: 0,
+        }
+    }
+
+    #[test]
+    fn body_around_the_camera_is_drawn_without_culling() {
+        let camera = camera();
+        let instances = [
+            body(Vector3::new(0.0, 0.0, -5.0), 1.0),
+            body(Vector3::new(0.0, 0.0, 0.5), 1.0),
+        ];
+        let (order, ranges) = pack_instances(&instances, &camera, true, &DEFAULT_LOD_THRESHOLDS);
+        assert_eq!(order, vec![0, 1]);
+        assert_eq!(ranges[0].category, InstanceCategory::Opaque);
+        assert_eq!(ranges[1].category, InstanceCategory::Inside);
+        assert_eq!(ranges[1].range, 1..2);
+        assert!(!ranges[1].category.culled(true));
+        assert!(ranges[0].category.culled(true));
+
+        // With the cutaway off the camera sees nothing of the body it's inside
+        let (_, ranges) = pack_instances(&instances, &camera, false, &DEFAULT_LOD_THRESHOLDS);
+        assert!(ranges
+            .iter()
+            .all(|range| range.category == InstanceCategory::Opaque));
+    }
+
+    #[test]
+    fn instances_are_packed_into_sorted_ranges() {
+        let camera = camera();
+        // A radius of 0.1, so the coarser meshes start 4 and 15 units away
+        let ahead = |distance: f32| body(Vector3::new(0.0, 0.0, -distance), 0.1);
+        let mut textured = ahead(3.5);
+        textured.texture = 1;
+        let instances = [
+            ahead(3.0),
+            ahead(2.0),
+            textured,
+            ahead(10.0),
+            // Behind the camera
+            body(Vector3::new(0.0, 0.0, 5.0), 0.1),
+            ahead(50.0),
+            // Around the camera
+            body(Vector3::new(0.0, 0.0, -0.5), 1.0),
+        ];
+        let (order, ranges) = pack_instances(&instances, &camera, true, &DEFAULT_LOD_THRESHOLDS);
+
+        // Grouped by category, then detail, then texture, and front to back within each
+        assert_eq!(order, vec![1, 0, 2, 3, 5, 6]);
+        let expected = [
+            (InstanceCategory::Opaque, 0, 0, 0..2),
+            (InstanceCategory::Opaque, 0, 1, 2..3),
+            (InstanceCategory::Opaque, 1, 0, 3..4),
+            (InstanceCategory::Opaque, 2, 0, 4..5),
+            (InstanceCategory::Inside, 0, 0, 5..6),
+        ];
+        assert_eq!(ranges.len(), expected.len());
+        for (range, (category, lod, texture, slots)) in ranges.iter().zip(expected) {
+            assert_eq!(range.category, category);
+            assert_eq!(range.lod, lod);
+            assert_eq!(range.texture, texture);
+            assert_eq!(range.range, slots);
+        }
+    }
+
+    #[test]
+    fn only_enabled_passes_are_drawn() {
+        assert_eq!(PassFlags::BODIES.drawn(EVERYTHING), PassFlags::BODIES);
+        assert_eq!(PassFlags::ALL.drawn(EVERYTHING), PassFlags::ALL);
+        assert_eq!(PassFlags(0).drawn(EVERYTHING), PassFlags(0));
+    }
+
+    #[test]
+    fn passes_with_nothing_to_draw_are_skipped() {
+        let nothing = PassContent {
+            starfield: false,
+            guides: false,
+            trails: false,
+            bloom: false,
+            ghosts: false,
+            hill_spheres: false,
+        };
+        let drawn = PassFlags::ALL.drawn(nothing);
+        for pass in [PassFlags::BODIES, PassFlags::LIGHT, PassFlags::UI] {
+            assert!(drawn.contains(pass));
+        }
+        for pass in [
+            PassFlags::SKYBOX,
+            PassFlags::GRID,
+            PassFlags::TRAILS,
+            PassFlags::BLOOM,
+            PassFlags::GHOSTS,
+            PassFlags::HILL_SPHERES,
+        ] {
+            assert!(!drawn.contains(pass));
+        }
+    }
+
+    #[test]
+    fn only_the_bodies_are_recorded_with_only_their_pass_on() {
+        let recorded: Vec<_> = PassFlags::BODIES.drawn(EVERYTHING).scene_passes().collect();
+        assert_eq!(recorded, [ScenePass::Bodies]);
+    }
+
+    #[test]
+    fn ghosts_and_hill_spheres_are_switched_off_on_their_own() {
+        let all: Vec<_> = PassFlags::ALL.drawn(EVERYTHING).scene_passes().collect();
+        assert_eq!(all, ScenePass::ORDER);
+
+        for (flag, pass) in [
+            (PassFlags::GHOSTS, ScenePass::Ghosts),
+            (PassFlags::HILL_SPHERES, ScenePass::HillSpheres),
+        ] {
+            let mut passes = PassFlags::ALL;
+            passes.toggle(flag);
+            let recorded: Vec<_> = passes.drawn(EVERYTHING).scene_passes().collect();
+            assert!(!recorded.contains(&pass));
+            assert!(recorded.contains(&ScenePass::Bodies));
+        }
+    }
+}
@@ -0,0 +1,668 @@
+use crate::physics;
+use crate::sphere::{BodyType, Entity};
+use crate::units::UnitSystem;
+use anyhow::{Context, Result};
+use cgmath::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Everything needed to place a single body in a scene
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyConfig {
+    /// The preset the body is built from
+    pub body_type: BodyType,
+    /// Where the body starts
+    pub position: Vector3<f32>,
+    /// How fast and which way the body starts moving
+    pub velocity: Vector3<f32>,
+    /// How strongly the body pulls on the others
+    pub mass: f32,
+    /// How big the body is
+    pub radius: f32,
+    /// The body's color as red, green and blue from 0 to 1
+    pub color: [f32; 3],
+    /// An equirectangular image to wrap around the body, e.g. a map of the Earth.
+    /// It's tinted by `color`, so textured bodies usually want white.
+    /// Relative paths are relative to the scene file
+    #[serde(default)]
+    pub texture: Option<PathBuf>,
+    /// The index of the body this body's Hill sphere is measured against. Scene files
+    /// that leave it out use the heavier body pulling hardest on it
+    #[serde(default)]
+    pub hill_primary: Option<usize>,
+}
+
+impl BodyConfig {
+    /// Describes a body with the defaults of its body type
+    pub fn new(body_type: BodyType, position: Vector3<f32>, velocity: Vector3<f32>) -> Self {
+        Self {
+            body_type,
+            position,
+            velocity,
+            mass: body_type.mass(),
+            radius: body_type.radius(),
+            color: body_type.color(),
+            texture: None,
+            hill_primary: None,
+        }
+    }
+
+    /// Combines two bodies into one, conserving mass, momentum and volume
+    fn merged(&self, other: &BodyConfig) -> BodyConfig {
+        let mass = self.mass + other.mass;
+        let weight = |a: f32, b: f32| (a * self.mass + b * other.mass) / mass;
+        let heavier = if self.mass >= other.mass { self } else { other };
+        BodyConfig {
+            body_type: heavier.body_type,
+            position: (self.position * self.mass + other.position * other.mass) / mass,
+            velocity: (self.velocity * self.mass + other.velocity * other.mass) / mass,
+            mass,
+            radius: (self.radius.powi(3) + other.radius.powi(3)).cbrt(),
+            color: [
+                weight(self.color[0], other.color[0]),
+                weight(self.color[1], other.color[1]),
+                weight(self.color[2], other.color[2]),
+            ],
+            texture: heavier.texture.clone(),
+            hill_primary: heavier.hill_primary,
+        }
+    }
+}
+
+/// What to do with bodies that start out overlapping each other
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OverlapPolicy {
+    /// Push the bodies apart until they just touch
+    Nudge,
+    /// Replace the bodies with a single body with their combined mass and momentum
+    Merge,
+}
+
+/// How many times resolve_initial_overlaps nudges bodies apart before giving up on crowded scenes
+const MAX_OVERLAP_PASSES: usize = 100;
+
+/// The light that lights up the bodies, apart from any glowing bodies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightConfig {
+    /// Where the light starts
+    pub position: Vector3<f32>,
+    /// The light's color as red, green and blue from 0 to 1
+    pub color: [f32; 3],
+    /// How many degrees a second the light turns about the y axis, 0 to hold it still.
+    /// Scene files that leave it out use state::DEFAULT_LIGHT_ROTATION_SPEED
+    #[serde(default)]
+    pub rotation_speed: Option<f32>,
+}
+
+/// How the bodies of a random cloud are spread out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CloudShape {
+    /// Evenly through a ball
+    Sphere,
+    /// Evenly over a thin disk in the xz plane
+    Disk,
+}
+
+/// How thick a disk cloud is, as a fraction of its radius
+const DISK_THICKNESS: f32 = 0.05;
+
+/// The settings for Scene::random_cloud
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CloudParams {
+    /// How the bodies are spread out
+    pub shape: CloudShape,
+    /// How far from the center bodies are placed
+    pub radius: f32,
+    /// The lightest a body can be. Each body's mass is picked evenly between the two
+    pub min_mass: f32,
+    /// The heaviest a body can be
+    pub max_mass: f32,
+    /// The radius of every body
+    pub body_radius: f32,
+    /// How fast the cloud starts turning about the y axis, as a fraction of the speed each
+    /// body would need to orbit the mass inside it. 0 lets the cloud collapse, 1 roughly
+    /// holds it up
+    pub spin: f32,
+    /// When set, the cloud is spun up or down on top of `spin` until its total angular
+    /// momentum about its center of mass is this, see physics::set_angular_momentum.
+    /// A large value flattens the collapse into a disk
+    pub angular_momentum: Option<Vector3<f32>>,
+    /// What to do with bodies that land on top of each other
+    pub overlaps: OverlapPolicy,
+}
+
+impl Default for CloudParams {
+    fn default() -> Self {
+        Self {
+            shape: CloudShape::Sphere,
+            radius: 20.0,
+            min_mass: 0.5,
+            max_mass: 2.0,
+            body_radius: 0.1,
+            spin: 0.5,
+            angular_momentum: None,
+            overlaps: OverlapPolicy::Nudge,
+        }
+    }
+}
+
+/// The initial conditions of a simulation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Scene {
+    /// Every body in the scene, as it starts out
+    pub bodies: Vec<BodyConfig>,
+    /// The units the bodies are described in, which also decides the value of G.
+    /// Scene files that leave it out use natural units
+    #[serde(default)]
+    pub units: UnitSystem,
+    /// The softening length in the scene's units, which should be well below the closest
+    /// approach worth simulating accurately. Scene files that leave it out use
+    /// physics::DEFAULT_SOFTENING
+    #[serde(default)]
+    pub softening: Option<f32>,
+    /// A flat color to draw behind the bodies, as red, green and blue from 0 to 1.
+    /// Scene files that leave it out get the starfield
+    #[serde(default)]
+    pub background: Option<[f32; 3]>,
+    /// Where the light that lights up the bodies starts, and how it moves.
+    /// Scene files that leave it out get a white light turning slowly about the scene
+    #[serde(default)]
+    pub light: Option<LightConfig>,
+    /// How many vertices along each edge of the six cube faces the finest body mesh is
+    /// built from, at least sphere::MIN_SPHERE_RESOLUTION. Coarser meshes for distant
+    /// bodies are derived from it. Scene files that leave it out use
+    /// sphere::DEFAULT_SPHERE_RESOLUTION
+    #[serde(default)]
+    pub sphere_resolution: Option<u32>,
+}
+
+/// The radius of the innermost orbit in the demo scenes
+const DEMO_ORBIT_RADIUS: f32 = 5.0;
+
+impl Scene {
+    /// Reads a scene from a RON file, see the scenes folder for examples
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scene file {}", path.display()))?;
+        let mut scene: Self = ron::from_str(&contents)
+            .with_context(|| format!("Failed to parse scene file {}", path.display()))?;
+
+        // Textures sit next to the scene that uses them, wherever it's run from
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        for body in &mut scene.bodies {
+            if let Some(texture) = &mut body.texture {
+                *texture = directory.join(&*texture);
+            }
+        }
+        Ok(scene)
+    }
+
+    /// Every pair of bodies that are closer than the sum of their radii
+    pub fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..self.bodies.len() {
+            for j in (i + 1)..self.bodies.len() {
+                let (a, b) = (&self.bodies[i], &self.bodies[j]);
+                if (b.position - a.position).magnitude() < a.radius + b.radius {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Overlapping bodies produce huge accelerations on the very first step,
+    /// so this separates or merges them before the simulation starts.
+    /// Returns the pairs that were overlapping, which are also logged as a warning.
+    pub fn resolve_initial_overlaps(&mut self, policy: OverlapPolicy) -> Vec<(usize, usize)> {
+        let offending = self.overlapping_pairs();
+        if offending.is_empty() {
+            return offending;
+        }
+        log::warn!("Bodies overlap in the initial conditions: {:?}", offending);
+
+        match policy {
+            OverlapPolicy::Nudge => {
+                // Pushing one pair apart can push a body into another, so repeat a few times
+                for _ in 0..MAX_OVERLAP_PASSES {
+                    let pairs = self.overlapping_pairs();
+                    if pairs.is_empty() {
+                        break;
+                    }
+                    for (i, j) in pairs {
+                        self.nudge_apart(i, j);
+                    }
+                }
+            }
+            OverlapPolicy::Merge => {
+                // Indices shift after a merge, so look for overlaps again each time
+                while let Some(&(i, j)) = self.overlapping_pairs().first() {
+                    let merged = self.bodies[i].merged(&self.bodies[j]);
+                    self.bodies.remove(j);
+                    self.bodies[i] = merged;
+                    // Every body after the removed one moved down a place
+                    for body in &mut self.bodies {
+                        body.hill_primary = match body.hill_primary {
+                            Some(primary) if primary == j => Some(i),
+                            Some(primary) if primary > j => Some(primary - 1),
+                            primary => primary,
+                        };
+                    }
+                    // A body can't be measured against itself
+                    if self.bodies[i].hill_primary == Some(i) {
+                        self.bodies[i].hill_primary = None;
+                    }
+                }
+            }
+        }
+        offending
+    }
+
+    /// Moves bodies `i` and `j` apart along the line between them until they just touch.
+    /// The lighter body moves further, so their center of mass stays put.
+    fn nudge_apart(&mut self, i: usize, j: usize) {
+        let (a, b) = (&self.bodies[i], &self.bodies[j]);
+        let offset = b.position - a.position;
+        let distance = offset.magnitude();
+        // Bodies at the exact same spot have no direction between them, so pick one
+        let direction = if distance > 0.0 {
+            offset / distance
+        } else {
+            Vector3::unit_x()
+        };
+        // A tiny margin so rounding doesn't leave them overlapping
+        let overlap = (a.radius + b.radius) * 1.0001 - distance;
+        let total_mass = a.mass + b.mass;
+        let (a_share, b_share) = (b.mass / total_mass, a.mass / total_mass);
+
+        self.bodies[i].position -= direction * overlap * a_share;
+        self.bodies[j].position += direction * overlap * b_share;
+    }
+
+    /// A star orbited by two planets whose periods are in the ratio `outer:inner`,
+    /// e.g. (2, 1) for the 2:1 mean-motion resonance of Jupiter's moons.
+    /// Both planets start lined up so the repeated conjunctions are easy to follow.
+    pub fn resonance_demo(ratio: (u32, u32)) -> Self {
+        let (outer, inner) = ratio;
+        let units = UnitSystem::Natural;
+        let g = units.gravitational_constant();
+        let star = BodyType::Star;
+        let central_mass = star.mass();
+
+        let inner_radius = DEMO_ORBIT_RADIUS;
+        let inner_period = physics::orbital_period(central_mass, inner_radius, g);
+        let outer_period = inner_period * outer as f32 / inner as f32;
+        let outer_radius = physics::semi_major_axis(central_mass, outer_period, g);
+
+        // Orbiting in the xz plane, counterclockwise when seen from above
+        let planet = |radius: f32| {
+            let position = Vector3::new(radius, 0.0, 0.0);
+            let velocity = circular_orbit(central_mass, position, g);
+            BodyConfig::new(BodyType::Planet, position, velocity)
+        };
+        let planets = [planet(inner_radius), planet(outer_radius)];
+
+        // Give the star the opposite momentum of the planets so the system doesn't drift
+        let momentum = planets
+            .iter()
+            .fold(Vector3::zero(), |sum, body| sum + body.velocity * body.mass);
+        let sun = BodyConfig::new(star, Vector3::zero(), -momentum / central_mass);
+
+        let mut bodies = vec![sun];
+        bodies.extend(planets);
+        Self {
+            bodies,
+            units,
+            softening: None,
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        }
+    }
+
+    /// `n` planets scattered at random through a cloud shaped by `params`.
+    /// The same seed always gives the same scene: StdRng is seeded directly, and the bodies
+    /// are placed with only arithmetic and square roots, which round the same everywhere
+    pub fn random_cloud(n: usize, seed: u64, params: CloudParams) -> Self {
+        let units = UnitSystem::Natural;
+        let g = units.gravitational_constant();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // Rejection sampling instead of random angles, since sin and cos aren't guaranteed
+        // to round the same on every platform
+        let unit_point = |rng: &mut StdRng| loop {
+            let mut coordinate = || rng.gen::<f32>() * 2.0 - 1.0;
+            let point = match params.shape {
+                CloudShape::Sphere => Vector3::new(coordinate(), coordinate(), coordinate()),
+                CloudShape::Disk => {
+                    let (x, z) = (coordinate(), coordinate());
+                    Vector3::new(x, coordinate() * DISK_THICKNESS, z)
+                }
+            };
+            let spread = match params.shape {
+                CloudShape::Sphere => point.magnitude2(),
+                CloudShape::Disk => point.x * point.x + point.z * point.z,
+            };
+            if spread <= 1.0 {
+                break point;
+            }
+        };
+
+        let mut bodies = (0..n)
+            .map(|_| {
+                let position = unit_point(&mut rng) * params.radius;
+                let mut body = BodyConfig::new(BodyType::Planet, position, Vector3::zero());
+                body.mass = rng.gen_range(params.min_mass..=params.max_mass);
+                body.radius = params.body_radius;
+                body
+            })
+            .collect::<Vec<_>>();
+
+        // Spinning each body about the y axis at a fraction of the speed it would need to
+        // orbit the mass closer in than it, counterclockwise when seen from above
+        let total_mass = bodies.iter().map(|body| body.mass).sum::<f32>();
+        for body in &mut bodies {
+            let p = body.position;
+            let axis_distance = (p.x * p.x + p.z * p.z).sqrt();
+            if axis_distance <= 0.0 {
+                continue;
+            }
+            let (distance, enclosed) = match params.shape {
+                CloudShape::Sphere => {
+                    let fraction = p.magnitude() / params.radius;
+                    (p.magnitude(), fraction * fraction * fraction)
+                }
+                CloudShape::Disk => {
+                    let fraction = axis_distance / params.radius;
+                    (axis_distance, fraction * fraction)
+                }
+            };
+            let speed =
+                params.spin * physics::circular_orbit_speed(total_mass * enclosed, distance, g);
+            body.velocity = Vector3::new(p.z, 0.0, -p.x) / axis_distance * speed;
+        }
+
+        // Taking out the overall drift so the cloud stays in view
+        if total_mass > 0.0 {
+            let drift = bodies
+                .iter()
+                .fold(Vector3::zero(), |sum, body| sum + body.velocity * body.mass)
+                / total_mass;
+            for body in &mut bodies {
+                body.velocity -= drift;
+            }
+        }
+
+        let mut scene = Self {
+            bodies,
+            units,
+            softening: None,
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        };
+        scene.resolve_initial_overlaps(params.overlaps);
+        if let Some(target) = params.angular_momentum {
+            scene.set_angular_momentum(target);
+        }
+        scene
+    }
+
+    /// Spins the bodies up or down so their total angular momentum about their center of
+    /// mass is `target`, keeping their random motion, see physics::set_angular_momentum
+    pub fn set_angular_momentum(&mut self, target: Vector3<f32>) {
+        let mut entities = self
+            .bodies
+            .iter()
+            .map(Entity::from_config)
+            .collect::<Vec<_>>();
+        physics::set_angular_momentum(&mut entities, target.map(f64::from));
+        for (body, entity) in self.bodies.iter_mut().zip(&entities) {
+            body.velocity = entity.velocity.map(|v| v as f32);
+        }
+    }
+
+    /// The built-in scene `preset`
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Binary => Self::binary_preset(),
+            Preset::FigureEight => Self::figure_eight(),
+            Preset::Pythagorean => Self::pythagorean(),
+            Preset::SolarSystem => Self::solar_system(),
+        }
+    }
+
+    /// Two stars of different masses circling their shared center of mass
+    fn binary_preset() -> Self {
+        let units = UnitSystem::Natural;
+        let g = units.gravitational_constant();
+        let star = BodyType::Star;
+        let (heavy, light) = (star.mass(), star.mass() * 0.5);
+        let [(heavy_position, heavy_velocity), (light_position, light_velocity)] =
+            binary_system(heavy, light, DEMO_ORBIT_RADIUS * 2.0, g);
+
+        let mut primary = BodyConfig::new(star, heavy_position, heavy_velocity);
+        primary.mass = heavy;
+        let mut secondary = BodyConfig::new(star, light_position, light_velocity);
+        secondary.mass = light;
+        secondary.radius = star.radius() * 0.8;
+        secondary.color = [1.0, 0.75, 0.5];
+        Self {
+            bodies: vec![primary, secondary],
+            units,
+            softening: None,
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        }
+    }
+
+    /// Three equal masses chasing each other around a figure eight, the periodic orbit found
+    /// by Chenciner and Montgomery. Starts from their initial conditions for G = m = 1,
+    /// scaled up by DEMO_ORBIT_RADIUS
+    fn figure_eight() -> Self {
+        let units = UnitSystem::Natural;
+        let scale = DEMO_ORBIT_RADIUS;
+        // Lengths grow by `scale`, so speeds shrink by its square root to keep the same shape
+        let speed_scale = 1.0 / scale.sqrt();
+        // The published solution is in the xy plane, which goes into our xz plane
+        // keeping the same sense of rotation when seen from above
+        let planar = |x: f32, y: f32| Vector3::new(x, 0.0, -y);
+        let outer = planar(0.970_004_4, -0.243_087_53) * scale;
+        let middle_velocity = planar(-0.932_407_4, -0.864_731_5) * speed_scale;
+
+        let body = |position, velocity, color| {
+            let mut body = BodyConfig::new(BodyType::Planet, position, velocity);
+            // The bodies pass close to each other, so small enough that they never touch
+            body.radius = 0.2;
+            body.color = color;
+            body
+        };
+        Self {
+            bodies: vec![
+                body(outer, -middle_velocity * 0.5, [0.9, 0.3, 0.3]),
+                body(-outer, -middle_velocity * 0.5, [0.3, 0.9, 0.3]),
+                body(Vector3::zero(), middle_velocity, [0.3, 0.4, 0.9]),
+            ],
+            units,
+            // Any softening would weaken the close passes and break the orbit apart
+            softening: Some(0.0),
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        }
+    }
+
+    /// Burrau's problem: masses of 3, 4 and 5 at rest on the corners of a 3-4-5 triangle,
+    /// each opposite the side of its own length, scaled up by DEMO_ORBIT_RADIUS.
+    /// The exact problem ends with the lightest body thrown out after a series of very close
+    /// encounters, which need far smaller steps than the default to follow. The bodies are
+    /// softened and kept tiny so the encounters neither blow up nor merge them, at the cost
+    /// of playing out differently
+    fn pythagorean() -> Self {
+        let units = UnitSystem::Natural;
+        let scale = DEMO_ORBIT_RADIUS;
+        let body = |mass: f32, x: f32, y: f32, color| {
+            let position = Vector3::new(x, 0.0, -y) * scale;
+            let mut body = BodyConfig::new(BodyType::Planet, position, Vector3::zero());
+            body.mass = mass;
+            body.radius = 0.01 * mass.cbrt();
+            body.color = color;
+            body
+        };
+        Self {
+            bodies: vec![
+                body(3.0, 1.0, 3.0, [0.9, 0.3, 0.3]),
+                body(4.0, -2.0, -1.0, [0.3, 0.9, 0.3]),
+                body(5.0, 1.0, -1.0, [0.3, 0.4, 0.9]),
+            ],
+            units,
+            softening: Some(0.5),
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        }
+    }
+
+    /// The Sun and the eight planets on circular orbits at their real distances and masses,
+    /// in astronomical units, solar masses and years. Radii are exaggerated so the planets
+    /// are visible, and the planets are spread around their orbits rather than lined up
+    fn solar_system() -> Self {
+        let units = UnitSystem::Astronomical;
+        let g = units.gravitational_constant();
+        let sun_mass = 1.0;
+
+        // (semi-major axis, mass, radius, color)
+        let planets: [(f32, f32, f32, [f32; 3]); 8] = [
+            (0.387, 1.66e-7, 0.02, [0.6, 0.58, 0.55]),
+            (0.723, 2.45e-6, 0.04, [0.9, 0.8, 0.55]),
+            (1.0, 3.0e-6, 0.04, [0.3, 0.5, 0.9]),
+            (1.524, 3.23e-7, 0.03, [0.8, 0.4, 0.25]),
+            (5.203, 9.55e-4, 0.2, [0.85, 0.75, 0.6]),
+            (9.537, 2.86e-4, 0.17, [0.9, 0.85, 0.6]),
+            (19.19, 4.37e-5, 0.1, [0.6, 0.85, 0.9]),
+            (30.07, 5.15e-5, 0.1, [0.3, 0.45, 0.9]),
+        ];
+        // Spaced by the golden angle, so no two planets start close together
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+        let mut bodies = planets
+            .iter()
+            .enumerate()
+            .map(|(i, &(distance, mass, radius, color))| {
+                let (sin, cos) = (golden_angle * i as f32).sin_cos();
+                let position = Vector3::new(cos, 0.0, -sin) * distance;
+                let velocity = circular_orbit(sun_mass, position, g);
+                let mut planet = BodyConfig::new(BodyType::Planet, position, velocity);
+                planet.mass = mass;
+                planet.radius = radius;
+                planet.color = color;
+                planet
+            })
+            .collect::<Vec<_>>();
+
+        // Give the Sun the opposite momentum of the planets so the system doesn't drift
+        let momentum = bodies
+            .iter()
+            .fold(Vector3::zero(), |sum, body| sum + body.velocity * body.mass);
+        let mut sun = BodyConfig::new(BodyType::Star, Vector3::zero(), -momentum / sun_mass);
+        sun.mass = sun_mass;
+        sun.radius = 0.1;
+        sun.color = [1.0, 0.93, 0.89];
+        bodies.insert(0, sun);
+
+        Self {
+            bodies,
+            units,
+            // Mercury orbits at 0.39 AU, so the default softening would be far too much
+            softening: Some(0.0001),
+            background: None,
+            light: None,
+            sphere_resolution: None,
+        }
+    }
+}
+
+/// The built-in scenes, see Scene::preset
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Preset {
+    /// Two stars circling their shared center of mass
+    Binary,
+    /// Three equal masses on the figure-eight orbit
+    FigureEight,
+    /// Burrau's three bodies starting at rest on a 3-4-5 triangle
+    Pythagorean,
+    /// The Sun and the eight planets
+    SolarSystem,
+}
+
+impl Preset {
+    /// Every preset, in the order they're listed
+    pub const ALL: [Preset; 4] = [
+        Preset::Binary,
+        Preset::FigureEight,
+        Preset::Pythagorean,
+        Preset::SolarSystem,
+    ];
+
+    /// The name the preset goes by on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Binary => "binary",
+            Preset::FigureEight => "figure-eight",
+            Preset::Pythagorean => "pythagorean",
+            Preset::SolarSystem => "solar-system",
+        }
+    }
+
+    /// The preset called `name`, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == name)
+    }
+}
+
+/// The velocity a light body at `offset` from `central_mass` needs to orbit it in a circle,
+/// with magnitude sqrt(G * M / r). It goes counterclockwise about the y axis when seen from
+/// above, like the demo scenes, so an offset straight along the y axis orbits about the
+/// x axis instead
+pub fn circular_orbit(central_mass: f32, offset: Vector3<f32>, g: f32) -> Vector3<f32> {
+    let radius = offset.magnitude();
+    if radius <= 0.0 {
+        return Vector3::zero();
+    }
+    let mut direction = Vector3::unit_y().cross(offset);
+    if direction.magnitude2() <= f32::EPSILON * radius * radius {
+        direction = Vector3::unit_x().cross(offset);
+    }
+    direction.normalize() * physics::circular_orbit_speed(central_mass, radius, g)
+}
+
+/// Two bodies of masses `m1` and `m2` on circular orbits `separation` apart, around their
+/// center of mass at the origin, as (position, velocity) for each. They lie along the
+/// x axis with the first on the negative side, orbiting counterclockwise when seen from
+/// above, and their momenta cancel so the pair doesn't drift
+pub fn binary_system(
+    m1: f32,
+    m2: f32,
+    separation: f32,
+    g: f32,
+) -> [(Vector3<f32>, Vector3<f32>); 2] {
+    let total = m1 + m2;
+    // The second body's velocity relative to the first, as if the first were fixed
+    let relative = circular_orbit(total, Vector3::unit_x() * separation, g);
+    // Each body is as far from the center of mass, and as fast, as the other's share of the mass
+    [
+        (
+            -Vector3::unit_x() * separation * m2 / total,
+            -relative * m2 / total,
+        ),
+        (
+            Vector3::unit_x() * separation * m1 / total,
+            relative * m1 / total,
+        ),
+    ]
+}
@@ -12,41 +12,131 @@ var<uniform> camera: CameraUniform;
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] color: vec3<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] uv: vec2<f32>;
+};
+
+struct InstanceInput {
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+    [[location(9)]] color: vec3<f32>;
+    [[location(10)]] emissive: f32;
+    [[location(11)]] normal_matrix_0: vec3<f32>;
+    [[location(12)]] normal_matrix_1: vec3<f32>;
+    [[location(13)]] normal_matrix_2: vec3<f32>;
 };
 
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] color: vec3<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] world_position: vec3<f32>;
+    [[location(3)]] emissive: f32;
+    [[location(4)]] uv: vec2<f32>;
 };
 
 [[stage(vertex)]]
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
     var out: VertexOutput;
-    out.color = model.color;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    // The mesh is white, so the instance color tints it to each body's own color
+    out.color = model.color * instance.color;
+    // Rotation turns the normals with the surface, and the normal matrix undoes the
+    // uneven scale of motion blur so they stay at right angles to it
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+    out.world_normal = normal_matrix * model.normal;
+    out.world_position = world_position.xyz;
+    out.emissive = instance.emissive;
+    out.uv = model.uv;
+    out.clip_position = camera.view_proj * world_position;
     return out;
 }
 
 // Fragment shader
 
-[[block]]
 struct Light {
     position: vec3<f32>;
+    // Brightness at distance d is scaled by 1 / (constant + linear * d + quadratic * d * d)
+    constant: f32;
     color: vec3<f32>;
+    linear: f32;
+    quadratic: f32;
+};
+
+[[block]]
+struct LightCount {
+    num_lights: u32;
+};
+[[group(1), binding(0)]]
+var<uniform> light_count: LightCount;
+
+// Only the first light_count.num_lights are in use. Must hold MAX_LIGHTS from render.rs
+[[block]]
+struct Lights {
+    data: array<Light, 8>;
 };
+[[group(1), binding(1)]]
+var<storage, read> lights: Lights;
+
+// The body's surface map, plain white for bodies without one
 [[group(2), binding(0)]]
-var<uniform> light: Light;
+var t_surface: texture_2d<f32>;
+[[group(2), binding(1)]]
+var s_surface: sampler;
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let object_color = in.color * textureSample(t_surface, s_surface, in.uv).rgb;
+
+    // Glowing bodies light themselves
+    if (in.emissive > 0.5) {
+        return vec4<f32>(object_color, 1.0);
+    }
+
+    let normal = normalize(in.world_normal);
+    var lighting = vec3<f32>(0.0, 0.0, 0.0);
+    var i: u32 = 0u;
+    loop {
+        if (i >= light_count.num_lights) {
+            break;
+        }
+        let light = lights.data[i];
 
-    //let object_color = in.color;
-    //let ambient_strength = 0.1;
-    //let ambient_color = light.color * ambient_strength;
+        // A little light everywhere so the far side of a body isn't pure black
+        let ambient_strength = 0.1;
+        let ambient_color = light.color * ambient_strength;
 
-    //let result = ambient_color * object_color.xyz;
+        // Lambertian shading, brightest where the surface faces the light
+        let to_light = light.position - in.world_position;
+        let light_dir = normalize(to_light);
+        let diffuse_strength = max(dot(normal, light_dir), 0.0);
+        let distance = length(to_light);
+        let attenuation = 1.0 / (light.constant + light.linear * distance + light.quadratic * distance * distance);
+        let diffuse_color = light.color * (diffuse_strength * attenuation);
 
-    return vec4<f32>(in.color, 1.0);
-}
\ No newline at end of file
+        lighting = lighting + ambient_color + diffuse_color;
+
+        continuing {
+            i = i + 1u;
+        }
+    }
+
+    let result = min(lighting, vec3<f32>(1.0)) * object_color;
+
+    return vec4<f32>(result, 1.0);
+}
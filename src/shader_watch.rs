@@ -0,0 +1,94 @@
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// Where shader.wgsl lives in the source tree, which is where it's reloaded from
+pub const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+/// Watches a shader file for changes so it can be reloaded without recompiling
+pub struct ShaderWatcher {
+    /// Stops watching when dropped, so it's kept alive alongside the events it sends
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    path: PathBuf,
+}
+
+impl ShaderWatcher {
+    /// Starts watching the shader at `path`. Fails if the platform's file watching can't be
+    /// set up or the file's directory can't be watched
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = RecommendedWatcher::new(sender, notify::Config::default())
+            .context("Failed to set up file watching")?;
+        // Watching the directory rather than the file, since editors often save by writing a
+        // new file and renaming it over the old one, which a watch on the file itself misses
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", directory.display()))?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The shader's new source if it's changed since the last poll, checked with naga so
+    /// a mistake is reported here instead of taking down the renderer
+    pub fn poll(&mut self) -> Option<anyhow::Result<String>> {
+        let mut changed = false;
+        // A single save can come through as several events, which all end up as one reload
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) => {
+                    changed |= !event.kind.is_access()
+                        && event
+                            .paths
+                            .iter()
+                            .any(|path| path.ends_with(self.shader_name()))
+                }
+                Err(e) => eprintln!("Error watching {}: {}", self.path.display(), e),
+            }
+        }
+        if !changed {
+            return None;
+        }
+        Some(
+            std::fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read {}", self.path.display()))
+                .and_then(|source| {
+                    validate(&source)?;
+                    Ok(source)
+                }),
+        )
+    }
+
+    fn shader_name(&self) -> &Path {
+        self.path.file_name().map_or(self.path.as_path(), Path::new)
+    }
+}
+
+/// Parses and validates a render shader the way wgpu would, and checks it has the vs_main
+/// and fs_main entry points the pipelines use. Doesn't check its bindings against the
+/// pipeline layout
+pub fn validate(source: &str) -> anyhow::Result<()> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| anyhow::anyhow!("{}", e.emit_to_string(source)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .context("The shader is invalid")?;
+    for entry_point in ["vs_main", "fs_main"] {
+        if !module
+            .entry_points
+            .iter()
+            .any(|entry| entry.name == entry_point)
+        {
+            anyhow::bail!("The shader has no {} entry point", entry_point);
+        }
+    }
+    Ok(())
+}
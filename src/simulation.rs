@@ -0,0 +1,292 @@
+use crate::integrator::{self, AdaptiveTimestep, Integrator};
+use crate::physics;
+use crate::scene::Scene;
+use crate::snapshot::Snapshot;
+use crate::spatial_hash;
+use crate::sphere::Entity;
+use crate::trajectory::TrajectoryRecorder;
+use cgmath::*;
+
+/// The bodies being simulated and everything needed to move them forward in time.
+/// This holds no window or GPU state, so it can run on its own as well as behind the renderer
+pub struct Simulation {
+    /// The bodies being simulated
+    pub entities: Vec<Entity>,
+    /// The strength of gravity in the scene's units
+    pub gravitational_constant: f32,
+    /// Keeps the pull between bodies finite as they pass through each other
+    pub softening: f32,
+    /// When on, pairs closer than min_separation pull as if they were that far apart,
+    /// see physics::compute_gravity_clamped
+    pub clamp_separation: bool,
+    /// The closest two bodies are treated as being while clamp_separation is on
+    pub min_separation: f32,
+    /// How many pairs were clamped to min_separation by the last step's gravity.
+    /// Only counted when gravity is computed on the CPU
+    pub clamped_pairs: usize,
+    /// How the pull between bodies is computed
+    pub gravity_strategy: physics::GravityStrategy,
+    /// How the bodies are moved forward in time
+    pub integrator: Integrator,
+    /// The accelerations on each body from the last physics step, reused by the next one
+    pub accelerations: Vec<Vector3<f64>>,
+    /// How much simulated time each physics step covers
+    pub dt: f64,
+    /// When set, each step's length is picked from the bodies' accelerations instead of `dt`
+    pub adaptive_timestep: Option<AdaptiveTimestep>,
+    /// How much time has been simulated so far
+    pub time: f64,
+    /// When on, bodies that touch are merged into one, see physics::handle_collisions.
+    /// When off they pass through each other
+    pub merge_on_collision: bool,
+    /// The size of the cells collisions are looked for in, see spatial_hash.
+    /// When unset it follows the largest body, which suits most scenes
+    pub collision_cell_size: Option<f32>,
+    /// When set, records the paths of the bodies it follows after every step
+    pub trajectory: Option<TrajectoryRecorder>,
+}
+
+impl Simulation {
+    /// Sets up the bodies of `scene`, ready to be stepped by `dt`
+    pub fn new(scene: &Scene, dt: f64) -> Self {
+        let mut simulation = Self {
+            entities: scene.bodies.iter().map(Entity::from_config).collect(),
+            gravitational_constant: scene.units.gravitational_constant(),
+            softening: scene.softening.unwrap_or(physics::DEFAULT_SOFTENING),
+            clamp_separation: false,
+            min_separation: physics::DEFAULT_MIN_SEPARATION,
+            clamped_pairs: 0,
+            gravity_strategy: physics::GravityStrategy::BruteForce,
+            integrator: Integrator::VelocityVerlet,
+            accelerations: Vec::new(),
+            dt,
+            adaptive_timestep: None,
+            time: 0.0,
+            merge_on_collision: true,
+            collision_cell_size: None,
+            trajectory: None,
+        };
+        simulation.refresh_accelerations();
+        simulation
+    }
+
+    /// The net gravitational acceleration on each of `bodies`, computed on the CPU
+    /// with this simulation's settings
+    pub fn gravity(&self) -> impl Fn(&[Entity]) -> Vec<Vector3<f64>> {
+        let (g, softening, strategy, min_separation) = self.gravity_settings();
+        move |bodies| {
+            physics::compute_gravity_clamped(bodies, g, softening, strategy, min_separation)
+                .accelerations
+        }
+    }
+
+    fn gravity_settings(&self) -> (f32, f32, physics::GravityStrategy, Option<f32>) {
+        (
+            self.gravitational_constant,
+            self.softening,
+            self.gravity_strategy,
+            self.min_separation(),
+        )
+    }
+
+    /// The minimum separation pairs are clamped to, if the clamp is on
+    pub fn min_separation(&self) -> Option<f32> {
+        if self.clamp_separation {
+            Some(self.min_separation)
+        } else {
+            None
+        }
+    }
+
+    /// Recomputes the accelerations at the bodies' current positions, needed whenever the
+    /// bodies are changed by anything other than a step
+    pub fn refresh_accelerations(&mut self) {
+        let gravity = self.gravity();
+        self.refresh_accelerations_with(gravity);
+    }
+
+    /// Same as refresh_accelerations, but with the accelerations worked out by `gravity`
+    pub fn refresh_accelerations_with<F>(&mut self, mut gravity: F)
+    where
+        F: FnMut(&[Entity]) -> Vec<Vector3<f64>>,
+    {
+        self.accelerations = gravity(&self.entities);
+    }
+
+    /// How long the next physics step should be
+    pub fn next_dt(&self) -> f64 {
+        match self.adaptive_timestep {
+            Some(adaptive) => adaptive.next_dt(&self.accelerations, self.softening),
+            None => self.dt,
+        }
+    }
+
+    /// Moves the bodies forward by `dt`, computing gravity on the CPU.
+    /// Returns the index removed by each collision, see step_with
+    pub fn step(&mut self, dt: f64) -> Vec<usize> {
+        let (g, softening, strategy, min_separation) = self.gravity_settings();
+        let mut clamped_pairs = 0;
+        let merged = self.step_with(dt, |bodies| {
+            let gravity =
+                physics::compute_gravity_clamped(bodies, g, softening, strategy, min_separation);
+            // Only the last pass counts, which is where the bodies ended up
+            clamped_pairs = gravity.clamped_pairs;
+            gravity.accelerations
+        });
+        self.clamped_pairs = clamped_pairs;
+        merged
+    }
+
+    /// Moves the bodies forward by `dt` with the accelerations worked out by `gravity`,
+    /// then merges any that collided. Returns the index removed by each merge, in the order
+    /// they happened, so anything kept alongside the bodies can be removed to match
+    pub fn step_with<F>(&mut self, dt: f64, mut gravity: F) -> Vec<usize>
+    where
+        F: FnMut(&[Entity]) -> Vec<Vector3<f64>>,
+    {
+        self.accelerations = integrator::step(
+            &mut self.entities,
+            &self.accelerations,
+            dt,
+            self.integrator,
+            &mut gravity,
+        );
+        self.time += dt;
+
+        let merged = self.merge_collisions();
+        if !merged.is_empty() {
+            // The merged bodies have new masses, so the pull on everything changed
+            self.refresh_accelerations_with(gravity);
+        }
+        self.record_trajectory();
+        merged
+    }
+
+    /// Adds where the bodies are now to the trajectory being recorded, if there is one.
+    /// Called after every step, and by anything else that moves the bodies forward
+    pub fn record_trajectory(&mut self) {
+        if let Some(recorder) = self.trajectory.as_mut() {
+            recorder.record(self.time, &self.entities);
+        }
+    }
+
+    /// Merges overlapping bodies, at most one merge per body, see physics::handle_collisions.
+    /// Returns the indices removed in the order they were removed. The accelerations are
+    /// left as they were.
+    /// Does nothing while merge_on_collision is off
+    pub fn merge_collisions(&mut self) -> Vec<usize> {
+        if !self.merge_on_collision {
+            return Vec::new();
+        }
+        let cell_size = self
+            .collision_cell_size
+            .unwrap_or_else(|| spatial_hash::default_cell_size(&self.entities));
+        physics::handle_collisions(&mut self.entities, cell_size)
+    }
+
+    /// The kinetic plus gravitational potential energy of the bodies.
+    /// A good integrator keeps this close to where it started
+    pub fn total_energy(&self) -> f64 {
+        physics::total_energy(&self.entities, self.gravitational_constant, self.softening)
+    }
+
+    /// The total linear momentum of the bodies
+    pub fn total_momentum(&self) -> Vector3<f64> {
+        physics::momentum(&self.entities)
+    }
+
+    /// A copy of this simulation as it is now, without the trajectory being recorded, to be
+    /// stepped alongside it and compared
+    pub fn comparison(&self) -> Self {
+        Self {
+            entities: self.entities.clone(),
+            gravitational_constant: self.gravitational_constant,
+            softening: self.softening,
+            clamp_separation: self.clamp_separation,
+            min_separation: self.min_separation,
+            clamped_pairs: self.clamped_pairs,
+            gravity_strategy: self.gravity_strategy,
+            integrator: self.integrator,
+            accelerations: self.accelerations.clone(),
+            dt: self.dt,
+            adaptive_timestep: self.adaptive_timestep,
+            time: self.time,
+            merge_on_collision: self.merge_on_collision,
+            collision_cell_size: self.collision_cell_size,
+            trajectory: None,
+        }
+    }
+
+    /// The furthest any body is from the body at the same index in `other`, which is how far
+    /// two runs of the same bodies have drifted apart. Bodies only one of them has are left out
+    pub fn divergence(&self, other: &Simulation) -> f64 {
+        self.entities
+            .iter()
+            .zip(&other.entities)
+            .map(|(a, b)| (a.position - b.position).magnitude())
+            .fold(0.0, f64::max)
+    }
+
+    /// The bodies, the simulated time and the physics settings, so the run can be resumed
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            time: self.time,
+            bodies: self.entities.clone(),
+            gravitational_constant: self.gravitational_constant,
+            softening: self.softening,
+            gravity_strategy: self.gravity_strategy,
+            integrator: self.integrator,
+            dt: self.dt,
+        }
+    }
+
+    /// Picks up the run saved in `snapshot`. The accelerations are recomputed on the CPU
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.time = snapshot.time;
+        self.entities = snapshot.bodies;
+        self.gravitational_constant = snapshot.gravitational_constant;
+        self.softening = snapshot.softening;
+        self.gravity_strategy = snapshot.gravity_strategy;
+        self.integrator = snapshot.integrator;
+        self.dt = snapshot.dt;
+        self.refresh_accelerations();
+    }
+}
+
+/// Simulates `scene` for `steps` steps of `dt` without a window or GPU.
+/// Takes a snapshot of the starting state, then one every `interval` steps,
+/// and one of the final state if it didn't land on the interval
+pub fn run_headless(scene: &Scene, steps: usize, dt: f64, interval: usize) -> Vec<Snapshot> {
+    let mut simulation = Simulation::new(scene, dt);
+    headless_snapshots(&mut simulation, steps, interval).collect()
+}
+
+/// The same snapshots as run_headless, but of a simulation that's already set up, and only
+/// simulated as far as they're asked for, so long runs can be written out as they go
+/// instead of being held in memory. The simulation is left where the last snapshot was taken
+pub fn headless_snapshots(
+    simulation: &mut Simulation,
+    steps: usize,
+    interval: usize,
+) -> impl Iterator<Item = Snapshot> + '_ {
+    let interval = interval.max(1);
+    let dt = simulation.dt;
+    let mut step = 0;
+    // The first snapshot is of the starting state, before any steps
+    let mut started = false;
+    std::iter::from_fn(move || {
+        if started {
+            if step == steps {
+                return None;
+            }
+            // Simulating up to the next interval, or the end of the run
+            let target = ((step / interval + 1) * interval).min(steps);
+            while step < target {
+                simulation.step(dt);
+                step += 1;
+            }
+        }
+        started = true;
+        Some(simulation.snapshot())
+    })
+}
@@ -0,0 +1,59 @@
+// Vertex shader
+
+[[block]]
+struct CameraUniform {
+    view_proj: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    eye: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+// A single triangle big enough to cover the whole screen, so no vertex buffer is needed
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index & 2u) * 2 - 1);
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.ndc = vec2<f32>(x, y);
+    return out;
+}
+
+// Fragment shader
+
+fn hash(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3<f32>(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+// How many cells the sky is split into along each axis. Each cell holds at most one star
+let STAR_DENSITY: f32 = 300.0;
+// The fraction of cells without a star
+let STAR_THRESHOLD: f32 = 0.92;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Any depth inside the frustum works, the point only gives the direction to look in
+    let world = camera.inv_view_proj * vec4<f32>(in.ndc, 0.5, 1.0);
+    let direction = normalize(world.xyz / world.w - camera.eye.xyz);
+
+    // Every cell the sky passes through gets a star at a random spot in it,
+    // and only the brightest few are kept
+    let p = direction * STAR_DENSITY;
+    let cell = floor(p);
+    let chance = hash(cell);
+    let offset = vec3<f32>(hash(cell + vec3<f32>(1.0)), hash(cell + vec3<f32>(2.0)), hash(cell + vec3<f32>(3.0))) - vec3<f32>(0.5);
+    let distance = length(p - (cell + vec3<f32>(0.5) + offset * 0.6));
+    let falloff = clamp(1.0 - distance / 0.15, 0.0, 1.0);
+    let glow = falloff * falloff;
+    let brightness = glow * step(STAR_THRESHOLD, chance) * (chance - STAR_THRESHOLD) / (1.0 - STAR_THRESHOLD);
+
+    // A little color variation between bluish and yellowish stars
+    let tint = mix(vec3<f32>(0.8, 0.85, 1.0), vec3<f32>(1.0, 0.9, 0.75), hash(cell + vec3<f32>(4.0)));
+    return vec4<f32>(tint * brightness, 1.0);
+}
@@ -0,0 +1,43 @@
+use crate::integrator::Integrator;
+use crate::physics::GravityStrategy;
+use crate::sphere::Entity;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Everything needed to pick a simulation back up exactly where it left off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// How much simulated time had passed
+    pub time: f64,
+    /// Every body in the simulation
+    pub bodies: Vec<Entity>,
+    /// The strength of gravity in the simulation's units
+    pub gravitational_constant: f32,
+    /// Keeps the pull between bodies finite as they pass through each other
+    pub softening: f32,
+    /// How the pull between bodies was computed
+    pub gravity_strategy: GravityStrategy,
+    /// How the bodies were moved forward in time
+    pub integrator: Integrator,
+    /// How much simulated time each physics step covered
+    pub dt: f64,
+}
+
+impl Snapshot {
+    /// Writes the snapshot to a RON file.
+    /// Floats are written in their shortest exact form, so loading it back gives identical values
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = ron::to_string(self).context("Failed to serialize snapshot")?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write snapshot {}", path.display()))
+    }
+
+    /// Reads a snapshot written by save
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+        ron::from_str(&contents)
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::sphere::Entity;
+use cgmath::*;
+use std::collections::HashMap;
+
+/// A uniform grid over space that buckets bodies by the cells they touch, so bodies can
+/// only be near each other if they share a cell. Finding overlapping bodies this way is
+/// close to O(n) instead of checking every pair
+pub struct SpatialHash {
+    /// The length of each cell's sides
+    cell_size: f32,
+    /// The bodies touching each occupied cell, keyed by the cell's coordinates
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// Buckets every body into each cell its bounding box touches.
+    /// Cells about the size of the largest body work best, see default_cell_size:
+    /// much smaller and big bodies land in lots of cells, much bigger and cells get crowded
+    pub fn new(entities: &[Entity], cell_size: f32) -> Self {
+        let mut hash = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+        for (index, body) in entities.iter().enumerate() {
+            let reach = Vector3::from_value(f64::from(body.radius));
+            let min = hash.cell(body.position - reach);
+            let max = hash.cell(body.position + reach);
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    for z in min.2..=max.2 {
+                        hash.cells.entry((x, y, z)).or_default().push(index);
+                    }
+                }
+            }
+        }
+        hash
+    }
+
+    /// The coordinates of the cell `position` falls in
+    fn cell(&self, position: Vector3<f64>) -> (i32, i32, i32) {
+        let coordinate = |value: f64| (value / f64::from(self.cell_size)).floor() as i32;
+        (
+            coordinate(position.x),
+            coordinate(position.y),
+            coordinate(position.z),
+        )
+    }
+
+    /// Every pair of bodies that share a cell, lower index first, sorted and without repeats.
+    /// Overlapping bodies always share a cell, but bodies sharing one may not overlap
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for bodies in self.cells.values() {
+            for (n, &i) in bodies.iter().enumerate() {
+                for &j in &bodies[n + 1..] {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+        // Bodies sharing several cells turn up once for each
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    /// Every body whose center is within `distance` of `position`, sorted and without
+    /// repeats. `entities` must be the bodies the hash was built from. Only the cells within
+    /// reach are searched, so it's quickest when `distance` is about the cell size
+    pub fn within(&self, entities: &[Entity], position: Vector3<f64>, distance: f32) -> Vec<usize> {
+        let reach = Vector3::from_value(f64::from(distance));
+        let min = self.cell(position - reach);
+        let max = self.cell(position + reach);
+        let limit = f64::from(distance) * f64::from(distance);
+        let mut found = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(bodies) = self.cells.get(&(x, y, z)) {
+                        found.extend(
+                            bodies.iter().copied().filter(|&i| {
+                                (entities[i].position - position).magnitude2() <= limit
+                            }),
+                        );
+                    }
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+/// A cell size that suits `entities`: the diameter of the largest body.
+/// Falls back to 1 when there are no bodies with any size
+pub fn default_cell_size(entities: &[Entity]) -> f32 {
+    let largest = entities.iter().map(|body| body.radius).fold(0.0, f32::max);
+    if largest > 0.0 {
+        largest * 2.0
+    } else {
+        1.0
+    }
+}
+
+/// Every pair of bodies whose spheres overlap, lower index first, in the same order a
+/// check of every pair would find them. Only bodies sharing a cell of `cell_size` are
+/// compared
+pub fn overlapping_pairs(entities: &[Entity], cell_size: f32) -> Vec<(usize, usize)> {
+    SpatialHash::new(entities, cell_size)
+        .candidate_pairs()
+        .into_iter()
+        .filter(|&(i, j)| {
+            let (body, other) = (&entities[i], &entities[j]);
+            let reach = f64::from(body.radius + other.radius);
+            (other.position - body.position).magnitude2() < reach * reach
+        })
+        .collect()
+}
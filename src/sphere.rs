@@ -1,43 +1,215 @@
-use anyhow::Result;
+use crate::scene::BodyConfig;
+use anyhow::{ensure, Context, Result};
 use cgmath::*;
 use std::ops::Range;
 use wgpu::util::DeviceExt;
 use wgpu::BindGroup;
 
-pub struct Entity {
-    pub sphere: Sphere,
-    pub position: Vector3<f32>,
+/// Presets for the kinds of gravitating bodies found in a scene.
+/// Applying a preset at construction fills in sensible defaults for the body's fields.
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum BodyType {
+    /// A reflective body lit by the stars around it
+    Planet,
+    /// A massive body that glows and lights up the scene
+    Star,
+    /// An extremely massive, non-emissive body with almost no visible surface
+    BlackHole,
 }
 
-impl Entity {
-    pub fn new(new_position: Vector3<f32>, device: &wgpu::Device) -> Self {
-        /*        let mut sphere;
-        match Sphere::new(5, &device) {
-            Ok(sp) => {
-                sphere = sp;
-            }
-            Err(e) => {
-                Panic!("Sphere failed to create!");
-            }
-        }*/
+/// How many vertices along each edge of a cube face the finest sphere mesh has,
+/// unless the scene or command line asks for something else
+pub const DEFAULT_SPHERE_RESOLUTION: u32 = 10;
+/// The fewest vertices along an edge of a cube face that still make triangles,
+/// which turns the sphere into a cube
+pub const MIN_SPHERE_RESOLUTION: u32 = 2;
+
+/// Surface temperature of our sun in kelvin, used as the default star temperature
+pub const SOLAR_TEMPERATURE: f32 = 5778.0;
+
+impl BodyType {
+    /// The default mass of a body of this type
+    pub fn mass(&self) -> f32 {
+        match self {
+            BodyType::Planet => 1.0,
+            BodyType::Star => 1000.0,
+            BodyType::BlackHole => 100000.0,
+        }
+    }
 
-        let mut sphere = Sphere::new(5, &device);
+    /// The default radius of a body of this type
+    pub fn radius(&self) -> f32 {
+        match self {
+            BodyType::Planet => 0.5,
+            BodyType::Star => 2.0,
+            // Tiny, so there's no visible surface
+            BodyType::BlackHole => 0.01,
+        }
+    }
+
+    /// The default color of a body of this type
+    pub fn color(&self) -> [f32; 3] {
+        match self {
+            BodyType::Planet => [0.5, 0.5, 0.5],
+            BodyType::Star => temperature_to_color(SOLAR_TEMPERATURE),
+            BodyType::BlackHole => [0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Whether bodies of this type glow rather than being shaded
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, BodyType::Star)
+    }
+
+    /// Whether bodies of this type light up the bodies around them
+    pub fn is_light_source(&self) -> bool {
+        matches!(self, BodyType::Star)
+    }
+
+    /// Whether bodies of this type reflect the light of other bodies
+    pub fn is_reflective(&self) -> bool {
+        matches!(self, BodyType::Planet)
+    }
+}
+
+/// Approximates the color of a black body at the given temperature in kelvin.
+/// Based on Tanner Helland's curve fit, which is good from 1000K to 40000K.
+pub fn temperature_to_color(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let g = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_16 * (t - 60.0).powf(-0.075_514_846)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    [
+        (r / 255.0).clamp(0.0, 1.0),
+        (g / 255.0).clamp(0.0, 1.0),
+        (b / 255.0).clamp(0.0, 1.0),
+    ]
+}
+
+/// Shifts a color towards red the deeper a body sits in a gravity well, as a stand-in for
+/// gravitational time dilation. This is purely a visual effect and not physically accurate.
+/// `depth` comes from physics::potential_depth or potential_depths, and a strength of 0
+/// leaves the color unchanged.
+pub fn redshift(color: [f32; 3], depth: f32, strength: f32) -> [f32; 3] {
+    // 0 when far from everything, approaching 1 deep inside a well
+    let shift = 1.0 - (-depth * strength).exp();
+    [
+        color[0] + (1.0 - color[0]) * shift,
+        color[1] * (1.0 - shift),
+        color[2] * (1.0 - shift),
+    ]
+}
+
+/// A single body in our simulation.
+/// Every body is drawn with the renderer's shared sphere mesh, so this only holds
+/// the body's simulation state and how its instance should look
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entity {
+    /// Where the body's center is. Kept in f64, like the velocity, so long runs don't lose
+    /// their orbits to rounding. Only the renderer narrows it to f32
+    pub position: Vector3<f64>,
+    /// How fast and which way the body is moving
+    pub velocity: Vector3<f64>,
+    /// The preset this body was built from
+    pub body_type: BodyType,
+    /// How strongly the body pulls on the others, and how hard it is to move
+    pub mass: f32,
+    /// How big the body is drawn, and how close bodies get before they collide
+    pub radius: f32,
+    /// The body's color as red, green and blue from 0 to 1
+    pub color: [f32; 3],
+    /// Emissive bodies are drawn at full brightness instead of being shaded
+    pub emissive: bool,
+    /// Light sources illuminate the bodies around them
+    pub light_source: bool,
+    /// Reflective bodies are shaded by the light sources around them
+    pub reflective: bool,
+    /// The index of the body this body's Hill sphere is measured against. When unset it's
+    /// the heavier body pulling hardest on it, see physics::hill_primary
+    pub hill_primary: Option<usize>,
+    /// An equirectangular map wrapped around the body's surface, if it has one
+    #[serde(default)]
+    pub texture: Option<std::path::PathBuf>,
+}
 
+impl Entity {
+    /// Creates a new body at the given position with the defaults of its body type
+    pub fn new(body_type: BodyType, new_position: Vector3<f64>) -> Self {
         let position = new_position;
 
-        Self { sphere, position }
+        Self {
+            position,
+            velocity: Vector3::zero(),
+            body_type,
+            mass: body_type.mass(),
+            radius: body_type.radius(),
+            color: body_type.color(),
+            emissive: body_type.is_emissive(),
+            light_source: body_type.is_light_source(),
+            reflective: body_type.is_reflective(),
+            hill_primary: None,
+            texture: None,
+        }
+    }
+
+    /// Creates a body from its description in a scene
+    pub fn from_config(config: &BodyConfig) -> Self {
+        Self {
+            velocity: config.velocity.map(f64::from),
+            mass: config.mass,
+            radius: config.radius,
+            color: config.color,
+            texture: config.texture.clone(),
+            hill_primary: config.hill_primary,
+            ..Self::new(config.body_type, config.position.map(f64::from))
+        }
     }
 }
 
+/// A vertex type that can be handed to a render pipeline
 pub trait Vertex {
+    /// How the vertex is laid out in its vertex buffer
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
 
+/// A corner of the sphere mesh, laid out the way shader.wgsl expects
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SphereMeshVertex {
     position: [f32; 3],
     color: [f32; 3],
+    /// Points straight out of the surface. On a unit sphere this is the same as the position
+    normal: [f32; 3],
+    /// Where on an equirectangular map this vertex sits, see sphere_uv
+    uv: [f32; 2],
+}
+
+impl SphereMeshVertex {
+    /// Where the vertex sits on the unit sphere
+    pub fn position(&self) -> Vector3<f32> {
+        self.position.into()
+    }
+
+    /// Which way the surface faces at the vertex
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal.into()
+    }
 }
 
 impl Vertex for SphereMeshVertex {
@@ -57,76 +229,45 @@ impl Vertex for SphereMeshVertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
 
+/// Triangles uploaded to the GPU, ready to be drawn
 pub struct Mesh {
-    resolution: u32,
-    local_up: Vector3<f32>,
-    axis_a: Vector3<f32>,
-    axis_b: Vector3<f32>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_elements: u32,
 }
 
 impl Mesh {
-    fn new(resolution: u32, local_up: Vector3<f32>, device: &wgpu::Device) -> Self {
-        let axis_a = Vector3::new(local_up.y, local_up.z, local_up.x);
-        let axis_b = Vector3::cross(local_up, axis_a);
-
-        let mut vertices = Vec::new();
-        let mut triangles = Vec::new();
-        for x in 0..resolution {
-            for y in 0..resolution {
-                let i = x + y * resolution;
-                let percent = Vector2::new(x as f32, y as f32) / (resolution - 1) as f32;
-                let point_on_unit_cube =
-                    local_up + (percent.x - 0.5) * 2.0 * axis_a + (percent.y - 0.5) * 2.0 * axis_b;
-                let point_on_unit_sphere = point_on_unit_cube.normalize();
-                vertices.push(SphereMeshVertex {
-                    position: [
-                        point_on_unit_sphere.x,
-                        point_on_unit_sphere.y,
-                        point_on_unit_sphere.z,
-                    ],
-                    color: [0.5, 0.5, 0.5],
-                });
-
-                if x != resolution - 1 && y != resolution - 1 {
-                    // First Triangle
-                    triangles.push(i);
-                    triangles.push(i + resolution + 1);
-                    triangles.push(i + resolution);
-
-                    // Second Triangle
-                    triangles.push(i);
-                    triangles.push(i + 1);
-                    triangles.push(i + resolution + 1);
-                }
-            }
-        }
+    fn new(vertices: &[SphereMeshVertex], indices: &[u32], device: &wgpu::Device) -> Self {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
+            contents: bytemuck::cast_slice(vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
 
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Index Buffer"),
-            contents: bytemuck::cast_slice(&triangles),
+            contents: bytemuck::cast_slice(indices),
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let num_elements = triangles.len() as u32;
+        let num_elements = indices.len() as u32;
 
         Self {
-            resolution,
-            local_up,
-            axis_a,
-            axis_b,
             vertex_buffer,
             index_buffer,
             num_elements,
@@ -134,6 +275,217 @@ impl Mesh {
     }
 }
 
+/// Builds one face of the cube that gets inflated into a sphere: a grid of
+/// `resolution` by `resolution` vertices facing along `local_up`.
+/// Returns the face's vertices and triangle indices.
+fn face_geometry(resolution: u32, local_up: Vector3<f32>) -> (Vec<SphereMeshVertex>, Vec<u32>) {
+    let axis_a = Vector3::new(local_up.y, local_up.z, local_up.x);
+    let axis_b = Vector3::cross(local_up, axis_a);
+
+    let mut vertices = Vec::with_capacity((resolution * resolution) as usize);
+    let mut triangles = Vec::new();
+    // Rows of y on the outside, so each vertex is pushed at index i
+    for y in 0..resolution {
+        for x in 0..resolution {
+            let i = x + y * resolution;
+            let percent = Vector2::new(x as f32, y as f32) / (resolution - 1) as f32;
+            let point_on_unit_cube =
+                local_up + (percent.x - 0.5) * 2.0 * axis_a + (percent.y - 0.5) * 2.0 * axis_b;
+            let point_on_unit_sphere = point_on_unit_cube.normalize();
+            vertices.push(SphereMeshVertex {
+                position: point_on_unit_sphere.into(),
+                color: [1.0, 1.0, 1.0],
+                normal: point_on_unit_sphere.into(),
+                uv: sphere_uv(point_on_unit_sphere),
+            });
+
+            // Stepping along x moves along axis_a and along y moves along axis_b,
+            // and axis_a x axis_b = local_up, so both triangles are counterclockwise
+            // when seen from outside the sphere
+            if x != resolution - 1 && y != resolution - 1 {
+                // First Triangle
+                triangles.push(i);
+                triangles.push(i + resolution + 1);
+                triangles.push(i + resolution);
+
+                // Second Triangle
+                triangles.push(i);
+                triangles.push(i + 1);
+                triangles.push(i + resolution + 1);
+            }
+        }
+    }
+    (vertices, triangles)
+}
+
+/// Where a point on the unit sphere lands on an equirectangular map. u runs east from 0 to 1
+/// starting at the date line, on the -x side, and v runs south from 0 at the north pole (+y)
+/// to 1 at the south pole
+pub fn sphere_uv(point: Vector3<f32>) -> [f32; 2] {
+    // Longitude grows counterclockwise when seen from above the north pole
+    let longitude = (-point.z).atan2(point.x);
+    let latitude = point.y.clamp(-1.0, 1.0).asin();
+    [
+        0.5 + longitude / (2.0 * std::f32::consts::PI),
+        0.5 - latitude / std::f32::consts::PI,
+    ]
+}
+
+/// Triangles that cross the date line have u jump from near 1 back to near 0 across them,
+/// which would squeeze the whole map into that one strip. This gives their vertices on the
+/// 0 side copies with u past 1, which a repeating sampler wraps back around. Vertices right
+/// on a pole have no longitude of their own, so each triangle touching one gets a copy
+/// with the u of the rest of the triangle.
+pub fn split_uv_seam(
+    vertices: &[SphereMeshVertex],
+    indices: &[u32],
+) -> (Vec<SphereMeshVertex>, Vec<u32>) {
+    use std::collections::HashMap;
+
+    let is_pole = |vertex: &SphereMeshVertex| {
+        vertex.position[0].abs() < WELD_EPSILON && vertex.position[2].abs() < WELD_EPSILON
+    };
+    let mut vertices = vertices.to_vec();
+    let mut indices = indices.to_vec();
+    // The copy made with u past 1 for each vertex, so triangles sharing one share the copy
+    let mut wrapped: HashMap<u32, u32> = HashMap::new();
+
+    for triangle in indices.chunks_mut(3) {
+        let us = triangle
+            .iter()
+            .filter(|&&i| !is_pole(&vertices[i as usize]))
+            .map(|&i| vertices[i as usize].uv[0])
+            .collect::<Vec<_>>();
+        let min = us.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = us.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        if max - min > 0.5 {
+            for index in triangle.iter_mut() {
+                let vertex = vertices[*index as usize];
+                if is_pole(&vertex) || vertex.uv[0] >= 0.5 {
+                    continue;
+                }
+                *index = *wrapped.entry(*index).or_insert_with(|| {
+                    vertices.push(SphereMeshVertex {
+                        uv: [vertex.uv[0] + 1.0, vertex.uv[1]],
+                        ..vertex
+                    });
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+
+        let others = triangle
+            .iter()
+            .filter(|&&i| !is_pole(&vertices[i as usize]))
+            .map(|&i| vertices[i as usize].uv[0])
+            .collect::<Vec<_>>();
+        if others.is_empty() {
+            continue;
+        }
+        let u = others.iter().sum::<f32>() / others.len() as f32;
+        for index in triangle.iter_mut() {
+            let vertex = vertices[*index as usize];
+            if is_pole(&vertex) {
+                vertices.push(SphereMeshVertex {
+                    uv: [u, vertex.uv[1]],
+                    ..vertex
+                });
+                *index = vertices.len() as u32 - 1;
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// How close two vertices need to be to be merged into one by weld_vertices
+const WELD_EPSILON: f32 = 1e-5;
+
+/// Merges vertices that sit within `epsilon` of each other and points the indices at the
+/// survivors. The faces of the sphere each have their own copy of the vertices along the
+/// cube's edges, which would otherwise leave cracks and lighting seams between them.
+pub fn weld_vertices(
+    vertices: &[SphereMeshVertex],
+    indices: &[u32],
+    epsilon: f32,
+) -> (Vec<SphereMeshVertex>, Vec<u32>) {
+    use std::collections::HashMap;
+
+    // Vertices are bucketed into cells of size epsilon. A match can land in a neighbouring
+    // cell when the two sit either side of a cell boundary, so those get checked too
+    let cell = |position: [f32; 3]| {
+        [
+            (position[0] / epsilon).floor() as i64,
+            (position[1] / epsilon).floor() as i64,
+            (position[2] / epsilon).floor() as i64,
+        ]
+    };
+    let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut welded: Vec<SphereMeshVertex> = Vec::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+
+    for vertex in vertices {
+        let position = Vector3::from(vertex.position);
+        let [cx, cy, cz] = cell(vertex.position);
+        let mut existing = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    for &index in grid.get(&[cx + dx, cy + dy, cz + dz]).into_iter().flatten() {
+                        let other = Vector3::from(welded[index as usize].position);
+                        if (other - position).magnitude() <= epsilon {
+                            existing = Some(index);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = existing.unwrap_or_else(|| {
+            let index = welded.len() as u32;
+            welded.push(*vertex);
+            grid.entry([cx, cy, cz]).or_default().push(index);
+            index
+        });
+        remap.push(index);
+    }
+
+    let indices = indices.iter().map(|&i| remap[i as usize]).collect();
+    (welded, indices)
+}
+
+/// The vertices and triangle indices of a unit sphere made of six cube faces,
+/// each a grid of `resolution` by `resolution` vertices, with the faces welded together
+/// and split again along the date line so textures wrap around cleanly.
+/// Fails if the resolution is too small to make any triangles
+pub fn sphere_geometry(resolution: u32) -> Result<(Vec<SphereMeshVertex>, Vec<u32>)> {
+    // Fewer vertices a side can't make a single triangle
+    ensure!(
+        resolution >= MIN_SPHERE_RESOLUTION,
+        "Mesh resolution must be at least {}, got {}",
+        MIN_SPHERE_RESOLUTION,
+        resolution
+    );
+    // Every vertex needs to be reachable with a u32 index
+    resolution
+        .checked_mul(resolution)
+        .and_then(|face| face.checked_mul(DIRECTIONS.len() as u32))
+        .with_context(|| format!("Mesh resolution {} is too large", resolution))?;
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    // Creating our 6 faces of the cube/sphere
+    for dir in DIRECTIONS {
+        let (face_vertices, face_indices) = face_geometry(resolution, dir);
+        let offset = vertices.len() as u32;
+        vertices.extend(face_vertices);
+        indices.extend(face_indices.into_iter().map(|i| i + offset));
+    }
+
+    let (vertices, indices) = weld_vertices(&vertices, &indices, WELD_EPSILON);
+    Ok(split_uv_seam(&vertices, &indices))
+}
+
 const DIRECTIONS: [Vector3<f32>; 6] = [
     Vector3::new(0.0, 1.0, 0.0),  // up
     Vector3::new(0.0, -1.0, 0.0), // down
@@ -143,23 +495,25 @@ const DIRECTIONS: [Vector3<f32>; 6] = [
     Vector3::new(0.0, 0.0, -1.0), // back
 ];
 
+/// The unit sphere mesh every body is drawn with, scaled and moved by its instance
 pub struct Sphere {
-    meshes: Vec<Mesh>,
+    mesh: Mesh,
 }
 
 impl Sphere {
-    pub fn new(resolution: u32, device: &wgpu::Device) -> Self {
-        let mut meshes: Vec<Mesh> = Vec::with_capacity(6);
-        // Creating our 6 faces of the cube/sphere
-        for dir in DIRECTIONS {
-            meshes.push(Mesh::new(resolution, dir, device));
-        }
-
-        Self { meshes }
+    /// Builds a sphere out of six cube faces, each a grid of `resolution` by `resolution` vertices.
+    /// Fails if the resolution is too small to make any triangles
+    pub fn new(resolution: u32, device: &wgpu::Device) -> Result<Self> {
+        let (vertices, indices) = sphere_geometry(resolution)?;
+        Ok(Self {
+            mesh: Mesh::new(&vertices, &indices, device),
+        })
     }
 }
 
+/// Draws meshes lit by the scene's lights, see shader.wgsl
 pub trait DrawSphere<'a> {
+    /// Draws a single copy of `mesh`
     fn draw_mesh(
         &mut self,
         mesh: &'a Mesh,
@@ -167,6 +521,7 @@ pub trait DrawSphere<'a> {
         light_bind_group: &'a wgpu::BindGroup,
     );
 
+    /// Draws a copy of `mesh` for each of `instances`
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
@@ -175,6 +530,7 @@ pub trait DrawSphere<'a> {
         light_bind_group: &'a wgpu::BindGroup,
     );
 
+    /// Draws a single copy of `sphere`
     fn draw_sphere(
         &mut self,
         sphere: &'a Sphere,
@@ -182,6 +538,7 @@ pub trait DrawSphere<'a> {
         light_bind_group: &'a wgpu::BindGroup,
     );
 
+    /// Draws a copy of `sphere` for each of `instances`
     fn draw_sphere_instanced(
         &mut self,
         sphere: &'a Sphere,
@@ -213,8 +570,8 @@ where
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        self.set_bind_group(0, &camera_bind_group, &[]);
-        self.set_bind_group(1, &light_bind_group, &[]);
+        self.set_bind_group(0, camera_bind_group, &[]);
+        self.set_bind_group(1, light_bind_group, &[]);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
 
@@ -234,19 +591,20 @@ where
         camera_bind_group: &'b BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
-        for mesh in &sphere.meshes {
-            self.draw_mesh_instanced(mesh, instances.clone(), camera_bind_group, light_bind_group);
-        }
+        self.draw_mesh_instanced(&sphere.mesh, instances, camera_bind_group, light_bind_group);
     }
 }
 
+/// Draws meshes at full brightness to mark where the lights are, see light.wgsl
 pub trait DrawLight<'a> {
+    /// Draws a single copy of `mesh`
     fn draw_light_mesh(
         &mut self,
         mesh: &'a Mesh,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    /// Draws a copy of `mesh` for each of `instances`
     fn draw_light_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
@@ -255,12 +613,14 @@ pub trait DrawLight<'a> {
         light_bind_group: &'a wgpu::BindGroup,
     );
 
+    /// Draws a single copy of `sphere`
     fn draw_light_model(
         &mut self,
         sphere: &'a Sphere,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    /// Draws a copy of `sphere` for each of `instances`
     fn draw_light_model_instanced(
         &mut self,
         sphere: &'a Sphere,
@@ -312,13 +672,30 @@ where
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
-        for mesh in &sphere.meshes {
-            self.draw_light_mesh_instanced(
-                mesh,
-                instances.clone(),
-                camera_bind_group,
-                light_bind_group,
-            );
-        }
+        self.draw_light_mesh_instanced(
+            &sphere.mesh,
+            instances,
+            camera_bind_group,
+            light_bind_group,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_glow_and_light_the_bodies_around_them() {
+        assert!(BodyType::Star.is_emissive());
+        assert!(BodyType::Star.is_light_source());
+        assert!(!BodyType::Star.is_reflective());
+    }
+
+    #[test]
+    fn planets_are_lit_rather_than_glowing() {
+        assert!(!BodyType::Planet.is_emissive());
+        assert!(!BodyType::Planet.is_light_source());
+        assert!(BodyType::Planet.is_reflective());
     }
 }
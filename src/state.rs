@@ -1,18 +1,146 @@
-use crate::sphere::{DrawLight, Entity, Sphere};
-use crate::{camera, render, sphere, texture, DrawSphere};
-use cgmath::{Rotation3, Vector3};
-use wgpu::*;
-use winit::event::WindowEvent;
+use crate::compute::GravityCompute;
+use crate::frame_timer::{self, FrameTimer};
+use crate::hud::{Hud, HudStats};
+use crate::integrator::AdaptiveTimestep;
+use crate::picking;
+use crate::scene::{self, Scene};
+use crate::simulation::Simulation;
+use crate::snapshot::Snapshot;
+use crate::stepper::Stepper;
+use crate::trail::{self, TrailBuffer};
+use crate::{camera, gpu, instance, physics, render, sphere, texture, DrawSphere};
+use anyhow::Context;
+use cgmath::{EuclideanSpace, InnerSpace, Rotation3, Vector3};
+use std::collections::VecDeque;
+use std::path::Path;
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
 use winit::window::Window;
-use winit::*;
+
+/// The default length of a physics step, in seconds
+pub const DEFAULT_DT: f64 = 1.0 / 120.0;
+/// The slowest the simulation can run compared to real time
+pub const MIN_TIME_SCALE: f32 = 0.01;
+/// The fastest the simulation can run compared to real time
+pub const MAX_TIME_SCALE: f32 = 1000.0;
+/// The lightest body I can spawn
+pub const MIN_BRUSH_MASS: f32 = 1e-3;
+/// The heaviest body I can spawn
+pub const MAX_BRUSH_MASS: f32 = 1e6;
+/// How many degrees a second the light turns about the scene unless the scene says otherwise
+pub const DEFAULT_LIGHT_ROTATION_SPEED: f32 = 60.0;
+/// How fast Shift+X kicks every body away from the center of mass
+pub const DEFAULT_EXPLODE_SPEED: f32 = 1.0;
+/// How many degrees a second the camera path circles the camera target
+pub const DEFAULT_CAMERA_PATH_SPEED: f32 = 10.0;
+/// How many pixels the cursor can move between pressing and releasing the left button
+/// for it to still count as a click rather than dragging the camera around
+const CLICK_TOLERANCE: f64 = 4.0;
+/// How many frames the bodies are stepped on the GPU before they're read back
+pub const DEFAULT_GPU_SYNC_INTERVAL: usize = 10;
+/// How many frames go by between readings of the total energy. Each reading sums every
+/// pair of bodies, which is too slow to do every frame once there are many of them
+pub const ENERGY_SAMPLE_INTERVAL: usize = 6;
+/// How many readings of total energy State::energy_history keeps, ten seconds at 60 fps
+pub const ENERGY_HISTORY_LENGTH: usize = 100;
+
+/// Independent switches for the parts of the scene that move on their own.
+/// Freezing physics while the light and camera keep moving gives a "cinematic pause" for
+/// presentations.
+pub struct Animations {
+    /// Whether the bodies are simulated
+    pub physics: bool,
+    /// Whether the light orbits the scene
+    pub light: bool,
+    /// How many degrees a second the light turns about the y axis. 0 holds it still
+    pub light_rotation_speed: f32,
+    /// Whether the camera flies itself around its target
+    pub camera_path: bool,
+    /// How many degrees a second the camera path circles the target
+    pub camera_path_speed: f32,
+}
+
+impl Default for Animations {
+    fn default() -> Self {
+        Self {
+            physics: true,
+            light: true,
+            light_rotation_speed: DEFAULT_LIGHT_ROTATION_SPEED,
+            camera_path: false,
+            camera_path_speed: DEFAULT_CAMERA_PATH_SPEED,
+        }
+    }
+}
+
+impl Animations {
+    /// Moves the camera along its path and the light about the scene by `dt` seconds of
+    /// real time, whichever are switched on. The bodies are left to the physics switch
+    pub fn animate(&self, camera: &mut camera::Camera, light_position: &mut [f32; 3], dt: f32) {
+        if self.camera_path {
+            camera.circle_target(cgmath::Deg(self.camera_path_speed * dt));
+        }
+        if self.light && self.light_rotation_speed != 0.0 {
+            // Scaled by the frame time so the light turns at the same speed at any frame rate
+            let angle = cgmath::Deg(self.light_rotation_speed * dt);
+            let old_position: cgmath::Vector3<_> = (*light_position).into();
+            *light_position = (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), angle)
+                * old_position)
+                .into();
+        }
+    }
+}
+
+/// What a spawned body is made of and how it starts moving
+pub struct Brush {
+    /// Decides how the body looks and its radius
+    pub body_type: sphere::BodyType,
+    pub mass: f32,
+    /// When on, spawned bodies start on a circular orbit about the body pulling hardest on
+    /// them, otherwise they start at rest
+    pub orbit: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            body_type: sphere::BodyType::Planet,
+            mass: sphere::BodyType::Planet.mass(),
+            orbit: true,
+        }
+    }
+}
+
+impl Brush {
+    /// A body of the brush's kind at `position`, given the bodies already there and the
+    /// strength of gravity between them
+    pub fn body(
+        &self,
+        position: Vector3<f64>,
+        bodies: &[sphere::Entity],
+        g: f32,
+    ) -> sphere::Entity {
+        let mut body = sphere::Entity::new(self.body_type, position);
+        body.mass = self.mass;
+        if let Some(primary) = physics::strongest_pull(bodies, position)
+            .filter(|_| self.orbit)
+            .map(|index| &bodies[index])
+        {
+            let offset = (position - primary.position).map(|x| x as f32);
+            // Relative to the primary, so the body keeps up with it while it moves
+            body.velocity =
+                primary.velocity + scene::circular_orbit(primary.mass, offset, g).map(f64::from);
+        }
+        body
+    }
+}
 
 /// The struct State holds the the current state of the program.
 ///
 pub struct State {
-    /// A handle to our surface and adapter(GPU)
-    pub instance: wgpu::Instance,
-    /// The window we will draw to
-    pub surface: wgpu::Surface,
+    /// The window we will draw to. Without one, frames can only be captured offscreen
+    pub surface: Option<wgpu::Surface>,
     /// The connection to our GPU
     pub device: wgpu::Device,
     /// The command queue for our device
@@ -23,53 +151,90 @@ pub struct State {
     pub size: winit::dpi::PhysicalSize<u32>,
     /// Our renderer from render.rs
     pub renderer: render::Render,
+    /// Which parts of the scene are currently animating
+    pub animations: Animations,
+    /// How long frames are taking, which also works out how much time each update covers
+    pub frame_timer: FrameTimer,
+    /// The bodies being simulated and the physics that moves them
+    pub simulation: Simulation,
+    /// A second run of the bodies with a different integrator, stepped alongside the
+    /// simulation and drawn see-through over it so the two can be compared. It keeps the
+    /// physics settings it started with, and is always stepped on the CPU
+    pub ghost: Option<Simulation>,
+    /// The simulation as it was at launch, which R resets to
+    pub initial: Snapshot,
+    /// Keeps the bodies on the GPU and steps them there when set, always with velocity
+    /// Verlet. The CPU's copy of the bodies is only brought up to date every
+    /// gpu_sync_interval frames, which is also when collisions are merged and trails,
+    /// lights and the followed body catch up. Only created when built with the gpu-gravity
+    /// feature
+    pub gravity_compute: Option<GravityCompute>,
+    /// How many frames of GPU steps go by between reading the bodies back
+    pub gpu_sync_interval: usize,
+    /// Frames with GPU steps since the bodies were last read back
+    pub frames_since_sync: usize,
+    /// How many pairs were clamped to the minimum separation in the last frame's last step
+    pub clamped_pairs: usize,
+    /// Cuts the time passing each frame into physics steps, falling back to slow motion
+    /// when a frame would need too many
+    pub stepper: Stepper,
+    /// How many seconds of simulated time pass for each second of real time
+    pub time_scale: f32,
+    /// Set to take a single physics step on the next update while physics is paused
+    pub step_requested: bool,
+    /// The total energy of the bodies every ENERGY_SAMPLE_INTERVAL frames while the HUD
+    /// shows it, oldest first, at most ENERGY_HISTORY_LENGTH of them. Started over whenever
+    /// the energy jumps for a reason other than the integrator, like bodies being added or
+    /// the softening changing
+    pub energy_history: VecDeque<f64>,
+    /// Frames since the total energy was last added to energy_history
+    pub frames_since_energy: usize,
+    /// The recent positions of each body, in the same order as the simulation's entities
+    pub trails: Vec<TrailBuffer>,
+    /// How many positions each trail holds, at most trail::MAX_TRAIL_LENGTH
+    pub trail_length: usize,
+    /// The diagnostics overlay
+    pub hud: Hud,
+    /// The index of the body the camera is following, if any. The camera keeps the same
+    /// view of it as it moves, so orbiting and zooming happen around the body
+    pub follow_target: Option<usize>,
+    /// The index of the body last clicked on, which is drawn highlighted
+    pub selected: Option<usize>,
+    /// Where the cursor is in the window, if it's been over it
+    pub cursor: Option<PhysicalPosition<f64>>,
+    /// Where the cursor was when the left button went down, to tell clicks from drags
+    pub click_start: Option<PhysicalPosition<f64>>,
+    /// Which modifier keys are held down
+    pub modifiers: ModifiersState,
+    /// What I spawns at the camera target
+    pub brush: Brush,
+    /// How fast Shift+X kicks every body away from the center of mass, see explode
+    pub explode_speed: f32,
 }
 
 impl State {
     /// Initializes a new state.
-    /// Takes a winit::window parameter
-    pub async fn new(window: &Window) -> Self {
+    /// Takes a winit::window parameter, the scene to start from and the simulation set up
+    /// from it
+    pub async fn new(
+        window: &Window,
+        scene: &Scene,
+        simulation: Simulation,
+    ) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
-        // An instance is a handle to surface and adapter
-        // We can target all backends (Vulkan, Metal, DX12, WebGPU)
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-
-        // Where we will draw to
-        let surface = unsafe { instance.create_surface(window) };
-
-        // Our GPU
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .unwrap();
-
-        // Creating our connection to the GPU and its command queue
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    // The features we want from our GPU
-                    // Currently only calling the line draw mode
-                    features: wgpu::Features::POLYGON_MODE_LINE,
-                    // The limits an adapter supports.
-                    // default() will support all modern backends
-                    limits: wgpu::Limits::default(),
-                    // Debug Label
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        // The first GPU that can draw to the window, along with where we will draw to
+        let gpu::Gpu {
+            surface,
+            device,
+            queue,
+            format,
+        } = gpu::connect(window).await?;
 
         // Definding our surface's configuration
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
+            format,
             width: size.width,
             height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
@@ -77,35 +242,148 @@ impl State {
         // Initializing our surface using the above config
         surface.configure(&device, &config);
 
+        Self::with_device(
+            Some(surface),
+            device,
+            queue,
+            config,
+            window.scale_factor(),
+            scene,
+            simulation,
+        )
+    }
+
+    /// Sets up everything but the GPU connection, drawing to `surface` if there is one.
+    /// `scale_factor` is the window's HiDPI scale
+    fn with_device(
+        surface: Option<wgpu::Surface>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        scale_factor: f64,
+        scene: &Scene,
+        simulation: Simulation,
+    ) -> anyhow::Result<Self> {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+
         // Initializing our render
-        let renderer = render::Render::new(&device, &config);
+        let sphere_resolution = scene
+            .sphere_resolution
+            .unwrap_or(sphere::DEFAULT_SPHERE_RESOLUTION);
+        let mut renderer = render::Render::new(&device, &queue, &config, sphere_resolution)?;
+        let hud = Hud::new(size, scale_factor, &device, config.format);
 
-        Self {
+        if let Some(rgb) = scene.background {
+            renderer.background = render::Background::from_rgb(rgb);
+        }
+        let mut animations = Animations::default();
+        if let Some(light) = &scene.light {
+            renderer.light_uniform.position = light.position.into();
+            renderer.light_uniform.color = light.color;
+            if let Some(speed) = light.rotation_speed {
+                animations.light_rotation_speed = speed;
+            }
+        }
+        let gravity_compute = if cfg!(feature = "gpu-gravity") {
+            let mut compute = GravityCompute::new(&device, simulation.entities.len());
+            compute.upload(
+                &device,
+                &queue,
+                &simulation.entities,
+                simulation.gravitational_constant,
+                simulation.softening,
+                simulation.min_separation(),
+            );
+            Some(compute)
+        } else {
+            None
+        };
+
+        Ok(Self {
             size,
-            instance,
             surface,
             device,
             queue,
             config,
             renderer,
-        }
+            animations,
+            frame_timer: FrameTimer::new(frame_timer::DEFAULT_FRAME_WINDOW),
+            initial: simulation.snapshot(),
+            simulation,
+            ghost: None,
+            gravity_compute,
+            gpu_sync_interval: DEFAULT_GPU_SYNC_INTERVAL,
+            frames_since_sync: 0,
+            clamped_pairs: 0,
+            stepper: Stepper::default(),
+            time_scale: 1.0,
+            step_requested: false,
+            energy_history: VecDeque::with_capacity(ENERGY_HISTORY_LENGTH),
+            frames_since_energy: 0,
+            trails: Vec::new(),
+            trail_length: trail::DEFAULT_TRAIL_LENGTH,
+            hud,
+            follow_target: None,
+            selected: None,
+            cursor: None,
+            click_start: None,
+            modifiers: ModifiersState::empty(),
+            brush: Brush::default(),
+            explode_speed: DEFAULT_EXPLODE_SPEED,
+        })
     }
 
     /// Recalculates window size whenever the user resizes the window.
     /// Takes in the state itself as well as the new size of the window.
     /// new_size is a winit::PhysicalSize struct that contains a width and height of the specificed type,
     /// in this case a u32.
-    /// Also recreates our depth texture.
+    /// Also recreates our depth texture and fits the camera to the new shape.
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            // Rebuilding our depth texture and then reconfiguring the surface
-            self.renderer.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
-            self.surface.configure(&self.device, &self.config);
+            self.resize_targets(new_size.width, new_size.height);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+        }
+    }
+
+    /// Draws at `width` by `height` from now on, whatever size the window is, for frames
+    /// captured offscreen. The window's surface is left alone, so it shouldn't be presented
+    /// to afterwards
+    pub fn set_render_size(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max || height > max {
+            anyhow::bail!(
+                "Can't render at {}x{}, the most this GPU can draw is {}x{}",
+                width,
+                height,
+                max,
+                max
+            );
         }
+        self.resize_targets(width, height);
+        Ok(())
+    }
+
+    /// Rebuilds everything drawn into at the old size and fits the camera to the new shape
+    fn resize_targets(&mut self, width: u32, height: u32) {
+        self.config.width = width;
+        self.config.height = height;
+        // Fixing the projection now rather than waiting for the next update
+        self.renderer.camera.resize(width, height);
+        self.renderer
+            .camera_uniform
+            .update_view_proj(&self.renderer.camera);
+        self.queue.write_buffer(
+            &self.renderer.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.renderer.camera_uniform]),
+        );
+        // Rebuilding our depth texture and the bloom targets
+        self.renderer.depth_texture =
+            texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+        self.renderer.bloom.resize(&self.device, &self.config);
     }
 
     // A lot of the following functions (input, update, render) can probably also
@@ -114,14 +392,840 @@ impl State {
 
     /// Catches window events such as keyboard and mouse clicks
     pub fn input(&mut self, event: &WindowEvent) -> bool {
+        // The mouse is only watched here, and still reaches the camera controller below
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.cursor = Some(*position),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => self.click_start = self.cursor,
+                ElementState::Released => {
+                    // Only a click picks a body, dragging orbits the camera
+                    if let (Some(start), Some(end)) = (self.click_start.take(), self.cursor) {
+                        if (end.x - start.x).hypot(end.y - start.y) <= CLICK_TOLERANCE {
+                            self.pick(end.x as f32, end.y as f32);
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+            ..
+        } = event
+        {
+            // Number keys toggle individual render passes for debugging
+            if let Some(pass) = render::PassFlags::from_key(*keycode) {
+                self.renderer.passes.toggle(pass);
+                return true;
+            }
+            match keycode {
+                // Toggles seeing the inside of bodies the camera flies into
+                VirtualKeyCode::C => {
+                    self.renderer.cutaway = !self.renderer.cutaway;
+                    return true;
+                }
+                // Switches between drawing bodies as spheres and as points
+                VirtualKeyCode::M => {
+                    let renderer = &mut self.renderer;
+                    renderer.style = match renderer.style {
+                        render::RenderStyle::Spheres => render::RenderStyle::Points,
+                        render::RenderStyle::Points => render::RenderStyle::Spheres,
+                    };
+                    eprintln!("Render style: {:?}", renderer.style);
+                    return true;
+                }
+                // Cycles what bodies are tinted by, or with Ctrl held the colors they're
+                // tinted with
+                VirtualKeyCode::U => {
+                    let scale = &mut self.renderer.color_scale;
+                    if self.modifiers.ctrl() {
+                        scale.colormap = scale.colormap.next();
+                        eprintln!("Colormap: {}", scale.colormap.name());
+                    } else {
+                        scale.color_by = scale.color_by.next();
+                        eprintln!("Color by: {}", scale.color_by.name());
+                    }
+                    return true;
+                }
+                // Switches between filled and wireframe bodies
+                VirtualKeyCode::L => {
+                    self.renderer.wireframe = !self.renderer.wireframe;
+                    return true;
+                }
+                // Turns back-face culling off to check the winding of the meshes
+                VirtualKeyCode::B => {
+                    self.renderer.culling = !self.renderer.culling;
+                    return true;
+                }
+                // Freezes or resumes the simulation independently of the light
+                VirtualKeyCode::P | VirtualKeyCode::Space => {
+                    self.toggle_pause();
+                    return true;
+                }
+                // Doubles or halves how fast the simulation runs,
+                // or with Ctrl held the mass of the bodies I spawns
+                VirtualKeyCode::Plus | VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                    if self.modifiers.ctrl() {
+                        self.set_brush_mass(self.brush.mass * 2.0);
+                    } else {
+                        self.set_time_scale(self.time_scale * 2.0);
+                    }
+                    return true;
+                }
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                    if self.modifiers.ctrl() {
+                        self.set_brush_mass(self.brush.mass * 0.5);
+                    } else {
+                        self.set_time_scale(self.time_scale * 0.5);
+                    }
+                    return true;
+                }
+                // Drops a body from the brush at the camera target
+                VirtualKeyCode::I | VirtualKeyCode::Insert => {
+                    self.spawn();
+                    return true;
+                }
+                // Steps a paused simulation forward by a single step
+                VirtualKeyCode::Period => {
+                    self.step_requested = true;
+                    return true;
+                }
+                // Cycles how the bodies are moved forward in time, or with Ctrl held starts
+                // or stops a comparison run with the next integrator
+                VirtualKeyCode::J if self.modifiers.ctrl() => {
+                    self.toggle_ghost();
+                    return true;
+                }
+                VirtualKeyCode::J => {
+                    if self.gravity_compute.is_some() {
+                        eprintln!("Bodies on the GPU are always stepped with velocity Verlet");
+                        return true;
+                    }
+                    self.simulation.integrator = self.simulation.integrator.next();
+                    // Leapfrog leaves the accelerations at the midpoint of its last step,
+                    // where the others start from the bodies' current positions
+                    self.simulation.refresh_accelerations();
+                    eprintln!("Integrator: {:?}", self.simulation.integrator);
+                    return true;
+                }
+                // Switches between fixed and adaptive physics steps
+                VirtualKeyCode::T => {
+                    let simulation = &mut self.simulation;
+                    simulation.adaptive_timestep = match simulation.adaptive_timestep {
+                        Some(_) => None,
+                        None => Some(AdaptiveTimestep::default()),
+                    };
+                    eprintln!(
+                        "Adaptive timestep: {}",
+                        if simulation.adaptive_timestep.is_some() {
+                            "on"
+                        } else {
+                            "off"
+                        }
+                    );
+                    return true;
+                }
+                // Turns the minimum separation clamp on or off
+                VirtualKeyCode::N => {
+                    let simulation = &mut self.simulation;
+                    simulation.clamp_separation = !simulation.clamp_separation;
+                    eprintln!(
+                        "Separation clamp: {}",
+                        match simulation.min_separation() {
+                            Some(separation) => format!("{}", separation),
+                            None => "off".to_string(),
+                        }
+                    );
+                    self.refresh_gravity();
+                    return true;
+                }
+                // Switches between merging bodies that touch and letting them pass through,
+                // or with Shift held blows the bodies apart
+                VirtualKeyCode::X if self.modifiers.shift() => {
+                    self.explode();
+                    return true;
+                }
+                VirtualKeyCode::X => {
+                    let simulation = &mut self.simulation;
+                    simulation.merge_on_collision = !simulation.merge_on_collision;
+                    eprintln!(
+                        "Collisions: {}",
+                        if simulation.merge_on_collision {
+                            "merge"
+                        } else {
+                            "pass through"
+                        }
+                    );
+                    return true;
+                }
+                // Halves or doubles the softening length
+                VirtualKeyCode::PageDown | VirtualKeyCode::PageUp => {
+                    let factor = if *keycode == VirtualKeyCode::PageDown {
+                        0.5
+                    } else {
+                        2.0
+                    };
+                    self.simulation.softening *= factor;
+                    eprintln!("Softening: {}", self.simulation.softening);
+                    self.refresh_gravity();
+                    return true;
+                }
+                // Switches between fitting the clipping planes around the scene every frame
+                // and keeping them where they are
+                VirtualKeyCode::Z => {
+                    let camera = &mut self.renderer.camera;
+                    camera.auto_depth_range = !camera.auto_depth_range;
+                    eprintln!(
+                        "Auto depth range: {} (near {}, far {})",
+                        if camera.auto_depth_range { "on" } else { "off" },
+                        camera.znear,
+                        camera.zfar
+                    );
+                    return true;
+                }
+                // Shows or hides the axes through the origin
+                VirtualKeyCode::G => {
+                    self.renderer.show_axes = !self.renderer.show_axes;
+                    return true;
+                }
+                // Shows or hides the grid in the plane of the orbits, or with Ctrl held the
+                // Hill spheres
+                VirtualKeyCode::H => {
+                    let renderer = &mut self.renderer;
+                    if self.modifiers.ctrl() {
+                        renderer.show_hill_spheres = !renderer.show_hill_spheres;
+                    } else {
+                        renderer.show_grid = !renderer.show_grid;
+                    }
+                    return true;
+                }
+                // Follows the next body with the camera
+                VirtualKeyCode::Tab => {
+                    self.follow_next();
+                    return true;
+                }
+                // Switches between perspective and orthographic projection
+                VirtualKeyCode::O => {
+                    self.renderer.camera.toggle_projection();
+                    return true;
+                }
+                // Frames every body
+                VirtualKeyCode::F | VirtualKeyCode::Home => {
+                    self.frame_all();
+                    return true;
+                }
+                // Looks at the center of mass, for when the whole system drifts off
+                VirtualKeyCode::Y => {
+                    self.center_on_mass();
+                    return true;
+                }
+                // Starts the simulation over from where it was at launch
+                VirtualKeyCode::R => {
+                    self.reset();
+                    return true;
+                }
+                // Takes the selected body out of the simulation
+                VirtualKeyCode::Delete => {
+                    if let Some(index) = self.selected {
+                        self.remove_body(index);
+                        eprintln!("Removed body {}", index);
+                    }
+                    return true;
+                }
+                // Stops following, leaving the camera where it is
+                VirtualKeyCode::Back => {
+                    self.follow_target = None;
+                    return true;
+                }
+                // Freezes or resumes the light orbiting the scene, or with Ctrl held starts
+                // or stops the camera circling its target
+                VirtualKeyCode::K if self.modifiers.ctrl() => {
+                    let animations = &mut self.animations;
+                    animations.camera_path = !animations.camera_path;
+                    eprintln!(
+                        "Camera path: {}",
+                        if animations.camera_path { "on" } else { "off" }
+                    );
+                    return true;
+                }
+                VirtualKeyCode::K => {
+                    self.animations.light = !self.animations.light;
+                    return true;
+                }
+                // Switches gravity between summing every pair and the Barnes-Hut octree
+                VirtualKeyCode::F2 => {
+                    let strategy = &mut self.simulation.gravity_strategy;
+                    *strategy = match strategy {
+                        physics::GravityStrategy::BruteForce => {
+                            physics::GravityStrategy::BarnesHut {
+                                theta: physics::DEFAULT_THETA,
+                            }
+                        }
+                        physics::GravityStrategy::BarnesHut { .. } => {
+                            physics::GravityStrategy::BruteForce
+                        }
+                    };
+                    eprintln!("Gravity: {:?}", strategy);
+                    return true;
+                }
+                _ => {}
+            }
+        }
         self.renderer.camera_controller.process_events(event)
     }
 
-    /// Updates our camera position and light uniform
+    /// Catches raw device input, which the flying camera looks around with
+    pub fn device_input(&mut self, event: &winit::event::DeviceEvent) -> bool {
+        self.renderer.camera_controller.process_device_events(event)
+    }
+
+    /// Switches the camera between orbiting and flying.
+    /// Returns whether it's now flying, in which case the cursor should be grabbed
+    pub fn toggle_camera_mode(&mut self) -> bool {
+        use crate::camera::CameraMode;
+        let mode = match self.renderer.camera_controller.mode() {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        };
+        self.renderer
+            .camera_controller
+            .set_mode(mode, &self.renderer.camera);
+        mode == CameraMode::Fly
+    }
+
+    /// Moves the camera on to following the next body, wrapping back around to the first
+    pub fn follow_next(&mut self) {
+        self.follow_target = match self.follow_target {
+            _ if self.simulation.entities.is_empty() => None,
+            Some(index) => Some((index + 1) % self.simulation.entities.len()),
+            None => Some(0),
+        };
+        // Jumping straight onto the body, keeping the eye's offset from the target
+        self.follow();
+    }
+
+    /// Selects and follows the body under the pixel at (`x`, `y`), printing what it's like.
+    /// Clicking empty space clears the selection, leaving the camera where it is
+    pub fn pick(&mut self, x: f32, y: f32) {
+        // The flying camera hides the cursor, so there's nothing to point with
+        if self.renderer.camera_controller.mode() == crate::camera::CameraMode::Fly {
+            return;
+        }
+        // Picking against where the bodies are now, not where they were at the last sync
+        if self.sync_from_gpu() {
+            self.renderer.write_instances(
+                &self.device,
+                &self.queue,
+                &self.simulation.entities,
+                self.selected,
+                0.0,
+            );
+        }
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        let ray = self.renderer.camera.screen_ray(x, y, width, height);
+        // Picking against the spheres as drawn, which aren't always the bodies' own radii
+        self.selected = ray.and_then(|(origin, direction)| {
+            let spheres = self
+                .renderer
+                .instances
+                .iter()
+                .map(|instance| (cgmath::Point3::from_vec(instance.position), instance.radius));
+            picking::pick(origin, direction, spheres)
+        });
+
+        if let Some(index) = self.selected {
+            let body = &self.simulation.entities[index];
+            let (position, velocity): ([f64; 3], [f64; 3]) =
+                (body.position.into(), body.velocity.into());
+            eprintln!(
+                "Body {}: {:?}, mass {}, radius {}, position {:?}, velocity {:?} (speed {})",
+                index,
+                body.body_type,
+                body.mass,
+                body.radius,
+                position,
+                velocity,
+                body.velocity.magnitude()
+            );
+            self.follow_target = Some(index);
+        }
+    }
+
+    /// Points the camera at the middle of the bodies and pulls it back until the sphere
+    /// around all of them fits on screen, whichever projection it's using.
+    /// Stops following any body, since that would drag the camera straight back.
+    /// Does nothing when there are no bodies to frame
+    pub fn frame_all(&mut self) {
+        if self.simulation.entities.is_empty() {
+            return;
+        }
+        let (_, min, max) = self.renderer.scene_bounds();
+        // The sphere through the corners of the box, so nothing in it is left out
+        let center = (min + max) * 0.5;
+        let radius =
+            ((max - min).magnitude() * 0.5).max(self.renderer.camera_controller.min_distance);
+        self.follow_target = None;
+        self.renderer
+            .camera
+            .frame(cgmath::Point3::from_vec(center), radius);
+    }
+
+    /// Slides the camera so it looks at the bodies' center of mass, keeping the same
+    /// direction and distance. Stops following any body, which would pull it away again
+    pub fn center_on_mass(&mut self) {
+        if self.simulation.entities.is_empty() {
+            return;
+        }
+        let center = physics::center_of_mass(&self.simulation.entities).map(|x| x as f32);
+        self.follow_target = None;
+        let camera = &mut self.renderer.camera;
+        let offset = cgmath::Point3::from_vec(center) - camera.target;
+        camera.target += offset;
+        camera.eye += offset;
+    }
+
+    /// Moves the camera along with the body it's following, keeping the same view of it
+    fn follow(&mut self) {
+        let position = match self
+            .follow_target
+            .and_then(|index| self.simulation.entities.get(index))
+        {
+            Some(entity) => cgmath::Point3::from_vec(entity.position.map(|x| x as f32)),
+            None => {
+                self.follow_target = None;
+                return;
+            }
+        };
+        let camera = &mut self.renderer.camera;
+        let offset = position - camera.target;
+        camera.target += offset;
+        camera.eye += offset;
+    }
+
+    /// Freezes or resumes the simulation, leaving the light and camera moving
+    pub fn toggle_pause(&mut self) {
+        self.animations.physics = !self.animations.physics;
+    }
+
+    /// Starts a comparison run from where the bodies are now, stepped with the integrator
+    /// after the simulation's, or stops the one that's running
+    pub fn toggle_ghost(&mut self) {
+        if self.ghost.take().is_some() {
+            eprintln!("Comparison: off");
+            return;
+        }
+        // The comparison starts from the bodies on the GPU when they're kept there
+        self.sync_from_gpu();
+        let mut ghost = self.simulation.comparison();
+        ghost.integrator = ghost.integrator.next();
+        ghost.refresh_accelerations();
+        eprintln!(
+            "Comparison: {:?} ghosted over {:?}",
+            ghost.integrator, self.simulation.integrator
+        );
+        self.ghost = Some(ghost);
+    }
+
+    /// Starts the comparison run over from the bodies as they are now, still with its own
+    /// integrator. The two runs' bodies are paired by index, so this is how they're kept
+    /// lined up when bodies are added, removed or replaced
+    fn restart_ghost(&mut self) {
+        if let Some(ghost) = self.ghost.as_mut() {
+            let integrator = ghost.integrator;
+            *ghost = self.simulation.comparison();
+            ghost.integrator = integrator;
+            ghost.refresh_accelerations();
+        }
+    }
+
+    /// Adds the bodies' current total energy to energy_history, dropping the oldest
+    /// reading once it's full
+    fn record_energy(&mut self) {
+        self.frames_since_energy = 0;
+        if self.energy_history.len() >= ENERGY_HISTORY_LENGTH {
+            self.energy_history.pop_front();
+        }
+        self.energy_history
+            .push_back(self.simulation.total_energy());
+    }
+
+    /// How far the total energy has moved across energy_history, as a fraction of the
+    /// oldest reading. A good integrator and step keep this close to 0 for bound orbits.
+    /// None until there are two readings, or if the oldest was 0
+    pub fn energy_drift(&self) -> Option<f64> {
+        let (first, last) = (self.energy_history.front()?, self.energy_history.back()?);
+        if self.energy_history.len() < 2 || *first == 0.0 {
+            return None;
+        }
+        Some((last - first) / first.abs())
+    }
+
+    /// Sets how fast the simulation runs compared to real time, within
+    /// MIN_TIME_SCALE and MAX_TIME_SCALE
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+        eprintln!("Time scale: {}x", self.time_scale);
+    }
+
+    /// Sets the mass of the bodies I spawns, within MIN_BRUSH_MASS and MAX_BRUSH_MASS
+    pub fn set_brush_mass(&mut self, mass: f32) {
+        self.brush.mass = mass.clamp(MIN_BRUSH_MASS, MAX_BRUSH_MASS);
+        eprintln!("Brush mass: {}", self.brush.mass);
+    }
+
+    /// Adds a body from the brush at the camera target, returning its index
+    pub fn spawn(&mut self) -> usize {
+        // Catching up first, so an orbit is worked out from where the bodies are now
+        self.sync_from_gpu();
+        let position = self.renderer.camera.target.to_vec().map(f64::from);
+        let body = self.brush.body(
+            position,
+            &self.simulation.entities,
+            self.simulation.gravitational_constant,
+        );
+        let index = self.add_body(body);
+        let position: [f64; 3] = position.into();
+        eprintln!(
+            "Spawned body {} with mass {} at {:?}",
+            index, self.brush.mass, position
+        );
+        index
+    }
+
+    /// Kicks every body straight away from their center of mass at explode_speed, along
+    /// with the comparison run's bodies, see physics::explode
+    pub fn explode(&mut self) {
+        // Catching the CPU up first, since the GPU's copy is replaced with it
+        self.sync_from_gpu();
+        let speed = f64::from(self.explode_speed);
+        let center = physics::center_of_mass(&self.simulation.entities);
+        physics::explode(&mut self.simulation.entities, center, speed);
+        if let Some(ghost) = self.ghost.as_mut() {
+            let center = physics::center_of_mass(&ghost.entities);
+            physics::explode(&mut ghost.entities, center, speed);
+        }
+        self.upload_gravity();
+        eprintln!("Exploded at {}", self.explode_speed);
+    }
+
+    /// Simulates `elapsed` seconds of real time, sped up by `self.time_scale`, in steps of
+    /// `self.dt`, so the simulation runs the same no matter the frame rate. With an adaptive
+    /// timestep the steps vary in length instead, and only the number taken each frame changes.
+    /// Time left over that doesn't fill a whole step is carried over to the next frame, unless
+    /// the frame hit the stepper's max_substeps_per_frame, see Stepper.
+    /// Returns how many steps were taken.
+    pub fn advance(&mut self, elapsed: f32) -> usize {
+        self.stepper
+            .begin_frame(f64::from(elapsed * self.time_scale));
+        loop {
+            let dt = self.simulation.next_dt();
+            if !self.stepper.next_step(dt) {
+                break;
+            }
+            self.step_physics(dt);
+        }
+        self.stepper.steps()
+    }
+
+    /// Moves the simulation forward by a single step of `dt`
+    fn step_physics(&mut self, dt: f64) {
+        // In lockstep with the simulation, so the two have always simulated the same time
+        if let Some(ghost) = self.ghost.as_mut() {
+            ghost.step(dt);
+        }
+        if let Some(compute) = self.gravity_compute.as_ref() {
+            // The bodies stay on the GPU, collisions and trails wait for the next sync
+            // The GPU steps in f32, while the simulated time is kept in f64
+            compute.step(&self.device, &self.queue, dt as f32);
+            self.simulation.time += dt;
+            return;
+        }
+        let merged = self.simulation.step(dt);
+        self.forget_merged(&merged);
+        self.record_trails();
+    }
+
+    /// Brings the simulation up to date with the bodies stepped on the GPU, merging any that
+    /// collided since the last sync. Returns false, doing nothing, when the bodies aren't
+    /// kept on the GPU
+    pub fn sync_from_gpu(&mut self) -> bool {
+        let compute = match self.gravity_compute.as_mut() {
+            Some(compute) => compute,
+            None => return false,
+        };
+        compute.download(&self.device, &self.queue, &mut self.simulation.entities);
+        let merged = self.simulation.merge_collisions();
+        // The steps taken on the GPU are only seen here, so only recorded once per sync
+        self.simulation.record_trajectory();
+        if !merged.is_empty() {
+            // Fewer bodies with new masses, so the GPU's copy is out of date
+            compute.upload(
+                &self.device,
+                &self.queue,
+                &self.simulation.entities,
+                self.simulation.gravitational_constant,
+                self.simulation.softening,
+                self.simulation.min_separation(),
+            );
+        }
+        self.frames_since_sync = 0;
+        self.forget_merged(&merged);
+        self.record_trails();
+        true
+    }
+
+    /// Recomputes the pull between the bodies after the bodies themselves, the softening or
+    /// the separation clamp changed
+    fn refresh_gravity(&mut self) {
+        // Catching the CPU up first, since the upload replaces what's on the GPU
+        self.sync_from_gpu();
+        self.upload_gravity();
+    }
+
+    /// Works out the pull between the bodies from the CPU's copy of them, replacing the
+    /// GPU's copy when they're kept there
+    fn upload_gravity(&mut self) {
+        // The energy is measured against different bodies or softening from here on
+        self.energy_history.clear();
+        match self.gravity_compute.as_mut() {
+            Some(compute) => compute.upload(
+                &self.device,
+                &self.queue,
+                &self.simulation.entities,
+                self.simulation.gravitational_constant,
+                self.simulation.softening,
+                self.simulation.min_separation(),
+            ),
+            None => self.simulation.refresh_accelerations(),
+        }
+    }
+
+    /// Adds `entity` to the simulation with an empty trail, returning its index.
+    /// It pulls on the other bodies and shows up in the instance buffer straight away
+    pub fn add_body(&mut self, entity: sphere::Entity) -> usize {
+        // Catching the CPU up first, since the GPU's copy is replaced with it
+        self.sync_from_gpu();
+        self.trails
+            .push(TrailBuffer::new(self.trail_length, entity.color));
+        self.simulation.entities.push(entity);
+        self.restart_ghost();
+        self.bodies_changed();
+        self.simulation.entities.len() - 1
+    }
+
+    /// Takes the body at `index` out of the simulation along with its trail, returning it.
+    /// The followed and selected bodies move down with the bodies after it, or are dropped
+    /// if it was one of them. Bodies kept on the GPU are read back first, so `index` counts
+    /// any merges that catching up with them causes
+    pub fn remove_body(&mut self, index: usize) -> Option<sphere::Entity> {
+        self.sync_from_gpu();
+        if index >= self.simulation.entities.len() {
+            return None;
+        }
+        let entity = self.simulation.entities.remove(index);
+        self.restart_ghost();
+        self.forget_merged(&[index]);
+        self.bodies_changed();
+        Some(entity)
+    }
+
+    /// Updates gravity and the instance buffer after bodies were added or removed.
+    /// The CPU's copy of the bodies has to be up to date, since it replaces the GPU's
+    fn bodies_changed(&mut self) {
+        self.upload_gravity();
+        self.renderer.write_instances(
+            &self.device,
+            &self.queue,
+            &self.simulation.entities,
+            self.selected,
+            0.0,
+        );
+    }
+
+    /// Drops the trails of the bodies at `merged`, the indices removed in the order they were
+    /// removed, and moves the followed and selected indices along with the bodies after them
+    fn forget_merged(&mut self, merged: &[usize]) {
+        for &index in merged {
+            if index < self.trails.len() {
+                self.trails.remove(index);
+            }
+            // The followed and selected bodies may be gone,
+            // and everything after the merged body moved down a place
+            let shift = |target: Option<usize>| match target {
+                Some(target) if target == index => None,
+                Some(target) if target > index => Some(target - 1),
+                target => target,
+            };
+            self.follow_target = shift(self.follow_target);
+            self.selected = shift(self.selected);
+        }
+    }
+
+    /// Adds each body's current position to its trail
+    fn record_trails(&mut self) {
+        let entities = &self.simulation.entities;
+        // Only out of step when starting over, since adding, removing and merging bodies
+        // keep the trails lined up with them
+        if self.trails.len() != entities.len() {
+            self.trails = entities
+                .iter()
+                .map(|entity| TrailBuffer::new(self.trail_length, entity.color))
+                .collect();
+        }
+        for (trail, entity) in self.trails.iter_mut().zip(entities) {
+            trail.push(entity.position.map(|x| x as f32));
+        }
+    }
+
+    /// Sets how many positions each trail holds, up to trail::MAX_TRAIL_LENGTH
+    pub fn set_trail_length(&mut self, length: usize) {
+        self.trail_length = length.min(trail::MAX_TRAIL_LENGTH);
+        for trail in &mut self.trails {
+            trail.set_length(self.trail_length);
+        }
+    }
+
+    /// Saves the bodies, the simulated time and the physics settings so the run can be resumed
+    pub fn save_snapshot(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.sync_from_gpu();
+        self.simulation.snapshot().save(path)
+    }
+
+    /// Replaces the simulation with one saved by save_snapshot
+    pub fn load_snapshot(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.restore(Snapshot::load(path)?);
+        Ok(())
+    }
+
+    /// Puts every body back where it was at launch, bringing back any that have merged
+    /// since, and sets the simulated time, time scale and pause back to how they started
+    pub fn reset(&mut self) {
+        self.restore(self.initial.clone());
+        self.time_scale = 1.0;
+        self.animations.physics = true;
+        self.step_requested = false;
+    }
+
+    /// Replaces the simulation with `snapshot`, rewriting everything kept alongside the bodies
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.simulation.restore(snapshot);
+        self.restart_ghost();
+        // Time that was waiting to be simulated belonged to the old run
+        self.stepper.clear();
+        if let Some(compute) = self.gravity_compute.as_mut() {
+            compute.upload(
+                &self.device,
+                &self.queue,
+                &self.simulation.entities,
+                self.simulation.gravitational_constant,
+                self.simulation.softening,
+                self.simulation.min_separation(),
+            );
+            self.frames_since_sync = 0;
+        }
+        // The bodies jumped, so their old trails no longer lead to them
+        self.trails.clear();
+        self.energy_history.clear();
+        // and the followed and selected indices may now be different bodies
+        self.follow_target = None;
+        self.selected = None;
+        self.renderer.write_instances(
+            &self.device,
+            &self.queue,
+            &self.simulation.entities,
+            self.selected,
+            0.0,
+        );
+    }
+
+    /// Advances the simulation and updates our camera position and light uniform
+    /// by however long the last frame took
     pub fn update(&mut self) {
+        let dt = self.frame_timer.tick();
+        self.update_by(dt);
+    }
+
+    /// Advances the simulation, camera and light by `dt` seconds of real time. Recording
+    /// calls this with the same `dt` every frame so the frames come out the same every run
+    pub fn update_by(&mut self, dt: f32) {
+        // The camera keeps moving and the scene keeps drawing while physics is paused
+        let stepped = if self.animations.physics {
+            self.advance(dt) > 0
+        } else if self.step_requested {
+            self.step_physics(self.simulation.next_dt());
+            true
+        } else {
+            false
+        };
+        self.step_requested = false;
+        if stepped && self.gravity_compute.is_none() {
+            let clamped = self.simulation.clamped_pairs;
+            // Only when clamping starts, rather than every frame it goes on
+            if clamped > 0 && self.clamped_pairs == 0 {
+                eprintln!(
+                    "{} pairs were clamped to the minimum separation, softening may be too small",
+                    clamped
+                );
+            }
+            self.clamped_pairs = clamped;
+        }
+        if stepped && self.gravity_compute.is_some() {
+            self.frames_since_sync += 1;
+            if self.frames_since_sync >= self.gpu_sync_interval {
+                self.sync_from_gpu();
+            }
+        }
+
+        // Only the HUD shows the energy, and a fresh history is filled in straight away
+        if self.renderer.drawn_passes().contains(render::PassFlags::UI) {
+            self.frames_since_energy += 1;
+            if self.frames_since_energy >= ENERGY_SAMPLE_INTERVAL || self.energy_history.is_empty()
+            {
+                self.record_energy();
+            }
+        }
+
+        self.follow();
         self.renderer
             .camera_controller
-            .update_camera(&mut self.renderer.camera);
+            .update_camera(&mut self.renderer.camera, dt);
+        // Before the instances are written, which places them relative to the camera
+        self.animations.animate(
+            &mut self.renderer.camera,
+            &mut self.renderer.light_uniform.position,
+            dt,
+        );
+        self.renderer.write_instances(
+            &self.device,
+            &self.queue,
+            &self.simulation.entities,
+            self.selected,
+            dt,
+        );
+        if let Some(compute) = self.gravity_compute.as_mut() {
+            // The bodies on the GPU are ahead of the CPU's copy between syncs
+            compute.place_instances(
+                &self.device,
+                &self.queue,
+                &self.renderer.instance_buffer,
+                &self.renderer.instance_order,
+                std::mem::size_of::<instance::InstanceRaw>(),
+                self.renderer.camera.render_origin(),
+            );
+        }
+        // Once the instances are written, which fits the clipping planes to where the
+        // bodies are now
         self.renderer
             .camera_uniform
             .update_view_proj(&self.renderer.camera);
@@ -130,22 +1234,43 @@ impl State {
             0,
             bytemuck::cast_slice(&[self.renderer.camera_uniform]),
         );
-        let old_position: cgmath::Vector3<_> = self.renderer.light_uniform.position.into();
-        self.renderer.light_uniform.position =
-            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
-                * old_position)
-                .into();
-        self.queue.write_buffer(
-            &self.renderer.light_buffer,
-            0,
-            bytemuck::cast_slice(&[self.renderer.light_uniform]),
-        );
+        if self.renderer.show_axes || self.renderer.show_grid {
+            self.renderer.write_guides(&self.queue);
+        }
+        if self.renderer.passes.contains(render::PassFlags::TRAILS) {
+            self.renderer
+                .write_trails(&self.device, &self.queue, &self.trails);
+        }
+        match &self.ghost {
+            Some(ghost) => self
+                .renderer
+                .write_ghosts(&self.device, &self.queue, &ghost.entities),
+            None => self.renderer.ghost_count = 0,
+        }
+        if self.renderer.show_hill_spheres {
+            self.renderer
+                .write_hill_spheres(&self.device, &self.queue, &self.simulation.entities);
+        }
+        // Glowing bodies move even when the orbiting light doesn't
+        self.renderer
+            .write_lights(&self.queue, &self.simulation.entities);
+    }
+
+    /// Swaps the shader the bodies are drawn with for `source`, which must be valid WGSL
+    /// with the same inputs as shader.wgsl
+    #[cfg(feature = "dev")]
+    pub fn set_body_shader(&mut self, source: &str) {
+        self.renderer
+            .set_body_shader(&self.device, self.config.format, source);
     }
 
     /// Calls all of the necessary rendering commands
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         // Store a surface texture to Render to
-        let output = self.surface.get_current_texture()?;
+        let output = match &self.surface {
+            Some(surface) => surface.get_current_texture()?,
+            None => return Ok(()),
+        };
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -157,24 +1282,91 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
+        self.draw(&mut encoder, &view);
+
+        // The HUD goes over the finished scene
+        if self.renderer.drawn_passes().contains(render::PassFlags::UI) {
+            self.draw_hud(&mut encoder, &view);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+
+    /// Records the commands that draw the HUD over whatever is already in `view`
+    fn draw_hud(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let stats = HudStats {
+            fps: self.frame_timer.fps(),
+            body_count: self.simulation.entities.len(),
+            drawn_count: self.renderer.instance_order.len(),
+            time_scale: self.time_scale,
+            slow_motion: self.stepper.slow_motion(),
+            paused: !self.animations.physics,
+            energy: self
+                .energy_history
+                .back()
+                .copied()
+                .unwrap_or_else(|| self.simulation.total_energy()),
+            energy_drift: self.energy_drift(),
+            momentum: self.simulation.total_momentum(),
+            softening: self.simulation.softening,
+            min_separation: self.simulation.min_separation(),
+            // Clamped pairs aren't counted on the GPU
+            clamped_pairs: self.gravity_compute.is_none().then_some(self.clamped_pairs),
+            color_scale: self
+                .renderer
+                .color_range
+                .map(|range| (self.renderer.color_scale, range)),
+            comparison: self
+                .ghost
+                .as_ref()
+                .map(|ghost| (ghost.integrator, ghost.divergence(&self.simulation))),
+        };
+        self.hud.draw(
+            &self.device,
+            &self.queue,
+            encoder,
+            view,
+            &self.config,
+            &stats,
+        );
+    }
+
+    /// Records the commands that draw the scene into `view` with the current camera,
+    /// going through bloom on the way if it's on
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let bloom = &self.renderer.bloom;
+        if self
+            .renderer
+            .drawn_passes()
+            .contains(render::PassFlags::BLOOM)
+        {
+            self.draw_scene(encoder, &bloom.scene.view);
+            bloom.apply(&self.queue, encoder, view);
+        } else {
+            self.draw_scene(encoder, view);
+        }
+    }
+
+    /// Records the commands that draw the scene into `view` with the current camera
+    fn draw_scene(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // Only the passes that are switched on and have something to draw bind a pipeline,
+        // see PassFlags::scene_passes
+        let passes = self.renderer.drawn_passes();
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             // Where we will draw our color to. In this case we will draw to view, our TextureView
             color_attachments: &[
                 // [[location(0)]] in our fragment shader
                 wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     // The texture to receive the output. Don't need to specify, so left a None
                     resolve_target: None,
                     // Telling wgpu what to do with the colors
                     ops: wgpu::Operations {
-                        // Loading the stored colors after clearing with a bluish color
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        // Loading the stored colors after clearing with the background's color
+                        load: wgpu::LoadOp::Clear(self.renderer.background.clear_color()),
                         // Store the results to the texture in TextureView
                         store: true,
                     },
@@ -183,7 +1375,7 @@ impl State {
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.renderer.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(self.renderer.camera.depth_clear_value()),
                     store: true,
                 }),
                 stencil_ops: None,
@@ -191,27 +1383,303 @@ impl State {
         });
 
         render_pass.set_vertex_buffer(1, self.renderer.instance_buffer.slice(..));
+        for pass in passes.scene_passes() {
+            match pass {
+                // The sky goes first, and doesn't touch the depth buffer, so everything
+                // covers it
+                render::ScenePass::Skybox => {
+                    render_pass.set_pipeline(&self.renderer.sky_render_pipeline);
+                    render_pass.set_bind_group(0, &self.renderer.camera_bind_group, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+                // The axes and grid are drawn like trails, as lines that don't hide what's
+                // behind them
+                render::ScenePass::Guides => {
+                    render_pass.set_pipeline(&self.renderer.trail_render_pipeline);
+                    render_pass.set_bind_group(0, &self.renderer.camera_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.renderer.guide_buffer.slice(..));
+                    if self.renderer.show_axes {
+                        render_pass.draw(0..render::AXIS_VERTEX_COUNT, 0..1);
+                    }
+                    if self.renderer.show_grid {
+                        let start = render::AXIS_VERTEX_COUNT;
+                        render_pass.draw(start..start + self.renderer.grid_vertex_count, 0..1);
+                    }
+                }
+                render::ScenePass::Light => {
+                    use crate::sphere::DrawLight;
+                    render_pass.set_pipeline(&self.renderer.light_render_pipeline);
+                    render_pass.draw_light_model(
+                        &self.renderer.spheres[0],
+                        &self.renderer.camera_bind_group,
+                        &self.renderer.light_bind_group,
+                    );
+                }
+                // One quad per instance, with no mesh, lights or textures
+                render::ScenePass::Bodies if self.renderer.style == render::RenderStyle::Points => {
+                    render_pass.set_pipeline(&self.renderer.point_render_pipeline);
+                    render_pass.set_bind_group(0, &self.renderer.camera_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.renderer.instance_buffer.slice(..));
+                    render_pass.draw(0..4, 0..self.renderer.instance_order.len() as u32);
+                }
+                render::ScenePass::Bodies => {
+                    for render::InstanceRange {
+                        category,
+                        lod,
+                        texture,
+                        range,
+                    } in &self.renderer.instance_ranges
+                    {
+                        render_pass.set_pipeline(self.renderer.body_pipeline(*category));
+                        render_pass.set_bind_group(
+                            2,
+                            &self.renderer.body_textures[*texture].bind_group,
+                            &[],
+                        );
+                        render_pass.draw_sphere_instanced(
+                            &self.renderer.spheres[*lod],
+                            range.clone(),
+                            &self.renderer.camera_bind_group,
+                            &self.renderer.light_bind_group,
+                        );
+                    }
+                }
+                // The comparison run goes over the bodies, blended so both can be seen
+                render::ScenePass::Ghosts => {
+                    let opacity = render::GHOST_OPACITY;
+                    render_pass.set_pipeline(&self.renderer.ghost_render_pipeline);
+                    render_pass.set_blend_constant(wgpu::Color {
+                        r: opacity,
+                        g: opacity,
+                        b: opacity,
+                        a: opacity,
+                    });
+                    render_pass.set_bind_group(2, &self.renderer.body_textures[0].bind_group, &[]);
+                    render_pass.set_vertex_buffer(1, self.renderer.ghost_buffer.slice(..));
+                    render_pass.draw_sphere_instanced(
+                        &self.renderer.spheres[render::LOD_LEVELS - 1],
+                        0..self.renderer.ghost_count,
+                        &self.renderer.camera_bind_group,
+                        &self.renderer.light_bind_group,
+                    );
+                }
+                // The Hill spheres are wireframes, so the bodies inside them still show
+                // through
+                render::ScenePass::HillSpheres => {
+                    render_pass.set_pipeline(&self.renderer.wireframe_no_cull_render_pipeline);
+                    render_pass.set_bind_group(2, &self.renderer.body_textures[0].bind_group, &[]);
+                    render_pass.set_vertex_buffer(1, self.renderer.hill_sphere_buffer.slice(..));
+                    render_pass.draw_sphere_instanced(
+                        &self.renderer.spheres[render::LOD_LEVELS - 1],
+                        0..self.renderer.hill_sphere_count,
+                        &self.renderer.camera_bind_group,
+                        &self.renderer.light_bind_group,
+                    );
+                }
+                // Trails go after the bodies so they blend over them and can be hidden
+                // behind them
+                render::ScenePass::Trails => {
+                    render_pass.set_pipeline(&self.renderer.trail_render_pipeline);
+                    render_pass.set_bind_group(0, &self.renderer.camera_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.renderer.trail_buffer.slice(..));
+                    render_pass.draw(0..self.renderer.trail_vertex_count, 0..1);
+                }
+            }
+        }
+    }
 
-        use crate::sphere::DrawLight;
-        render_pass.set_pipeline(&self.renderer.light_render_pipeline);
-        render_pass.draw_light_model(
-            &self.renderer.sphere,
-            &self.renderer.camera_bind_group,
-            &self.renderer.light_bind_group,
-        );
+    /// Draws the current scene into an offscreen texture the size of the window
+    /// and saves it as a PNG
+    pub fn capture_screenshot(&self, path: &Path) -> anyhow::Result<()> {
+        self.capture_frame()?
+            .save(path)
+            .with_context(|| format!("Failed to save screenshot {}", path.display()))
+    }
 
-        render_pass.set_pipeline(&self.renderer.render_pipeline);
-        render_pass.draw_sphere(
-            &self.renderer.sphere,
-            &self.renderer.camera_bind_group,
-            &self.renderer.light_bind_group,
-        );
+    /// Draws the current scene into an offscreen texture the size of the window, or the
+    /// size set by set_render_size, and reads it back
+    pub fn capture_frame(&self) -> anyhow::Result<image::RgbaImage> {
+        let (width, height) = (self.config.width, self.config.height);
+        // BGRA surfaces are common, and need their red and blue swapped to be saved as RGBA
+        let swap_red_blue = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => anyhow::bail!("Can't capture a {:?} surface", format),
+        };
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        // Releasing the borrow on 'encoder'
-        drop(render_pass);
+        // Rows copied out of a texture have to start on a 256 byte boundary,
+        // so each row is padded out and the padding is stripped afterwards
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        self.draw(&mut encoder, &view);
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+            },
+            size,
+        );
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        buffer.unmap();
+        if swap_red_blue {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .context("Capture buffer is the wrong size")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Preset;
+    use cgmath::{Deg, InnerSpace};
+
+    #[test]
+    fn camera_path_runs_while_physics_is_paused() {
+        let animations = Animations {
+            physics: false,
+            light: false,
+            camera_path: true,
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(&Scene::preset(Preset::FigureEight), DEFAULT_DT);
+        let bodies = simulation.entities.clone();
+        let mut camera = camera::Camera::new(&wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 800,
+            height: 600,
+            present_mode: wgpu::PresentMode::Fifo,
+        });
+        let start = camera.eye;
+        let mut light = [1.0, 2.0, 3.0];
+
+        let (frames, dt) = (30, 0.1_f32);
+        for _ in 0..frames {
+            // Each frame of State::update_by, which only steps the bodies with physics on
+            if animations.physics {
+                simulation.step(f64::from(dt));
+            }
+            animations.animate(&mut camera, &mut light, dt);
+        }
+
+        for (body, before) in simulation.entities.iter().zip(&bodies) {
+            assert_eq!(body.position, before.position);
+            assert_eq!(body.velocity, before.velocity);
+        }
+        assert_eq!(light, [1.0, 2.0, 3.0]);
+        // Circled a third of the way to a right angle, at the same distance and height
+        let (from, to) = (start - camera.target, camera.eye - camera.target);
+        // Measured about the up axis, since the camera starts out above the target, and
+        // seen from above, where -z is up the screen
+        let flat = |offset: cgmath::Vector3<f32>| cgmath::Vector2::new(offset.x, -offset.z);
+        let angle = Deg::from(flat(from).angle(flat(to)));
+        let expected = DEFAULT_CAMERA_PATH_SPEED * dt * frames as f32;
+        assert!((angle.0 - expected).abs() < 1e-3, "circled {:?}", angle);
+        assert!((to.magnitude() - from.magnitude()).abs() < 1e-5);
+        assert!((camera.eye.y - start.y).abs() < 1e-5);
+    }
+
+    #[test]
+    fn screenshot_of_one_star_is_window_sized() {
+        let (device, queue) = match pollster::block_on(gpu::headless()) {
+            Some(device) => device,
+            None => {
+                eprintln!("no adapter, skipping");
+                return;
+            }
+        };
+        let scene = Scene {
+            bodies: vec![scene::BodyConfig::new(
+                sphere::BodyType::Star,
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 0.0),
+            )],
+            background: Some([0.0, 0.0, 0.0]),
+            ..Default::default()
+        };
+        let simulation = Simulation::new(&scene, DEFAULT_DT);
+        // Not a multiple of 64 pixels wide, so each row read back is padded
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 100,
+            height: 60,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let mut state =
+            State::with_device(None, device, queue, config, 1.0, &scene, simulation).unwrap();
+        state.renderer.camera.frame(
+            cgmath::Point3::origin(),
+            sphere::BodyType::Star.radius() * 2.0,
+        );
+        state.update_by(0.0);
+
+        let image = state.capture_frame().unwrap();
+        assert_eq!(image.dimensions(), (100, 60));
+        // The star glows in the middle of the black background
+        let center = image.get_pixel(50, 30);
+        assert!(
+            center.0[..3].iter().any(|&channel| channel > 0),
+            "{:?}",
+            center
+        );
     }
 }
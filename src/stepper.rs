@@ -0,0 +1,95 @@
+/// The fraction of a step the accumulator can be short by and still take the step
+const STEP_EPSILON: f64 = 1e-4;
+/// The most physics steps a frame takes unless told otherwise
+pub const DEFAULT_MAX_SUBSTEPS_PER_FRAME: usize = 256;
+
+/// Cuts the simulated time that passes each frame into whole physics steps, so the
+/// simulation runs the same no matter the frame rate. Time that doesn't fill a whole step
+/// is carried over to the next frame.
+///
+/// A frame takes at most max_substeps_per_frame steps. One that needs more drops the rest
+/// of its time instead of piling it onto the next frame, which would then need even more,
+/// so the simulation falls into slow motion rather than spiralling until it freezes.
+/// Each frame calls begin_frame, then takes steps for as long as next_step says to
+#[derive(Debug, Clone)]
+pub struct Stepper {
+    /// Simulated time that has passed but hasn't been stepped through yet
+    pub accumulator: f64,
+    /// The most steps a single frame can take
+    pub max_substeps_per_frame: usize,
+    /// How many steps the current frame has taken
+    steps: usize,
+    /// Whether the last frame to finish ran out of steps before it ran out of time
+    slow_motion: bool,
+}
+
+impl Stepper {
+    /// A stepper with no time waiting, taking at most `max_substeps_per_frame` steps a frame
+    pub fn new(max_substeps_per_frame: usize) -> Self {
+        Self {
+            accumulator: 0.0,
+            max_substeps_per_frame,
+            steps: 0,
+            slow_motion: false,
+        }
+    }
+
+    /// Starts a frame with `elapsed` more seconds of simulated time to step through
+    pub fn begin_frame(&mut self, elapsed: f64) {
+        self.accumulator += elapsed;
+        self.steps = 0;
+    }
+
+    /// Whether to take another step of `dt` this frame, taking its time off the accumulator
+    /// if so. Returns false once the frame's time is used up, or once the frame has taken
+    /// max_substeps_per_frame steps, dropping the time that's left and logging a warning
+    /// if it wasn't already in slow motion
+    pub fn next_step(&mut self, dt: f64) -> bool {
+        // A little slack so rounding in the accumulator doesn't drop a step
+        if self.accumulator < dt * (1.0 - STEP_EPSILON) {
+            if self.slow_motion {
+                log::info!("Physics caught up, back to full speed");
+                self.slow_motion = false;
+            }
+            return false;
+        }
+        if self.steps >= self.max_substeps_per_frame {
+            if !self.slow_motion {
+                log::warn!(
+                    "Physics needs more than {} steps a frame to keep up, running in slow \
+                     motion. Lower the time scale or lengthen the step to get back to speed",
+                    self.max_substeps_per_frame
+                );
+                self.slow_motion = true;
+            }
+            self.accumulator = 0.0;
+            return false;
+        }
+        self.accumulator -= dt;
+        self.steps += 1;
+        true
+    }
+
+    /// How many steps the current frame has taken
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Whether the simulation is running slower than asked because frames can't take enough
+    /// steps to keep up
+    pub fn slow_motion(&self) -> bool {
+        self.slow_motion
+    }
+
+    /// Drops any time waiting to be simulated, e.g. when the simulation starts over
+    pub fn clear(&mut self) {
+        self.accumulator = 0.0;
+        self.slow_motion = false;
+    }
+}
+
+impl Default for Stepper {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SUBSTEPS_PER_FRAME)
+    }
+}
@@ -2,8 +2,8 @@ use image::GenericImageView;
 use anyhow::*;
 use std::path::Path;
 
+/// A texture's view and sampler. The view keeps the texture itself alive
 pub struct Texture {
-    pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
 }
@@ -47,16 +47,59 @@ impl Texture {
                 ..Default::default()
             }
         );
-        Self { texture, view, sampler }
+        Self { view, sampler }
     }
 
-    pub fn from_bytes(
+    /// A texture of the given size that can be drawn into and then sampled from,
+    /// for passes that work on the output of an earlier pass
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            // A minimized window has no size, but textures need at least one texel
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            }
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+        Self { view, sampler }
+    }
+
+    /// A single pixel of `rgba`, for things that need a texture but don't have one
+    pub fn from_color(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        bytes: &[u8],
+        rgba: [u8; 4],
         label: &str
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)));
         Self::from_image(device, queue, &img, Some(label))
     }
 
@@ -115,7 +158,7 @@ impl Texture {
             }
         );
 
-        Ok(Self { texture, view, sampler })
+        Ok(Self { view, sampler })
     }
 
     pub fn load<P: AsRef<Path>>(
@@ -0,0 +1,129 @@
+use crate::sphere::Vertex;
+use cgmath::Vector3;
+use std::collections::VecDeque;
+
+/// The longest a trail can be, so memory stays bounded however many bodies there are
+pub const MAX_TRAIL_LENGTH: usize = 1024;
+/// How many positions a trail holds unless told otherwise
+pub const DEFAULT_TRAIL_LENGTH: usize = 256;
+
+/// The most recent positions of a single body, oldest first, and the color it's drawn in
+#[derive(Debug, Clone)]
+pub struct TrailBuffer {
+    positions: VecDeque<Vector3<f32>>,
+    length: usize,
+    color: [f32; 3],
+}
+
+impl TrailBuffer {
+    /// An empty trail that holds up to `length` positions, drawn in `color`
+    pub fn new(length: usize, color: [f32; 3]) -> Self {
+        let length = length.min(MAX_TRAIL_LENGTH);
+        Self {
+            positions: VecDeque::with_capacity(length),
+            length,
+            color,
+        }
+    }
+
+    /// Adds the body's newest position, dropping the oldest once the trail is full
+    pub fn push(&mut self, position: Vector3<f32>) {
+        if self.length == 0 {
+            return;
+        }
+        if self.positions.len() == self.length {
+            self.positions.pop_front();
+        }
+        self.positions.push_back(position);
+    }
+
+    /// Changes how many positions the trail holds, dropping the oldest if it shrinks
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length.min(MAX_TRAIL_LENGTH);
+        while self.positions.len() > self.length {
+            self.positions.pop_front();
+        }
+    }
+
+    /// The positions in the trail, oldest first
+    pub fn positions(&self) -> impl Iterator<Item = &Vector3<f32>> {
+        self.positions.iter()
+    }
+
+    /// How many positions the trail currently holds
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+/// A point on a colored line. Used for the trails, and by anything else drawn as plain lines
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TrailVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl TrailVertex {
+    pub fn new(position: [f32; 3], color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+
+    /// The same vertex moved by `offset`
+    pub fn translated(&self, offset: Vector3<f32>) -> Self {
+        let [x, y, z] = self.position;
+        Self {
+            position: [x + offset.x, y + offset.y, z + offset.z],
+            color: self.color,
+        }
+    }
+}
+
+impl Vertex for TrailVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<TrailVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Turns trails into a line list, one segment between each pair of neighbouring positions.
+/// Each trail is drawn in its own color, fading from opaque at the body to
+/// fully transparent at the oldest position. Positions are given relative to `origin`.
+pub fn trail_vertices<'a>(
+    trails: impl IntoIterator<Item = &'a TrailBuffer>,
+    origin: Vector3<f32>,
+) -> Vec<TrailVertex> {
+    let mut vertices = Vec::new();
+    for trail in trails {
+        let [r, g, b] = trail.color;
+        let count = trail.len();
+        if count < 2 {
+            continue;
+        }
+        let vertex = |i: usize, position: &Vector3<f32>| TrailVertex {
+            position: (position - origin).into(),
+            color: [r, g, b, i as f32 / (count - 1) as f32],
+        };
+        let positions = trail.positions().enumerate().collect::<Vec<_>>();
+        for pair in positions.windows(2) {
+            vertices.push(vertex(pair[0].0, pair[0].1));
+            vertices.push(vertex(pair[1].0, pair[1].1));
+        }
+    }
+    vertices
+}
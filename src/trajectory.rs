@@ -0,0 +1,200 @@
+use crate::snapshot::Snapshot;
+use crate::sphere::Entity;
+use anyhow::*;
+use cgmath::Vector3;
+use std::io::Write;
+use std::path::Path;
+
+/// The state of one recorded body at one point in time
+#[derive(Debug, Copy, Clone)]
+pub struct Sample {
+    /// How much simulated time had passed
+    pub time: f64,
+    /// Where the body was
+    pub position: Vector3<f64>,
+    /// How fast and which way the body was moving
+    pub velocity: Vector3<f64>,
+}
+
+/// Records the full trajectory of a few chosen bodies over a run,
+/// so their orbits can be plotted with external tools afterwards
+pub struct TrajectoryRecorder {
+    /// Which bodies to record, by index
+    bodies: Vec<usize>,
+    /// Only every nth step is recorded
+    interval: usize,
+    /// Whether velocities are written out along with positions
+    pub record_velocity: bool,
+    steps_seen: usize,
+    /// One time series per recorded body, in the same order as `bodies`
+    trajectories: Vec<Vec<Sample>>,
+}
+
+impl TrajectoryRecorder {
+    /// Records the bodies at the given indices every `interval` steps
+    pub fn new(bodies: Vec<usize>, interval: usize) -> Self {
+        let trajectories = vec![Vec::new(); bodies.len()];
+        Self {
+            bodies,
+            interval: interval.max(1),
+            record_velocity: false,
+            steps_seen: 0,
+            trajectories,
+        }
+    }
+
+    /// Call once per simulation step. Bodies that no longer exist are skipped
+    pub fn record(&mut self, time: f64, entities: &[Entity]) {
+        let sample_this_step = self.steps_seen.is_multiple_of(self.interval);
+        self.steps_seen += 1;
+        if !sample_this_step {
+            return;
+        }
+
+        for (trajectory, &index) in self.trajectories.iter_mut().zip(&self.bodies) {
+            if let Some(entity) = entities.get(index) {
+                trajectory.push(Sample {
+                    time,
+                    position: entity.position,
+                    velocity: entity.velocity,
+                });
+            }
+        }
+    }
+
+    /// The samples recorded so far for the body at `index`
+    pub fn trajectory(&self, index: usize) -> Option<&[Sample]> {
+        let slot = self.bodies.iter().position(|&body| body == index)?;
+        Some(&self.trajectories[slot])
+    }
+
+    /// Writes every recorded sample to `path`, as NPY if it ends in .npy and CSV otherwise
+    pub fn save(&self, path: &Path) -> Result<()> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("npy") => self.finalize_npy(path),
+            _ => self.finalize(path),
+        }
+    }
+
+    /// The columns finalize and finalize_npy write, in order
+    pub fn columns(&self) -> &'static [&'static str] {
+        if self.record_velocity {
+            &["time", "body", "x", "y", "z", "vx", "vy", "vz"]
+        } else {
+            &["time", "body", "x", "y", "z"]
+        }
+    }
+
+    /// Every recorded sample as a row of `columns`, body by body
+    fn rows(&self) -> impl Iterator<Item = Vec<f64>> + '_ {
+        self.trajectories
+            .iter()
+            .zip(&self.bodies)
+            .flat_map(move |(trajectory, &index)| {
+                trajectory.iter().map(move |sample| {
+                    let (p, v) = (sample.position, sample.velocity);
+                    let mut row = vec![sample.time, index as f64, p.x, p.y, p.z];
+                    if self.record_velocity {
+                        row.extend([v.x, v.y, v.z]);
+                    }
+                    row
+                })
+            })
+    }
+
+    /// Writes every recorded sample to `path` as a NumPy array of f64 with a row per sample
+    /// and the same columns as the CSV, ready for numpy.load
+    pub fn finalize_npy(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Couldn't create {}", path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        let rows = self.rows().collect::<Vec<_>>();
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+            rows.len(),
+            self.columns().len()
+        );
+        // The magic string, version and header length take 10 bytes, and the header is
+        // padded with spaces so the data starts on a 64 byte boundary
+        let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for value in rows.iter().flatten() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes every recorded sample to a CSV file at `path`
+    pub fn finalize(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Couldn't create {}", path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        writeln!(writer, "{}", self.columns().join(","))?;
+        for (trajectory, index) in self.trajectories.iter().zip(&self.bodies) {
+            for sample in trajectory {
+                let p = sample.position;
+                write!(writer, "{},{},{},{},{}", sample.time, index, p.x, p.y, p.z)?;
+                if self.record_velocity {
+                    let v = sample.velocity;
+                    write!(writer, ",{},{},{}", v.x, v.y, v.z)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes the position and velocity of every body to a CSV file a row at a time,
+/// so long runs can be written out as they go instead of being held in memory
+pub struct CsvWriter {
+    writer: std::io::BufWriter<std::fs::File>,
+}
+
+impl CsvWriter {
+    /// Creates the file at `path` and writes the header
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Couldn't create {}", path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        writeln!(writer, "time,body_id,x,y,z,vx,vy,vz")?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a row for each body at `time`, numbered by their index
+    pub fn write_bodies(&mut self, time: f64, bodies: &[Entity]) -> Result<()> {
+        for (id, body) in bodies.iter().enumerate() {
+            let (p, v) = (body.position, body.velocity);
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{}",
+                time, id, p.x, p.y, p.z, v.x, v.y, v.z
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Flushes everything written to the file
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes every body in every snapshot to a CSV file with the columns
+/// time, body_id, x, y, z, vx, vy, vz
+pub fn export_csv(snapshots: &[Snapshot], path: &Path) -> Result<()> {
+    let mut writer = CsvWriter::create(path)?;
+    for snapshot in snapshots {
+        writer.write_bodies(snapshot.time, &snapshot.bodies)?;
+    }
+    writer.finish()
+}
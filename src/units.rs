@@ -0,0 +1,92 @@
+/// The gravitational constant in SI units, m^3 / (kg s^2)
+pub const G_SI: f64 = 6.674_30e-11;
+
+const METERS_PER_AU: f64 = 1.495_978_707e11;
+const METERS_PER_KILOPARSEC: f64 = 3.085_677_581e19;
+const KILOGRAMS_PER_SOLAR_MASS: f64 = 1.988_47e30;
+/// A Julian year
+const SECONDS_PER_YEAR: f64 = 3.155_76e7;
+
+/// The units positions, masses and times are measured in.
+/// Picking one sets the value of G, so orbits come out right without hand-tuned scale factors.
+#[derive(Debug, Copy, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum UnitSystem {
+    /// Dimensionless units where G = 1
+    #[default]
+    Natural,
+    /// Meters, kilograms and seconds
+    Si,
+    /// Astronomical units, solar masses and years, where G is about 4 pi^2
+    Astronomical,
+    /// Kiloparsecs, solar masses and megayears, for galaxy scale scenes
+    Galactic,
+}
+
+impl UnitSystem {
+    /// The size of this system's units of (length, mass, time) in meters, kilograms and seconds.
+    /// Natural units have no fixed size, so they return None.
+    pub fn scales(&self) -> Option<(f64, f64, f64)> {
+        match self {
+            UnitSystem::Natural => None,
+            UnitSystem::Si => Some((1.0, 1.0, 1.0)),
+            UnitSystem::Astronomical => {
+                Some((METERS_PER_AU, KILOGRAMS_PER_SOLAR_MASS, SECONDS_PER_YEAR))
+            }
+            UnitSystem::Galactic => Some((
+                METERS_PER_KILOPARSEC,
+                KILOGRAMS_PER_SOLAR_MASS,
+                SECONDS_PER_YEAR * 1.0e6,
+            )),
+        }
+    }
+
+    /// The gravitational constant expressed in this system's units
+    pub fn gravitational_constant(&self) -> f32 {
+        match self.scales() {
+            None => 1.0,
+            Some((length, mass, time)) => (G_SI * mass * time * time / length.powi(3)) as f32,
+        }
+    }
+
+    /// Converts a length in this system's units to meters
+    pub fn length_to_si(&self, length: f32) -> Option<f64> {
+        Some(length as f64 * self.scales()?.0)
+    }
+
+    /// Converts a length in meters to this system's units
+    pub fn length_from_si(&self, meters: f64) -> Option<f32> {
+        Some((meters / self.scales()?.0) as f32)
+    }
+
+    /// Converts a mass in this system's units to kilograms
+    pub fn mass_to_si(&self, mass: f32) -> Option<f64> {
+        Some(mass as f64 * self.scales()?.1)
+    }
+
+    /// Converts a mass in kilograms to this system's units
+    pub fn mass_from_si(&self, kilograms: f64) -> Option<f32> {
+        Some((kilograms / self.scales()?.1) as f32)
+    }
+
+    /// Converts a time in this system's units to seconds
+    pub fn time_to_si(&self, time: f32) -> Option<f64> {
+        Some(time as f64 * self.scales()?.2)
+    }
+
+    /// Converts a time in seconds to this system's units
+    pub fn time_from_si(&self, seconds: f64) -> Option<f32> {
+        Some((seconds / self.scales()?.2) as f32)
+    }
+
+    /// Converts a speed in this system's units to meters per second
+    pub fn speed_to_si(&self, speed: f32) -> Option<f64> {
+        let (length, _, time) = self.scales()?;
+        Some(speed as f64 * length / time)
+    }
+
+    /// Converts a speed in meters per second to this system's units
+    pub fn speed_from_si(&self, meters_per_second: f64) -> Option<f32> {
+        let (length, _, time) = self.scales()?;
+        Some((meters_per_second * time / length) as f32)
+    }
+}
@@ -0,0 +1,66 @@
+//! The adaptive timestep shortens the steps as bodies get closer and pull harder.
+
+use cgmath::*;
+use nbodysim::integrator::AdaptiveTimestep;
+use nbodysim::scene::{BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+
+const MASS: f32 = 1000.0;
+
+/// Two heavy planets `separation` apart, at rest
+fn pair(separation: f32) -> Simulation {
+    let body = |x: f32| {
+        let mut body =
+            BodyConfig::new(BodyType::Planet, Vector3::new(x, 0.0, 0.0), Vector3::zero());
+        body.mass = MASS;
+        body
+    };
+    let scene = Scene {
+        bodies: vec![body(-separation / 2.0), body(separation / 2.0)],
+        ..Default::default()
+    };
+    let mut simulation = Simulation::new(&scene, AdaptiveTimestep::default().max_dt);
+    simulation.adaptive_timestep = Some(AdaptiveTimestep::default());
+    simulation
+}
+
+#[test]
+fn closer_bodies_get_shorter_steps() {
+    let dts: Vec<f64> = [10.0, 5.0, 2.0, 1.0]
+        .iter()
+        .map(|&separation| pair(separation).next_dt())
+        .collect();
+    let adaptive = AdaptiveTimestep::default();
+    for window in dts.windows(2) {
+        assert!(window[1] < window[0], "{:?}", dts);
+    }
+    // None of them hit the limits, which would stop them shrinking
+    assert!(
+        dts[0] < adaptive.max_dt && dts[3] > adaptive.min_dt,
+        "{:?}",
+        dts
+    );
+}
+
+#[test]
+fn steps_shrink_as_bodies_fall_together() {
+    let mut simulation = pair(10.0);
+    let separation = |simulation: &Simulation| {
+        (simulation.entities[1].position - simulation.entities[0].position).magnitude()
+    };
+    let first = simulation.next_dt();
+    let mut previous = first;
+    while separation(&simulation) > 2.0 {
+        simulation.step(previous);
+        let dt = simulation.next_dt();
+        assert!(dt <= previous, "grew from {} to {}", previous, dt);
+        previous = dt;
+    }
+    assert!(
+        previous < first / 2.0,
+        "only shrank from {} to {}",
+        first,
+        previous
+    );
+}
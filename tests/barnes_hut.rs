@@ -0,0 +1,73 @@
+//! Barnes-Hut gravity against summing every pair: exact with theta at 0, and close with the
+//! usual opening angles.
+
+use cgmath::*;
+use nbodysim::octree::Octree;
+use nbodysim::physics::{self, GravityStrategy};
+use nbodysim::sphere::{BodyType, Entity};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const G: f32 = 1.0;
+const SOFTENING: f32 = 0.05;
+
+/// `n` bodies of mixed masses scattered through a cube `size` across
+fn cloud(n: usize, size: f64, seed: u64) -> Vec<Entity> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            let mut coordinate = || rng.gen_range(-size / 2.0..size / 2.0);
+            let position = Vector3::new(coordinate(), coordinate(), coordinate());
+            let mut body = Entity::new(BodyType::Planet, position);
+            body.mass = rng.gen_range(0.1..10.0);
+            body
+        })
+        .collect()
+}
+
+/// The furthest any body's Barnes-Hut acceleration is from summing every pair, as a
+/// fraction of the size of the exact one
+fn worst_error(bodies: &[Entity], theta: f32) -> f64 {
+    let exact = physics::compute_gravity(bodies, G, SOFTENING, GravityStrategy::BruteForce);
+    let approximate =
+        physics::compute_gravity(bodies, G, SOFTENING, GravityStrategy::BarnesHut { theta });
+    exact
+        .iter()
+        .zip(&approximate)
+        .map(|(exact, approximate)| (approximate - exact).magnitude() / exact.magnitude())
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn zero_theta_matches_brute_force() {
+    let bodies = cloud(300, 20.0, 1);
+    // Only the order the pulls are added in differs
+    let error = worst_error(&bodies, 0.0);
+    assert!(error < 1e-10, "off by {}", error);
+}
+
+#[test]
+fn fully_opened_octree_matches_brute_force_at_each_body() {
+    let bodies = cloud(50, 10.0, 3);
+    let exact = physics::compute_gravity(&bodies, G, SOFTENING, GravityStrategy::BruteForce);
+    let octree = Octree::new(&bodies);
+    let softening2 = f64::from(SOFTENING).powi(2);
+    for (body, exact) in bodies.iter().zip(&exact) {
+        let (acceleration, clamped) =
+            octree.acceleration(body.position, 0.0, f64::from(G), softening2, 0.0);
+        assert_eq!(clamped, 0);
+        let error = (acceleration - exact).magnitude() / exact.magnitude();
+        assert!(error < 1e-10, "off by {}", error);
+    }
+}
+
+#[test]
+fn usual_theta_is_close_to_brute_force() {
+    let bodies = cloud(1000, 20.0, 2);
+    // The worst body is one near the middle, where pulls from every side mostly cancel and
+    // a small error is large next to what's left. Most are off by far less
+    for &(theta, tolerance) in &[(0.3, 0.03), (0.5, 0.1)] {
+        let error = worst_error(&bodies, theta);
+        assert!(error < tolerance, "theta {} was off by {}", theta, error);
+    }
+}
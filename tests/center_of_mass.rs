@@ -0,0 +1,32 @@
+//! The center of mass of simple pairs, and taking out net momentum without changing how the
+//! bodies move relative to each other.
+
+mod common;
+
+use cgmath::*;
+use common::body;
+use nbodysim::physics;
+
+#[test]
+fn symmetric_pair_is_centered_on_its_midpoint() {
+    let (a, b) = (Vector3::new(1.0, -2.0, 3.0), Vector3::new(5.0, 4.0, -1.0));
+    let pair = [body(2.0, a, Vector3::zero()), body(2.0, b, Vector3::zero())];
+    assert_eq!(physics::center_of_mass(&pair), (a + b) / 2.0);
+
+    // Three times the mass pulls the center three quarters of the way over
+    let uneven = [body(1.0, a, Vector3::zero()), body(3.0, b, Vector3::zero())];
+    assert_eq!(physics::center_of_mass(&uneven), a + (b - a) * 0.75);
+}
+
+#[test]
+fn removing_net_momentum_keeps_relative_motion() {
+    let mut bodies = vec![
+        body(1.0, Vector3::zero(), Vector3::new(1.0, 2.0, 0.0)),
+        body(3.0, Vector3::unit_x(), Vector3::new(3.0, 0.0, -1.0)),
+        body(0.5, Vector3::unit_y(), Vector3::new(-2.0, 1.0, 4.0)),
+    ];
+    let relative = bodies[1].velocity - bodies[0].velocity;
+    physics::remove_net_momentum(&mut bodies);
+    assert!(physics::momentum(&bodies).magnitude() < 1e-12);
+    assert!((bodies[1].velocity - bodies[0].velocity - relative).magnitude() < 1e-12);
+}
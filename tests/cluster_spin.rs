@@ -0,0 +1,59 @@
+//! Random clouds given a target angular momentum end up spinning with it.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::scene::{CloudParams, CloudShape, Scene};
+use nbodysim::sphere::Entity;
+
+/// The total angular momentum of `scene`'s bodies about their center of mass
+fn angular_momentum(scene: &Scene) -> Vector3<f64> {
+    let bodies = scene
+        .bodies
+        .iter()
+        .map(Entity::from_config)
+        .collect::<Vec<_>>();
+    physics::angular_momentum(&bodies)
+}
+
+#[test]
+fn cloud_spins_with_the_requested_angular_momentum() {
+    let targets = [
+        Vector3::new(0.0, 500.0, 0.0),
+        Vector3::new(120.0, -40.0, 300.0),
+        Vector3::zero(),
+    ];
+    for shape in [CloudShape::Sphere, CloudShape::Disk] {
+        for &target in &targets {
+            let params = CloudParams {
+                shape,
+                angular_momentum: Some(target),
+                ..Default::default()
+            };
+            let scene = Scene::random_cloud(200, 7, params);
+            let error = (angular_momentum(&scene) - target.map(f64::from)).magnitude();
+            // The velocities are stored in f32, so only that close
+            assert!(
+                error < 1e-3 * (1.0 + f64::from(target.magnitude())),
+                "{:?} cloud wanted {:?}, off by {}",
+                shape,
+                target,
+                error
+            );
+        }
+    }
+}
+
+#[test]
+fn spinning_up_keeps_the_cloud_from_drifting() {
+    let params = CloudParams {
+        angular_momentum: Some(Vector3::new(0.0, 800.0, 0.0)),
+        ..Default::default()
+    };
+    let scene = Scene::random_cloud(100, 3, params);
+    let bodies = scene
+        .bodies
+        .iter()
+        .map(Entity::from_config)
+        .collect::<Vec<_>>();
+    assert!(physics::momentum(&bodies).magnitude() < 1e-3);
+}
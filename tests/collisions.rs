@@ -0,0 +1,143 @@
+//! Bodies that touch merge into one, keeping their combined mass, momentum and volume, and
+//! pass through each other with merging off.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::scene::{BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+use nbodysim::units::UnitSystem;
+
+const DT: f64 = 1.0 / 120.0;
+
+/// Bodies given as (position, velocity, mass, radius), with no softening
+fn scene(bodies: &[(Vector3<f32>, Vector3<f32>, f32, f32)]) -> Scene {
+    Scene {
+        bodies: bodies
+            .iter()
+            .map(|&(position, velocity, mass, radius)| {
+                let mut config = BodyConfig::new(BodyType::Planet, position, velocity);
+                config.mass = mass;
+                config.radius = radius;
+                config
+            })
+            .collect(),
+        units: UnitSystem::Natural,
+        softening: Some(0.0),
+        ..Default::default()
+    }
+}
+
+/// A heavy body and two lighter ones, all touching and moving in different directions,
+/// so it takes two merges, one per call, to untangle
+fn overlapping() -> Scene {
+    scene(&[
+        (Vector3::zero(), Vector3::new(1.0, 0.0, 0.0), 10.0, 1.0),
+        (
+            Vector3::new(1.5, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            2.0,
+            0.6,
+        ),
+        (
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -3.0),
+            1.0,
+            0.3,
+        ),
+    ])
+}
+
+fn total_mass(simulation: &Simulation) -> f32 {
+    simulation.entities.iter().map(|body| body.mass).sum()
+}
+
+#[test]
+fn merging_keeps_mass_and_momentum() {
+    let mut simulation = Simulation::new(&overlapping(), DT);
+    let mass = total_mass(&simulation);
+    let momentum = simulation.total_momentum();
+    let center = physics::center_of_mass(&simulation.entities);
+    let volume: f32 = simulation
+        .entities
+        .iter()
+        .map(|body| body.radius.powi(3))
+        .sum();
+
+    // The heavy body can only merge with one of the others at a time
+    assert_eq!(simulation.merge_collisions(), vec![1]);
+    assert_eq!(simulation.entities.len(), 2);
+    assert_eq!(total_mass(&simulation), mass);
+    assert!((simulation.total_momentum() - momentum).magnitude() < 1e-9);
+
+    // The merged body still overlaps the last one, so they merge on the next call
+    assert_eq!(simulation.merge_collisions(), vec![1]);
+    assert_eq!(simulation.entities.len(), 1);
+    let body = &simulation.entities[0];
+    assert_eq!(total_mass(&simulation), mass);
+    assert!((simulation.total_momentum() - momentum).magnitude() < 1e-9);
+    assert!((body.position - center).magnitude() < 1e-9);
+    assert!((body.radius.powi(3) - volume).abs() < 1e-4);
+}
+
+#[test]
+fn bodies_pass_through_with_merging_off() {
+    let mut simulation = Simulation::new(&overlapping(), DT);
+    simulation.merge_on_collision = false;
+    assert!(simulation.merge_collisions().is_empty());
+    assert!(simulation.step(DT).is_empty());
+    assert_eq!(simulation.entities.len(), 3);
+}
+
+#[test]
+fn head_on_equal_masses_merge_at_rest() {
+    // Closing at 2 units a second from 1.5 apart, so they touch within a second
+    let mut simulation = Simulation::new(
+        &scene(&[
+            (
+                Vector3::new(-0.75, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                1.0,
+                0.5,
+            ),
+            (
+                Vector3::new(0.75, 0.0, 0.0),
+                Vector3::new(-1.0, 0.0, 0.0),
+                1.0,
+                0.5,
+            ),
+        ]),
+        DT,
+    );
+    let mut merged = Vec::new();
+    for _ in 0..120 {
+        merged = simulation.step(DT);
+        if !merged.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(merged.len(), 1, "never merged");
+    assert_eq!(simulation.entities.len(), 1);
+    assert_eq!(simulation.entities[0].mass, 2.0);
+    assert!(simulation.total_momentum().magnitude() < 1e-9);
+    assert!(simulation.entities[0].position.magnitude() < 1e-9);
+}
+
+#[test]
+fn separate_pairs_merge_in_the_same_call() {
+    let still = Vector3::zero();
+    let mut simulation = Simulation::new(
+        &scene(&[
+            (Vector3::new(0.0, 0.0, 0.0), still, 1.0, 0.5),
+            (Vector3::new(0.5, 0.0, 0.0), still, 1.0, 0.5),
+            (Vector3::new(10.0, 0.0, 0.0), still, 1.0, 0.5),
+            (Vector3::new(10.5, 0.0, 0.0), still, 1.0, 0.5),
+        ]),
+        DT,
+    );
+    // From the back, so removing them one after another gives the same bodies
+    assert_eq!(simulation.merge_collisions(), vec![3, 1]);
+    assert_eq!(simulation.entities.len(), 2);
+    assert_eq!(simulation.entities[0].position.x, 0.25);
+    assert_eq!(simulation.entities[1].position.x, 10.25);
+}
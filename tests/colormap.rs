@@ -0,0 +1,90 @@
+//! Tinting bodies by a value: the colormaps' ends, normalizing over the bodies' own range,
+//! and local density picking out clustered bodies.
+
+use cgmath::*;
+use nbodysim::colormap::{self, ColorBy, ColorRange, ColorScale, Colormap};
+use nbodysim::sphere::{BodyType, Entity};
+
+/// How far apart two colors are, channel by channel
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).abs()).fold(0.0, f32::max)
+}
+
+/// A planet at `position` moving along x at `speed`
+fn body(position: Vector3<f64>, speed: f64) -> Entity {
+    let mut body = Entity::new(BodyType::Planet, position);
+    body.velocity = Vector3::unit_x() * speed;
+    body
+}
+
+#[test]
+fn colormaps_run_from_dark_to_bright() {
+    // The published ends of each colormap. The polynomial fits stray furthest from the
+    // tables at the ends, turbo's by about 0.13 in blue at the bottom
+    let viridis = ([0.267, 0.005, 0.329], [0.993, 0.906, 0.144]);
+    let turbo = ([0.190, 0.072, 0.232], [0.480, 0.016, 0.011]);
+    for (colormap, (low, high)) in [(Colormap::Viridis, viridis), (Colormap::Turbo, turbo)] {
+        assert!(distance(colormap.sample(0.0), low) < 0.15, "{:?}", colormap);
+        assert!(
+            distance(colormap.sample(1.0), high) < 0.15,
+            "{:?}",
+            colormap
+        );
+        // Clamped past either end
+        assert_eq!(colormap.sample(-1.0), colormap.sample(0.0));
+        assert_eq!(colormap.sample(2.0), colormap.sample(1.0));
+    }
+}
+
+#[test]
+fn auto_range_spans_the_bodies() {
+    let bodies = [1.0, 2.0, 3.0]
+        .iter()
+        .map(|&speed| body(Vector3::unit_x() * speed * 10.0, speed))
+        .collect::<Vec<_>>();
+    let scale = ColorScale {
+        color_by: ColorBy::Speed,
+        colormap: Colormap::Viridis,
+        range: ColorRange::Auto,
+    };
+    let tint = scale.colors(&bodies).unwrap();
+    assert_eq!((tint.min, tint.max), (1.0, 3.0));
+    let colors = tint.colors;
+    assert_eq!(colors[0], Colormap::Viridis.sample(0.0));
+    assert_eq!(colors[1], Colormap::Viridis.sample(0.5));
+    assert_eq!(colors[2], Colormap::Viridis.sample(1.0));
+
+    // A fixed range clamps what falls outside it
+    let fixed = ColorScale {
+        range: ColorRange::Fixed(2.0, 4.0),
+        ..scale
+    };
+    let colors = fixed.colors(&bodies).unwrap().colors;
+    assert_eq!(colors[0], Colormap::Viridis.sample(0.0));
+    assert_eq!(colors[2], Colormap::Viridis.sample(0.5));
+
+    // Bodies keep their own colors
+    let own = ColorScale {
+        color_by: ColorBy::Fixed,
+        ..scale
+    };
+    assert!(own.colors(&bodies).is_none());
+}
+
+#[test]
+fn clustered_bodies_are_denser_than_isolated_ones() {
+    // Five bodies packed together and one far off on its own
+    let mut bodies = (0..5)
+        .map(|i| body(Vector3::new(f64::from(i) * 0.5, 0.0, 0.0), 0.0))
+        .collect::<Vec<_>>();
+    bodies.push(body(Vector3::new(50.0, 0.0, 0.0), 0.0));
+    let density = colormap::local_density(&bodies, 3.0);
+    assert!(density[..5].iter().all(|&d| d > density[5] * 4.0));
+
+    let scale = ColorScale {
+        color_by: ColorBy::LocalDensity,
+        ..ColorScale::default()
+    };
+    let colors = scale.colors(&bodies).unwrap().colors;
+    assert_eq!(colors[5], Colormap::Viridis.sample(0.0));
+}
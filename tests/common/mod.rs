@@ -0,0 +1,12 @@
+//! Helpers shared between the integration tests.
+
+use cgmath::Vector3;
+use nbodysim::sphere::{BodyType, Entity};
+
+/// A body of `mass` at `position` moving at `velocity`
+pub fn body(mass: f32, position: Vector3<f64>, velocity: Vector3<f64>) -> Entity {
+    let mut body = Entity::new(BodyType::Planet, position);
+    body.mass = mass;
+    body.velocity = velocity;
+    body
+}
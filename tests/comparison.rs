@@ -0,0 +1,40 @@
+//! Two runs of the same bodies stepped side by side, as the ghosted comparison view does.
+
+use nbodysim::integrator::Integrator;
+use nbodysim::scene::{CloudParams, Scene};
+use nbodysim::simulation::Simulation;
+
+const DT: f64 = 0.01;
+const STEPS: usize = 500;
+
+#[test]
+fn identical_simulations_never_diverge() {
+    let scene = Scene::random_cloud(30, 11, CloudParams::default());
+    let mut simulation = Simulation::new(&scene, DT);
+    let mut ghost = simulation.comparison();
+    for step in 0..STEPS {
+        simulation.step(DT);
+        ghost.step(DT);
+        assert_eq!(
+            simulation.divergence(&ghost),
+            0.0,
+            "apart after step {}",
+            step
+        );
+        assert_eq!(simulation.entities.len(), ghost.entities.len());
+        assert_eq!(simulation.time, ghost.time);
+    }
+}
+
+#[test]
+fn other_integrator_drifts_apart() {
+    let scene = Scene::random_cloud(30, 11, CloudParams::default());
+    let mut simulation = Simulation::new(&scene, DT);
+    let mut ghost = simulation.comparison();
+    ghost.integrator = Integrator::Euler;
+    for _ in 0..STEPS {
+        simulation.step(DT);
+        ghost.step(DT);
+    }
+    assert!(simulation.divergence(&ghost) > 0.0);
+}
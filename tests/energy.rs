@@ -0,0 +1,69 @@
+//! The total energy of two-body systems worked out by hand: a pair at rest, a pair in
+//! motion, and a circular binary, whose energy is -G m1 m2 / 2a.
+
+mod common;
+
+use cgmath::*;
+use common::body;
+use nbodysim::physics;
+use nbodysim::scene;
+
+const G: f32 = 2.0;
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() <= 1e-9 * expected.abs(),
+        "got {}, expected {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn pair_at_rest_has_only_potential_energy() {
+    let bodies = [
+        body(3.0, Vector3::zero(), Vector3::zero()),
+        body(5.0, Vector3::new(0.0, 4.0, 0.0), Vector3::zero()),
+    ];
+    assert_eq!(physics::kinetic_energy(&bodies), 0.0);
+    // -G m1 m2 / r = -2 * 3 * 5 / 4
+    assert_close(physics::total_energy(&bodies, G, 0.0), -7.5);
+    // Softening stretches the distance to sqrt(r^2 + eps^2) = 5
+    assert_close(physics::total_energy(&bodies, G, 3.0), -6.0);
+}
+
+#[test]
+fn moving_pair_adds_kinetic_energy() {
+    let bodies = [
+        body(2.0, Vector3::zero(), Vector3::new(1.0, 2.0, 2.0)),
+        body(
+            4.0,
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(-0.5, 0.0, 0.0),
+        ),
+    ];
+    // 1/2 * 2 * 9 + 1/2 * 4 * 0.25 - 2 * 2 * 4 / 8
+    assert_close(physics::kinetic_energy(&bodies), 9.5);
+    assert_close(physics::total_energy(&bodies, G, 0.0), 7.5);
+}
+
+#[test]
+fn circular_binary_has_energy_minus_g_m1_m2_over_2a() {
+    let (m1, m2, separation) = (10.0, 1.0, 4.0);
+    let pair = scene::binary_system(m1, m2, separation, G);
+    let bodies = [(m1, pair[0]), (m2, pair[1])]
+        .iter()
+        .map(|&(mass, (position, velocity))| {
+            body(mass, position.map(f64::from), velocity.map(f64::from))
+        })
+        .collect::<Vec<_>>();
+    let expected = -f64::from(G * m1 * m2) / (2.0 * f64::from(separation));
+    // The orbit is set up in f32, so only good to its precision
+    let energy = physics::total_energy(&bodies, G, 0.0);
+    assert!(
+        (energy - expected).abs() < 1e-5 * expected.abs(),
+        "got {}, expected {}",
+        energy,
+        expected
+    );
+}
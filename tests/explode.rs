@@ -0,0 +1,42 @@
+//! Blowing a cluster apart kicks every body straight away from the center.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::scene::{CloudParams, Scene};
+use nbodysim::sphere::Entity;
+
+#[test]
+fn every_body_gains_the_outward_speed() {
+    let scene = Scene::random_cloud(50, 5, CloudParams::default());
+    let mut bodies = scene
+        .bodies
+        .iter()
+        .map(Entity::from_config)
+        .collect::<Vec<_>>();
+    let before = bodies.clone();
+    let center = physics::center_of_mass(&bodies);
+    let magnitude = 2.5;
+    physics::explode(&mut bodies, center, magnitude);
+
+    for (body, old) in bodies.iter().zip(&before) {
+        let outward = (old.position - center).normalize();
+        let kick = body.velocity - old.velocity;
+        assert!((kick.magnitude() - magnitude).abs() < 1e-9);
+        assert!((kick.dot(outward) - magnitude).abs() < 1e-9);
+        assert_eq!(body.position, old.position);
+    }
+}
+
+#[test]
+fn body_on_the_center_is_left_alone() {
+    let scene = Scene::random_cloud(2, 1, CloudParams::default());
+    let mut bodies = scene
+        .bodies
+        .iter()
+        .map(Entity::from_config)
+        .collect::<Vec<_>>();
+    let center = bodies[0].position;
+    let velocity = bodies[0].velocity;
+    physics::explode(&mut bodies, center, 1.0);
+    assert_eq!(bodies[0].velocity, velocity);
+}
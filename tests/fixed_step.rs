@@ -0,0 +1,39 @@
+//! Physics runs in fixed steps however long the frame was: a frame's time is cut into whole
+//! steps, and what's left over waits for the next frame.
+
+use nbodysim::scene::{Preset, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::stepper::{Stepper, DEFAULT_MAX_SUBSTEPS_PER_FRAME};
+
+const DT: f64 = 0.01;
+
+/// Runs a frame of `elapsed` seconds through `stepper`, stepping `simulation` each time
+/// it says to. Returns how many steps were taken
+fn run_frame(stepper: &mut Stepper, simulation: &mut Simulation, elapsed: f64) -> usize {
+    stepper.begin_frame(elapsed);
+    let mut steps = 0;
+    while stepper.next_step(DT) {
+        simulation.step(DT);
+        steps += 1;
+    }
+    steps
+}
+
+#[test]
+fn a_twentieth_of_a_second_is_five_steps() {
+    let mut stepper = Stepper::new(DEFAULT_MAX_SUBSTEPS_PER_FRAME);
+    let mut simulation = Simulation::new(&Scene::preset(Preset::Binary), DT);
+    assert_eq!(run_frame(&mut stepper, &mut simulation, 0.05), 5);
+    assert!((simulation.time - 0.05).abs() < 1e-6);
+    assert!(stepper.accumulator.abs() < 1e-6);
+}
+
+#[test]
+fn leftover_time_waits_for_the_next_frame() {
+    let mut stepper = Stepper::new(DEFAULT_MAX_SUBSTEPS_PER_FRAME);
+    let mut simulation = Simulation::new(&Scene::preset(Preset::Binary), DT);
+    assert_eq!(run_frame(&mut stepper, &mut simulation, 0.055), 5);
+    assert!((stepper.accumulator - 0.005).abs() < 1e-6);
+    // Together with the leftover this is a whole step
+    assert_eq!(run_frame(&mut stepper, &mut simulation, 0.005), 1);
+}
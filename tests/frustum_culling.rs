@@ -0,0 +1,69 @@
+//! Frustum culling against the planes pulled out of a view-projection matrix.
+
+use cgmath::*;
+use nbodysim::frustum::Frustum;
+
+/// The same depth conversions the camera applies, from OpenGL's -1 to 1 to wgpu's 0 to 1,
+/// and to 1 to 0 for reversed depth
+#[rustfmt::skip]
+const TO_WGPU: [Matrix4<f32>; 2] = [
+    Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    ),
+    Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, -0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    ),
+];
+
+/// A camera at (0, 0, 10) looking at the origin with a 90 degree field of view, seeing
+/// from 1 to 100 units away
+fn frustums() -> Vec<Frustum> {
+    let view = Matrix4::look_at_rh(
+        Point3::new(0.0, 0.0, 10.0),
+        Point3::origin(),
+        Vector3::unit_y(),
+    );
+    let projection = perspective(Deg(90.0), 1.0, 1.0, 100.0);
+    TO_WGPU
+        .iter()
+        .map(|correction| Frustum::from_view_projection(correction * projection * view))
+        .collect()
+}
+
+#[test]
+fn body_behind_the_camera_is_culled() {
+    for frustum in frustums() {
+        assert!(frustum.intersects_sphere(Point3::origin(), 1.0));
+        assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, 20.0), 1.0));
+        // Behind the eye, but big enough to reach back in front of the near plane
+        assert!(frustum.intersects_sphere(Point3::new(0.0, 0.0, 12.0), 4.0));
+    }
+}
+
+#[test]
+fn bodies_past_the_edges_are_culled() {
+    for frustum in frustums() {
+        // The sides are 45 degrees off the view direction, so 10 units out at the origin
+        assert!(!frustum.intersects_sphere(Point3::new(12.0, 0.0, 0.0), 1.0));
+        assert!(!frustum.intersects_sphere(Point3::new(0.0, -12.0, 0.0), 1.0));
+        // Centered off screen but poking over the edge
+        assert!(frustum.intersects_sphere(Point3::new(10.5, 0.0, 0.0), 1.0));
+        assert!(frustum.intersects_sphere(Point3::new(0.0, 9.0, 0.0), 0.5));
+    }
+}
+
+#[test]
+fn bodies_past_the_clipping_planes_are_culled() {
+    for frustum in frustums() {
+        assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, -95.0), 1.0));
+        assert!(frustum.intersects_sphere(Point3::new(0.0, 0.0, -89.5), 1.0));
+        // Between the eye and the near plane
+        assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, 9.5), 0.2));
+    }
+}
@@ -0,0 +1,24 @@
+//! The pairwise pull between bodies: two equal masses should pull on each other equally
+//! and in opposite directions, straight along the line between them.
+
+use cgmath::*;
+use nbodysim::physics::{self, GravityStrategy};
+use nbodysim::sphere::{BodyType, Entity};
+
+#[test]
+fn equal_masses_accelerate_towards_each_other_symmetrically() {
+    let a = Entity::new(BodyType::Planet, Vector3::new(-1.0, 0.5, 2.0));
+    let b = Entity::new(BodyType::Planet, Vector3::new(2.0, -1.0, 0.0));
+    let accelerations = physics::compute_gravity(
+        &[a.clone(), b.clone()],
+        1.0,
+        physics::DEFAULT_SOFTENING,
+        GravityStrategy::BruteForce,
+    );
+
+    // Equal and opposite
+    assert!((accelerations[0] + accelerations[1]).magnitude() < 1e-12);
+    // Towards the other body
+    let towards_b = (b.position - a.position).normalize();
+    assert!((accelerations[0].normalize() - towards_b).magnitude() < 1e-12);
+}
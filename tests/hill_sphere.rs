@@ -0,0 +1,47 @@
+//! Hill radii, a * cbrt(m / 3M), and the primaries they're measured against.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::sphere::{BodyType, Entity};
+
+/// A planet of `mass` at `position`
+fn body(mass: f32, position: Vector3<f64>) -> Entity {
+    let mut body = Entity::new(BodyType::Planet, position);
+    body.mass = mass;
+    body
+}
+
+#[test]
+fn hill_radius_matches_the_formula() {
+    // The Earth around the Sun, in solar masses and AU: about 0.01 AU
+    let radius = physics::hill_radius(3.0e-6, 1.0, 1.0);
+    assert!((radius - 0.01).abs() < 1e-6, "got {}", radius);
+
+    // A mass ratio of 1:9 gives a third of the separation, whatever the units
+    let (mass, primary, separation) = (2.0, 18.0, 9.0);
+    let radius = physics::hill_radius(mass, primary, separation);
+    assert!((radius - 3.0).abs() < 1e-5, "got {}", radius);
+}
+
+#[test]
+fn body_hill_radius_uses_the_primary() {
+    let star = body(18.0, Vector3::zero());
+    let planet = body(2.0, Vector3::new(0.0, 0.0, 9.0));
+    let moon = body(0.01, Vector3::new(0.0, 0.0, 9.5));
+    let mut bodies = vec![star, planet, moon];
+
+    // With none picked, the planet goes around the star and the moon around the planet,
+    // which pulls on it harder
+    assert_eq!(physics::hill_primary(&bodies, 0), None);
+    assert_eq!(physics::hill_primary(&bodies, 1), Some(0));
+    assert_eq!(physics::hill_primary(&bodies, 2), Some(1));
+    assert_eq!(physics::body_hill_radius(&bodies, 0), None);
+    let radius = physics::body_hill_radius(&bodies, 1).unwrap();
+    assert!((radius - 3.0).abs() < 1e-5, "got {}", radius);
+
+    // A primary picked for the moon replaces the one it would get
+    bodies[2].hill_primary = Some(0);
+    let expected = physics::hill_radius(0.01, 18.0, 9.5);
+    let radius = physics::body_hill_radius(&bodies, 2).unwrap();
+    assert!((radius - expected).abs() < 1e-5, "got {}", radius);
+}
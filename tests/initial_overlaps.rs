@@ -0,0 +1,43 @@
+//! Bodies that start out overlapping are pushed apart or merged before the simulation starts.
+
+use cgmath::*;
+use nbodysim::scene::{BodyConfig, OverlapPolicy, Scene};
+use nbodysim::sphere::BodyType;
+
+/// A row of planets closer together than their radii, with the last one well clear
+fn crowded_scene() -> Scene {
+    let planet =
+        |x: f32| BodyConfig::new(BodyType::Planet, Vector3::new(x, 0.0, 0.0), Vector3::zero());
+    let radius = BodyType::Planet.radius();
+    let bodies = vec![
+        planet(0.0),
+        planet(radius),
+        planet(radius * 1.5),
+        planet(radius * 20.0),
+    ];
+    Scene {
+        bodies,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn nudging_leaves_no_pair_overlapping() {
+    let mut scene = crowded_scene();
+    let offending = scene.resolve_initial_overlaps(OverlapPolicy::Nudge);
+    assert_eq!(offending, vec![(0, 1), (0, 2), (1, 2)]);
+    assert_eq!(scene.bodies.len(), 4);
+    assert!(scene.overlapping_pairs().is_empty());
+}
+
+#[test]
+fn merging_leaves_fewer_bodies_and_no_overlaps() {
+    let mut scene = crowded_scene();
+    let mass = scene.bodies.iter().map(|body| body.mass).sum::<f32>();
+    scene.resolve_initial_overlaps(OverlapPolicy::Merge);
+    // The three touching planets become one
+    assert_eq!(scene.bodies.len(), 2);
+    assert!(scene.overlapping_pairs().is_empty());
+    let merged_mass = scene.bodies.iter().map(|body| body.mass).sum::<f32>();
+    assert!((merged_mass - mass).abs() < 1e-4 * mass);
+}
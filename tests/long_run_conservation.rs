@@ -0,0 +1,77 @@
+//! Rounding error builds up over long runs. A binary placed far from the origin is the
+//! hardest case, since every step's small move is added to a large position. With the
+//! bodies kept in f64 it still conserves energy and angular momentum over hundreds of orbits,
+//! where in f32 the orbit falls apart.
+
+use cgmath::*;
+use nbodysim::integrator::Integrator;
+use nbodysim::physics;
+use nbodysim::scene::{self, BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+use nbodysim::units::UnitSystem;
+
+const STAR_MASS: f32 = 1000.0;
+const PLANET_MASS: f32 = 1.0;
+const SEPARATION: f32 = 5.0;
+/// Where the pair's center of mass sits. At this distance f32 can only place the bodies to
+/// within about 0.0001, against steps of about 0.1
+const OFFSET: f32 = 1000.0;
+const DT: f64 = 1.0 / 120.0;
+const ORBITS: f32 = 200.0;
+
+/// The star and planet circling their center of mass at OFFSET along the x axis
+fn distant_binary() -> Scene {
+    let g = UnitSystem::Natural.gravitational_constant();
+    let [(star_position, star_velocity), (planet_position, planet_velocity)] =
+        scene::binary_system(STAR_MASS, PLANET_MASS, SEPARATION, g);
+    let offset = Vector3::unit_x() * OFFSET;
+
+    let mut star = BodyConfig::new(BodyType::Star, star_position + offset, star_velocity);
+    star.mass = STAR_MASS;
+    star.radius = 0.5;
+    let mut planet = BodyConfig::new(BodyType::Planet, planet_position + offset, planet_velocity);
+    planet.mass = PLANET_MASS;
+    planet.radius = 0.1;
+    Scene {
+        bodies: vec![star, planet],
+        units: UnitSystem::Natural,
+        softening: Some(0.0),
+        ..Default::default()
+    }
+}
+
+/// How far `value` has moved from `start`, as a fraction of `start`
+fn relative_error(value: f64, start: f64) -> f64 {
+    ((value - start) / start).abs()
+}
+
+#[test]
+fn distant_binary_conserves_energy_and_angular_momentum() {
+    let mut simulation = Simulation::new(&distant_binary(), DT);
+    simulation.integrator = Integrator::VelocityVerlet;
+    let g = UnitSystem::Natural.gravitational_constant();
+    let period = physics::orbital_period(STAR_MASS + PLANET_MASS, SEPARATION, g);
+    let steps = (f64::from(ORBITS * period) / DT).ceil() as usize;
+
+    let start_energy = simulation.total_energy();
+    let start_spin = physics::angular_momentum(&simulation.entities).y;
+    let (mut energy_error, mut spin_error) = (0.0_f64, 0.0_f64);
+    for _ in 0..steps {
+        let merged = simulation.step(DT);
+        assert!(merged.is_empty(), "the bodies collided");
+        energy_error = energy_error.max(relative_error(simulation.total_energy(), start_energy));
+        let spin = physics::angular_momentum(&simulation.entities).y;
+        spin_error = spin_error.max(relative_error(spin, start_spin));
+    }
+
+    // Velocity Verlet's own error only wobbles the energy by about 1e-7 at this step, and
+    // it keeps angular momentum exactly, so anything much past that is rounding.
+    // Run in f32 these come out around 1e-3
+    assert!(energy_error < 1e-4, "energy drifted by {}", energy_error);
+    assert!(
+        spin_error < 1e-6,
+        "angular momentum drifted by {}",
+        spin_error
+    );
+}
@@ -0,0 +1,106 @@
+//! The initial-velocity helpers in scene.rs, and the presets built from them.
+
+use cgmath::*;
+use nbodysim::scene::{self, Preset, Scene};
+use nbodysim::units::UnitSystem;
+
+/// Whether `a` and `b` agree to within a relative `tolerance`
+fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() <= tolerance * a.abs().max(b.abs())
+}
+
+#[test]
+fn circular_orbit_speed_is_sqrt_gm_over_r() {
+    let g = UnitSystem::Astronomical.gravitational_constant();
+    for &(mass, offset) in &[
+        (1.0, Vector3::new(1.0, 0.0, 0.0)),
+        (1000.0, Vector3::new(-3.0, 0.0, 4.0)),
+        (5.0e-3, Vector3::new(0.2, 0.7, -0.1)),
+        // Straight up the y axis, where there's no counterclockwise-from-above to go by
+        (2.0, Vector3::new(0.0, 6.0, 0.0)),
+    ] {
+        let velocity = scene::circular_orbit(mass, offset, g);
+        let expected = (g * mass / offset.magnitude()).sqrt();
+        assert!(
+            close(velocity.magnitude(), expected, 1e-5),
+            "speed {} around {} at {:?}, expected {}",
+            velocity.magnitude(),
+            mass,
+            offset,
+            expected
+        );
+        // Circular orbits move at right angles to the line to the center
+        assert!(velocity.dot(offset).abs() <= 1e-5 * velocity.magnitude() * offset.magnitude());
+    }
+}
+
+#[test]
+fn binary_system_circles_its_center_of_mass() {
+    let (m1, m2, separation, g) = (3.0, 1.0, 8.0, 1.0);
+    let [(p1, v1), (p2, v2)] = scene::binary_system(m1, m2, separation, g);
+
+    assert!(close((p2 - p1).magnitude(), separation, 1e-6));
+    assert!(
+        (p1 * m1 + p2 * m2).magnitude() < 1e-5,
+        "center of mass moved"
+    );
+    assert!((v1 * m1 + v2 * m2).magnitude() < 1e-5, "the pair drifts");
+    // Relative to each other, the bodies orbit their total mass
+    let relative = (v2 - v1).magnitude();
+    assert!(close(relative, (g * (m1 + m2) / separation).sqrt(), 1e-5));
+}
+
+#[test]
+fn presets_start_without_drifting() {
+    for &preset in &Preset::ALL {
+        let scene = Scene::preset(preset);
+        let momentum = scene
+            .bodies
+            .iter()
+            .fold(Vector3::zero(), |sum, body| sum + body.velocity * body.mass);
+        // Compared to the momentum moving around in the scene, which can be far from 1
+        let scale = scene
+            .bodies
+            .iter()
+            .map(|body| body.velocity.magnitude() * body.mass)
+            .sum::<f32>()
+            .max(f32::MIN_POSITIVE);
+        assert!(
+            momentum.magnitude() <= 1e-5 * scale,
+            "{} starts with momentum {:?}",
+            preset.name(),
+            momentum
+        );
+        assert_eq!(Preset::from_name(preset.name()), Some(preset));
+    }
+}
+
+#[test]
+fn resonance_demo_periods_are_in_the_requested_ratio() {
+    for &(outer, inner) in &[(2, 1), (3, 2), (5, 3)] {
+        let scene = Scene::resonance_demo((outer, inner));
+        let g = scene.units.gravitational_constant();
+        let star = &scene.bodies[0];
+        // Each planet's period from how fast it goes around its circle about the star
+        let period = |index: usize| {
+            let planet = &scene.bodies[index];
+            let radius = (planet.position - star.position).magnitude();
+            // The star's small recoil isn't part of the planets' orbits
+            let speed = planet.velocity.magnitude();
+            let period = 2.0 * std::f32::consts::PI * radius / speed;
+            // and the same from Kepler's third law
+            let kepler = nbodysim::physics::orbital_period(star.mass, radius, g);
+            assert!(close(period, kepler, 1e-3), "{} against {}", period, kepler);
+            period
+        };
+        let ratio = period(2) / period(1);
+        let expected = outer as f32 / inner as f32;
+        assert!(
+            close(ratio, expected, 1e-3),
+            "{}:{} gave a ratio of {}",
+            outer,
+            inner,
+            ratio
+        );
+    }
+}
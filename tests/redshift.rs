@@ -0,0 +1,67 @@
+//! The toy redshift tint: bodies deeper in a gravity well are drawn redder.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::sphere::{self, BodyType, Entity};
+
+#[test]
+fn deeper_body_is_shifted_redder() {
+    let star = Entity::new(BodyType::Star, Vector3::zero());
+    let near = Entity::new(BodyType::Planet, Vector3::new(5.0, 0.0, 0.0));
+    let far = Entity::new(BodyType::Planet, Vector3::new(0.0, 0.0, 500.0));
+    let bodies = [star, near, far];
+    let (near_depth, far_depth) = (
+        physics::potential_depth(&bodies, 1),
+        physics::potential_depth(&bodies, 2),
+    );
+    assert!(near_depth > far_depth);
+
+    let strength = 0.001;
+    let color = bodies[1].color;
+    let near_color = sphere::redshift(color, near_depth, strength);
+    let far_color = sphere::redshift(color, far_depth, strength);
+    // More red and less green and blue
+    assert!(near_color[0] > far_color[0] && far_color[0] > color[0]);
+    assert!(near_color[1] < far_color[1] && near_color[2] < far_color[2]);
+}
+
+#[test]
+fn no_strength_leaves_the_color_alone() {
+    let color = [0.2, 0.4, 0.6];
+    assert_eq!(sphere::redshift(color, 1000.0, 0.0), color);
+}
+
+#[test]
+fn octree_depths_match_summing_every_pair() {
+    let bodies: Vec<_> = (0..40)
+        .map(|i| {
+            let angle = i as f64 * 0.7;
+            let position = Vector3::new(angle.cos(), angle.sin(), 0.1 * i as f64) * (i + 1) as f64;
+            Entity::new(BodyType::Planet, position)
+        })
+        .collect();
+    let exact: Vec<_> = (0..bodies.len())
+        .map(|i| physics::potential_depth(&bodies, i))
+        .collect();
+
+    for (depth, exact) in physics::potential_depths(&bodies, 0.0).iter().zip(&exact) {
+        assert!(
+            (depth - exact).abs() <= exact * 1e-5,
+            "{} vs {}",
+            depth,
+            exact
+        );
+    }
+    // The opening angle the renderer uses stays close enough to tint by
+    for (depth, exact) in physics::potential_depths(&bodies, physics::DEFAULT_THETA)
+        .iter()
+        .zip(&exact)
+    {
+        assert!(
+            (depth - exact).abs() <= exact * 0.05,
+            "{} vs {}",
+            depth,
+            exact
+        );
+    }
+}
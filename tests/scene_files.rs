@@ -0,0 +1,29 @@
+//! Scene files: the samples in the scenes folder should load, and a broken one should come
+//! back as an error naming the file rather than a panic.
+
+use nbodysim::scene::Scene;
+use std::path::Path;
+
+#[test]
+fn sample_scenes_load() {
+    for name in &["binary_star.ron", "inner_solar_system.ron"] {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("scenes")
+            .join(name);
+        let scene = Scene::load(&path).unwrap();
+        assert!(!scene.bodies.is_empty(), "{} has no bodies", name);
+    }
+}
+
+#[test]
+fn malformed_scene_is_a_parse_error() {
+    let path = std::env::temp_dir().join(format!("nbodysim-malformed-{}.ron", std::process::id()));
+    // The closing parentheses and brackets are missing
+    std::fs::write(&path, "(bodies: [(body_type: Planet, position: (0.0, 0.0").unwrap();
+    let result = Scene::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let error = format!("{:#}", result.unwrap_err());
+    assert!(error.contains("Failed to parse scene file"), "{}", error);
+    assert!(error.contains("nbodysim-malformed"), "{}", error);
+}
@@ -0,0 +1,65 @@
+//! Two bodies falling straight into each other with no softening and nothing to merge them,
+//! which without the separation clamp can fling them apart at an arbitrary speed.
+
+use cgmath::*;
+use nbodysim::scene::{BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+use nbodysim::units::UnitSystem;
+
+const MASS: f32 = 1.0;
+const MIN_SEPARATION: f32 = 0.1;
+const DT: f64 = 1.0 / 120.0;
+
+/// The pair heading head-on for each other along x. Radii of 0 so they never collide
+fn collision_course() -> Scene {
+    let body = |x: f32, speed: f32| {
+        let mut config = BodyConfig::new(
+            BodyType::Planet,
+            Vector3::new(x, 0.0, 0.0),
+            Vector3::new(speed, 0.0, 0.0),
+        );
+        config.mass = MASS;
+        config.radius = 0.0;
+        config
+    };
+    Scene {
+        bodies: vec![body(-5.0, 1.0), body(5.0, -1.0)],
+        units: UnitSystem::Natural,
+        softening: Some(0.0),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn clamped_close_pass_stays_at_finite_speed() {
+    let mut simulation = Simulation::new(&collision_course(), DT);
+    simulation.clamp_separation = true;
+    simulation.min_separation = MIN_SEPARATION;
+    simulation.refresh_accelerations();
+
+    // The deepest the clamped potential goes is 3/2 G m / min_separation, which is as much
+    // kinetic energy as the pair can pick up on top of what they started with
+    let g = simulation.gravitational_constant;
+    let start_speed2 = 1.0;
+    let max_speed = f64::from((start_speed2 + 1.5 * g * MASS / MIN_SEPARATION).sqrt());
+
+    let mut clamped = false;
+    for _ in 0..(20.0 / DT) as usize {
+        let merged = simulation.step(DT);
+        assert!(merged.is_empty(), "the bodies merged");
+        clamped |= simulation.clamped_pairs > 0;
+        for body in &simulation.entities {
+            let speed = body.velocity.magnitude();
+            assert!(speed.is_finite(), "a body reached speed {}", speed);
+            // Some slack for the integrator's error around the sharp turn in the force
+            assert!(
+                speed < max_speed * 1.1,
+                "a body reached speed {}, more than {}",
+                speed,
+                max_speed
+            );
+        }
+    }
+    assert!(clamped, "the pair never came within the minimum separation");
+}
@@ -0,0 +1,49 @@
+//! Saving a simulation to disk and loading it back should resume it exactly, down to the
+//! last bit of every position and velocity.
+
+use nbodysim::integrator::Integrator;
+use nbodysim::scene::{Preset, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::snapshot::Snapshot;
+
+const DT: f64 = 0.01;
+
+/// The bits of every body's position and velocity
+fn bits(simulation: &Simulation) -> Vec<[u64; 6]> {
+    simulation
+        .entities
+        .iter()
+        .map(|body| {
+            let (p, v) = (body.position, body.velocity);
+            [p.x, p.y, p.z, v.x, v.y, v.z].map(f64::to_bits)
+        })
+        .collect()
+}
+
+#[test]
+fn save_and_load_round_trips_exactly() {
+    let mut simulation = Simulation::new(&Scene::preset(Preset::Pythagorean), DT);
+    simulation.integrator = Integrator::Leapfrog;
+    assert_eq!(simulation.entities.len(), 3);
+    for _ in 0..100 {
+        simulation.step(DT);
+    }
+    let saved = bits(&simulation);
+    let time = simulation.time;
+
+    let path = std::env::temp_dir().join(format!("nbodysim-snapshot-{}.ron", std::process::id()));
+    simulation.snapshot().save(&path).unwrap();
+    // Moving on, so there's something to go back from
+    for _ in 0..100 {
+        simulation.step(DT);
+    }
+    simulation.integrator = Integrator::Euler;
+    assert_ne!(bits(&simulation), saved);
+
+    let snapshot = Snapshot::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    simulation.restore(snapshot.unwrap());
+    assert_eq!(bits(&simulation), saved);
+    assert_eq!(simulation.time.to_bits(), time.to_bits());
+    assert_eq!(simulation.integrator, Integrator::Leapfrog);
+}
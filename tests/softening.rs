@@ -0,0 +1,53 @@
+//! Plummer softening: each pair pulls with G * m / (r^2 + softening^2), so bodies sitting
+//! on top of each other get a finite pull, and a softening of 0 is plain Newtonian gravity.
+
+use cgmath::*;
+use nbodysim::physics::{self, GravityStrategy};
+use nbodysim::sphere::{BodyType, Entity};
+
+const G: f32 = 1.0;
+const MASS: f32 = 2.0;
+
+/// Planets of MASS at each of `positions`
+fn bodies(positions: &[Vector3<f64>]) -> Vec<Entity> {
+    positions
+        .iter()
+        .map(|&position| {
+            let mut body = Entity::new(BodyType::Planet, position);
+            body.mass = MASS;
+            body
+        })
+        .collect()
+}
+
+#[test]
+fn overlapping_bodies_get_a_finite_pull() {
+    let softening = physics::DEFAULT_SOFTENING;
+    let strategy = GravityStrategy::BruteForce;
+    // Exactly on top of each other, where there's no direction to pull in
+    let together = bodies(&[Vector3::zero(), Vector3::zero()]);
+    for acceleration in physics::compute_gravity(&together, G, softening, strategy) {
+        assert_eq!(acceleration, Vector3::zero());
+    }
+
+    // Almost on top of each other, where unsoftened gravity would be enormous
+    let gap = 1e-6;
+    let close = bodies(&[Vector3::zero(), Vector3::unit_x() * gap]);
+    let accelerations = physics::compute_gravity(&close, G, softening, strategy);
+    let limit = f64::from(G * MASS / (softening * softening));
+    for acceleration in &accelerations {
+        assert!(acceleration.x.is_finite() && acceleration.magnitude() < limit);
+    }
+    // Still pulled towards each other
+    assert!(accelerations[0].x > 0.0 && accelerations[1].x < 0.0);
+}
+
+#[test]
+fn zero_softening_is_newtonian() {
+    let distance = 3.0;
+    let pair = bodies(&[Vector3::zero(), Vector3::unit_x() * distance]);
+    let accelerations = physics::compute_gravity(&pair, G, 0.0, GravityStrategy::BruteForce);
+    let expected = f64::from(G * MASS) / (distance * distance);
+    assert!((accelerations[0].x - expected).abs() < 1e-12 * expected);
+    assert!((accelerations[1].x + expected).abs() < 1e-12 * expected);
+}
@@ -0,0 +1,66 @@
+//! The spatial hash should find exactly the overlapping pairs a check of every pair finds.
+
+use cgmath::*;
+use nbodysim::spatial_hash;
+use nbodysim::sphere::{BodyType, Entity};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// `n` bodies of mixed sizes crammed into a cube `size` across, so plenty of them overlap
+fn dense_cluster(n: usize, size: f32, seed: u64) -> Vec<Entity> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            let mut coordinate = || rng.gen_range(-size / 2.0..size / 2.0);
+            let position = Vector3::new(coordinate(), coordinate(), coordinate());
+            let mut body = Entity::new(BodyType::Planet, position.map(f64::from));
+            body.radius = rng.gen_range(0.05..0.6);
+            body
+        })
+        .collect()
+}
+
+/// Every overlapping pair, found by checking every pair
+fn brute_force_pairs(entities: &[Entity]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, body) in entities.iter().enumerate() {
+        for (j, other) in entities.iter().enumerate().skip(i + 1) {
+            let reach = f64::from(body.radius + other.radius);
+            if (other.position - body.position).magnitude2() < reach * reach {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+#[test]
+fn finds_the_same_pairs_as_brute_force() {
+    let entities = dense_cluster(500, 10.0, 1);
+    let expected = brute_force_pairs(&entities);
+    assert!(
+        expected.len() > 100,
+        "the cluster isn't dense enough to test with"
+    );
+
+    let default = spatial_hash::default_cell_size(&entities);
+    // Too small and too large cells are slower, but should never change the answer
+    for cell_size in [default, default * 0.25, default * 4.0, 100.0] {
+        assert_eq!(
+            spatial_hash::overlapping_pairs(&entities, cell_size),
+            expected,
+            "cell size {}",
+            cell_size
+        );
+    }
+}
+
+#[test]
+fn finds_pairs_across_cell_boundaries() {
+    // Two bodies just either side of the boundary between cells at x = 0
+    let mut a = Entity::new(BodyType::Planet, Vector3::new(-0.1, 0.0, 0.0));
+    let mut b = Entity::new(BodyType::Planet, Vector3::new(0.1, 0.0, 0.0));
+    a.radius = 0.15;
+    b.radius = 0.15;
+    assert_eq!(spatial_hash::overlapping_pairs(&[a, b], 1.0), vec![(0, 1)]);
+}
@@ -0,0 +1,83 @@
+//! Sphere meshes should stay closed, outward-facing spheres down to the coarsest resolution.
+
+use cgmath::*;
+use nbodysim::sphere::{self, SphereMeshVertex};
+
+/// The distinct corners of the mesh, ignoring the copies made to wrap textures cleanly
+fn distinct_positions(vertices: &[SphereMeshVertex]) -> Vec<Vector3<f32>> {
+    let mut distinct: Vec<Vector3<f32>> = Vec::new();
+    for vertex in vertices {
+        let position = vertex.position();
+        if distinct
+            .iter()
+            .all(|other| (other - position).magnitude() > 1e-4)
+        {
+            distinct.push(position);
+        }
+    }
+    distinct
+}
+
+/// Checks every vertex sits on the unit sphere facing straight out, and every triangle
+/// winds counterclockwise seen from outside, which back-face culling relies on
+fn assert_outward_unit_sphere(vertices: &[SphereMeshVertex], indices: &[u32]) {
+    for vertex in vertices {
+        assert!((vertex.position().magnitude() - 1.0).abs() < 1e-5);
+        assert!((vertex.normal() - vertex.position()).magnitude() < 1e-5);
+    }
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position());
+        let facing = (b - a).cross(c - a);
+        assert!(
+            facing.dot(a + b + c) > 0.0,
+            "triangle {:?} faces inwards",
+            triangle
+        );
+    }
+}
+
+#[test]
+fn coarsest_sphere_is_a_closed_cube() {
+    let (vertices, indices) = sphere::sphere_geometry(sphere::MIN_SPHERE_RESOLUTION).unwrap();
+    assert_outward_unit_sphere(&vertices, &indices);
+
+    // Each face is a single quad, so the sphere comes out as a cube's 8 corners and
+    // 2 triangles on each of its 6 faces, still centered and the same size either way
+    let corners = distinct_positions(&vertices);
+    assert_eq!(corners.len(), 8);
+    assert_eq!(indices.len(), 6 * 2 * 3);
+    for corner in corners {
+        for axis in 0..3 {
+            assert!((corner[axis].abs() - 1.0 / 3.0_f32.sqrt()).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn default_sphere_faces_outwards() {
+    let (vertices, indices) = sphere::sphere_geometry(sphere::DEFAULT_SPHERE_RESOLUTION).unwrap();
+    assert_outward_unit_sphere(&vertices, &indices);
+}
+
+#[test]
+fn resolution_below_minimum_is_rejected() {
+    assert!(sphere::sphere_geometry(sphere::MIN_SPHERE_RESOLUTION - 1).is_err());
+    assert!(sphere::sphere_geometry(0).is_err());
+}
+
+#[test]
+fn seams_are_welded() {
+    let resolution = 4;
+    let (vertices, _) = sphere::sphere_geometry(resolution).unwrap();
+    // Six separate faces would have resolution² vertices each. Welded, the edges and
+    // corners they share are stored once, leaving only the copies along the date line
+    let separate_faces = 6 * resolution as usize * resolution as usize;
+    assert!(
+        vertices.len() < separate_faces,
+        "{} vertices, {} for separate faces",
+        vertices.len(),
+        separate_faces
+    );
+    // The cube's 8 corners, 12 edges with 2 vertices inside each and 6 faces with 4
+    assert_eq!(distinct_positions(&vertices).len(), 8 + 12 * 2 + 6 * 4);
+}
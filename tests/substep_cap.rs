@@ -0,0 +1,48 @@
+//! A frame that would need more steps than the cap allows takes exactly that many and
+//! drops the rest of its time, slowing the simulation down instead of falling behind.
+
+use nbodysim::stepper::Stepper;
+
+const MAX_SUBSTEPS: usize = 8;
+
+/// Runs a frame with `elapsed` seconds to cover in steps of `dt`, returning how many
+/// steps it took
+fn run_frame(stepper: &mut Stepper, elapsed: f64, dt: f64) -> usize {
+    stepper.begin_frame(elapsed);
+    let mut steps = 0;
+    while stepper.next_step(dt) {
+        steps += 1;
+    }
+    assert_eq!(steps, stepper.steps());
+    steps
+}
+
+#[test]
+fn huge_frame_is_capped_and_slows_down() {
+    let mut stepper = Stepper::new(MAX_SUBSTEPS);
+    // Would need a hundred million steps to catch up
+    assert_eq!(run_frame(&mut stepper, 100.0, 1e-6), MAX_SUBSTEPS);
+    assert!(stepper.slow_motion());
+    // The rest was dropped, so the next frame isn't even further behind
+    assert_eq!(stepper.accumulator, 0.0);
+    assert_eq!(run_frame(&mut stepper, 100.0, 1e-6), MAX_SUBSTEPS);
+}
+
+#[test]
+fn frames_within_the_cap_run_at_full_speed() {
+    let mut stepper = Stepper::new(MAX_SUBSTEPS);
+    assert_eq!(run_frame(&mut stepper, 0.05, 0.01), 5);
+    assert!(!stepper.slow_motion());
+    // Time short of a whole step waits for the next frame
+    assert_eq!(run_frame(&mut stepper, 0.015, 0.01), 1);
+    assert_eq!(run_frame(&mut stepper, 0.005, 0.01), 1);
+}
+
+#[test]
+fn catching_up_leaves_slow_motion() {
+    let mut stepper = Stepper::new(MAX_SUBSTEPS);
+    run_frame(&mut stepper, 1.0, 0.01);
+    assert!(stepper.slow_motion());
+    run_frame(&mut stepper, 0.02, 0.01);
+    assert!(!stepper.slow_motion());
+}
@@ -0,0 +1,114 @@
+//! Recording bodies' paths as a simulation steps, and writing them out as NPY or CSV.
+
+use cgmath::*;
+use nbodysim::scene::{BodyConfig, Preset, Scene};
+use nbodysim::simulation::{self, Simulation};
+use nbodysim::sphere::BodyType;
+use nbodysim::trajectory::{self, TrajectoryRecorder};
+use std::convert::TryInto;
+
+const DT: f64 = 0.01;
+
+#[test]
+fn recorder_samples_the_stepped_positions() {
+    let scene = Scene::preset(Preset::FigureEight);
+    let mut simulation = Simulation::new(&scene, DT);
+    let interval = 3;
+    simulation.trajectory = Some(TrajectoryRecorder::new(vec![0, 2], interval));
+
+    let steps: usize = 10;
+    let mut stepped = Vec::new();
+    for _ in 0..steps {
+        simulation.step(DT);
+        stepped.push((simulation.time, simulation.entities.clone()));
+    }
+
+    let recorder = simulation.trajectory.as_ref().unwrap();
+    // Steps 0, 3, 6 and 9
+    let expected_samples = steps.div_ceil(interval);
+    for body in [0, 2] {
+        let samples = recorder.trajectory(body).unwrap();
+        assert_eq!(samples.len(), expected_samples);
+        for (n, sample) in samples.iter().enumerate() {
+            let (time, entities) = &stepped[n * interval];
+            assert_eq!(sample.time, *time);
+            assert_eq!(sample.position, entities[body].position);
+            assert_eq!(sample.velocity, entities[body].velocity);
+        }
+    }
+    // Only the bodies asked for are followed
+    assert!(recorder.trajectory(1).is_none());
+}
+
+#[test]
+fn npy_holds_a_row_per_sample() {
+    let scene = Scene::preset(Preset::Binary);
+    let mut simulation = Simulation::new(&scene, DT);
+    let mut recorder = TrajectoryRecorder::new(vec![1], 1);
+    recorder.record_velocity = true;
+    simulation.trajectory = Some(recorder);
+    for _ in 0..4 {
+        simulation.step(DT);
+    }
+
+    let path = std::env::temp_dir().join(format!("nbodysim-trajectory-{}.npy", std::process::id()));
+    let recorder = simulation.trajectory.as_ref().unwrap();
+    recorder.save(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let data_start = 10 + header_length;
+    assert_eq!(data_start % 64, 0);
+    let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+    assert!(header.contains("'descr': '<f8'"), "{}", header);
+    assert!(header.contains("'shape': (4, 8)"), "{}", header);
+    assert!(header.ends_with('\n'));
+
+    let values = bytes[data_start..]
+        .chunks(8)
+        .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(values.len(), 4 * 8);
+    let last = &values[3 * 8..];
+    let body = &simulation.entities[1];
+    assert_eq!(last[0], simulation.time);
+    assert_eq!(last[1], 1.0);
+    assert_eq!(
+        &last[2..5],
+        &[body.position.x, body.position.y, body.position.z]
+    );
+    assert_eq!(
+        &last[5..],
+        &[body.velocity.x, body.velocity.y, body.velocity.z]
+    );
+}
+
+#[test]
+fn csv_has_a_row_per_body_per_snapshot() {
+    // A lone body feels no pull, so it coasts along x at a steady 1 unit a second
+    let scene = Scene {
+        bodies: vec![BodyConfig::new(
+            BodyType::Planet,
+            Vector3::zero(),
+            Vector3::unit_x(),
+        )],
+        ..Default::default()
+    };
+    let snapshots = simulation::run_headless(&scene, 2, 0.5, 1);
+
+    let path = std::env::temp_dir().join(format!("nbodysim-trajectory-{}.csv", std::process::id()));
+    trajectory::export_csv(&snapshots, &path).unwrap();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // The starting state, then one row after each step
+    let expected = "\
+time,body_id,x,y,z,vx,vy,vz
+0,0,0,0,0,1,0,0
+0.5,0,0.5,0,0,1,0,0
+1,0,1,0,0,1,0,0
+";
+    assert_eq!(csv, expected);
+}
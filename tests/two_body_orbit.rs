@@ -0,0 +1,218 @@
+//! A planet on a circular orbit around a star, checked against the analytic solution:
+//! the distance between them stays at the starting radius `a`, and each orbit takes
+//! `2π√(a³/GM)`. Run without a window through the headless Simulation.
+
+use cgmath::*;
+use nbodysim::integrator::Integrator;
+use nbodysim::physics;
+use nbodysim::scene::{BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+use nbodysim::units::UnitSystem;
+use std::f32::consts::TAU;
+
+const STAR_MASS: f32 = 1000.0;
+const PLANET_MASS: f32 = 1.0;
+const ORBIT_RADIUS: f32 = 5.0;
+const DT: f64 = 1.0 / 120.0;
+const ORBITS: f64 = 5.0;
+
+/// The star at the origin and the planet on a circular orbit around it, counterclockwise
+/// in the xz plane. The star gets the opposite momentum so the pair doesn't drift
+fn two_body_scene() -> Scene {
+    let g = UnitSystem::Natural.gravitational_constant();
+    // Both bodies circle their shared center of mass, so the orbit is set by their total mass
+    let speed = physics::circular_orbit_speed(STAR_MASS + PLANET_MASS, ORBIT_RADIUS, g);
+    let planet_velocity = Vector3::new(0.0, 0.0, -speed) * STAR_MASS / (STAR_MASS + PLANET_MASS);
+
+    let mut star = BodyConfig::new(BodyType::Star, Vector3::zero(), Vector3::zero());
+    star.mass = STAR_MASS;
+    star.velocity = -planet_velocity * PLANET_MASS / STAR_MASS;
+    let mut planet = BodyConfig::new(
+        BodyType::Planet,
+        Vector3::new(ORBIT_RADIUS, 0.0, 0.0),
+        planet_velocity,
+    );
+    planet.mass = PLANET_MASS;
+
+    Scene {
+        bodies: vec![star, planet],
+        units: UnitSystem::Natural,
+        // Softening would weaken the pull and stretch the period
+        softening: Some(0.0),
+        ..Default::default()
+    }
+}
+
+/// How an integrator did over the run
+struct OrbitError {
+    /// The furthest the separation got from ORBIT_RADIUS, as a fraction of it
+    radius: f32,
+    /// How long the first orbit took, if one was completed
+    period: Option<f64>,
+}
+
+/// Steps the two-body scene for ORBITS orbits with `integrator`
+fn simulate(integrator: Integrator) -> OrbitError {
+    let scene = two_body_scene();
+    let mut simulation = Simulation::new(&scene, DT);
+    simulation.integrator = integrator;
+
+    let steps = (ORBITS * expected_period() / DT).ceil() as usize;
+
+    let mut radius_error: f32 = 0.0;
+    let mut period = None;
+    // The angle swept so far, unwrapped so it keeps growing past a full turn
+    let mut swept = 0.0;
+    let separation = |simulation: &Simulation| {
+        (simulation.entities[1].position - simulation.entities[0].position).map(|x| x as f32)
+    };
+    let mut previous = separation(&simulation);
+    for _ in 0..steps {
+        let merged = simulation.step(DT);
+        assert!(merged.is_empty(), "the bodies collided");
+
+        let current = separation(&simulation);
+        radius_error = radius_error.max((current.magnitude() - ORBIT_RADIUS).abs() / ORBIT_RADIUS);
+
+        // The angle turned this step, positive counterclockwise from above (+x towards -z)
+        let step_angle = (previous.z * current.x - previous.x * current.z)
+            .atan2(previous.x * current.x + previous.z * current.z);
+        if period.is_none() && swept + step_angle >= TAU {
+            // Interpolating within the step for when the turn was completed
+            let fraction = (TAU - swept) / step_angle;
+            period = Some(simulation.time - DT + f64::from(fraction) * DT);
+        }
+        swept += step_angle;
+        previous = current;
+    }
+
+    OrbitError {
+        radius: radius_error,
+        period,
+    }
+}
+
+/// 2π√(a³/GM), with M the total mass since both bodies circle their center of mass
+fn expected_period() -> f64 {
+    let g = UnitSystem::Natural.gravitational_constant();
+    f64::from(physics::orbital_period(
+        STAR_MASS + PLANET_MASS,
+        ORBIT_RADIUS,
+        g,
+    ))
+}
+
+/// Checks that `integrator` keeps the planet on its circle and gets the period right
+fn assert_holds_orbit(integrator: Integrator) {
+    let error = simulate(integrator);
+    assert!(error.radius < 1e-3, "radius drifted by {}", error.radius);
+    let period = error.period.expect("never completed an orbit");
+    let expected = expected_period();
+    assert!(
+        (period - expected).abs() / expected < 1e-3,
+        "period was {}, expected {}",
+        period,
+        expected
+    );
+}
+
+#[test]
+fn velocity_verlet_holds_a_circular_orbit() {
+    assert_holds_orbit(Integrator::VelocityVerlet);
+}
+
+#[test]
+fn leapfrog_holds_a_circular_orbit() {
+    assert_holds_orbit(Integrator::Leapfrog);
+}
+
+#[test]
+fn runge_kutta_holds_a_circular_orbit() {
+    assert_holds_orbit(Integrator::RungeKutta4);
+}
+
+/// The furthest the total energy got from where it started over ORBITS orbits with
+/// `integrator`, as a fraction of it
+fn energy_drift(integrator: Integrator) -> f64 {
+    let mut simulation = Simulation::new(&two_body_scene(), DT);
+    simulation.integrator = integrator;
+    let steps = (ORBITS * expected_period() / DT).ceil() as usize;
+    let start = simulation.total_energy();
+    let mut drift: f64 = 0.0;
+    for _ in 0..steps {
+        simulation.step(DT);
+        drift = drift.max(((simulation.total_energy() - start) / start).abs());
+    }
+    drift
+}
+
+/// On a circular orbit Verlet's energy error mostly cancels out from one step to the next,
+/// so it's only around ten times RK4's at this step, but that gap grows quickly as the
+/// step shrinks. RK4 losing to Verlet means it's lost an order somewhere
+#[test]
+fn runge_kutta_drifts_less_than_verlet() {
+    let verlet = energy_drift(Integrator::VelocityVerlet);
+    let runge_kutta = energy_drift(Integrator::RungeKutta4);
+    assert!(
+        runge_kutta * 5.0 < verlet,
+        "RK4 drifted by {}, Verlet by {}",
+        runge_kutta,
+        verlet
+    );
+}
+
+/// Plain Euler gains energy every step, so the orbit spirals outwards. At 120 steps a
+/// second the planet ends up around 80% further out after five orbits, where velocity
+/// Verlet stays within a few hundredths of a percent. Euler suddenly doing as well as the
+/// others probably means it's no longer Euler.
+#[test]
+fn euler_spirals_outwards() {
+    let error = simulate(Integrator::Euler);
+    assert!(
+        error.radius > 0.1 && error.radius.is_finite(),
+        "radius drifted by {}",
+        error.radius
+    );
+}
+
+/// The furthest the separation got from ORBIT_RADIUS over `steps` steps with `integrator`,
+/// as a fraction of it
+fn radius_drift(integrator: Integrator, steps: usize) -> f32 {
+    let mut simulation = Simulation::new(&two_body_scene(), DT);
+    simulation.integrator = integrator;
+    let mut drift: f32 = 0.0;
+    for _ in 0..steps {
+        simulation.step(DT);
+        let separation = simulation.entities[1].position - simulation.entities[0].position;
+        drift = drift.max((separation.magnitude() as f32 - ORBIT_RADIUS).abs() / ORBIT_RADIUS);
+    }
+    drift
+}
+
+/// Over 1000 steps, a little under four orbits, the symplectic integrators keep the radius
+/// within a few percent and Euler doesn't
+#[test]
+fn only_euler_drifts_over_a_thousand_steps() {
+    for &integrator in &[Integrator::VelocityVerlet, Integrator::Leapfrog] {
+        let drift = radius_drift(integrator, 1000);
+        assert!(drift < 0.02, "{:?} drifted by {}", integrator, drift);
+    }
+    let drift = radius_drift(Integrator::Euler, 1000);
+    assert!(drift > 0.02, "Euler only drifted by {}", drift);
+}
+
+/// Verlet's energy error stays bounded, around 1e-7 here, while Euler's grows every step
+/// to tens of percent. Even a thousandfold gap is a generous margin
+#[test]
+fn verlet_conserves_energy_far_better_than_euler() {
+    let verlet = energy_drift(Integrator::VelocityVerlet);
+    let euler = energy_drift(Integrator::Euler);
+    assert!(verlet < 1e-5, "Verlet drifted by {}", verlet);
+    assert!(
+        verlet * 1000.0 < euler,
+        "Verlet drifted by {}, Euler by {}",
+        verlet,
+        euler
+    );
+}
@@ -0,0 +1,44 @@
+//! The unit system presets give G the value the familiar orbits need.
+
+use cgmath::*;
+use nbodysim::physics;
+use nbodysim::scene::{self, BodyConfig, Scene};
+use nbodysim::simulation::Simulation;
+use nbodysim::sphere::BodyType;
+use nbodysim::units::UnitSystem;
+
+#[test]
+fn earth_takes_a_year_to_orbit_the_sun() {
+    let units = UnitSystem::Astronomical;
+    let g = units.gravitational_constant();
+    assert!((g - 4.0 * std::f32::consts::PI.powi(2)).abs() < 1e-2 * g);
+    let period = physics::orbital_period(1.0, 1.0, g);
+    assert!((period - 1.0).abs() < 1e-3, "period {} years", period);
+
+    // Stepping Earth round for a year brings it back where it started
+    let mut sun = BodyConfig::new(BodyType::Star, Vector3::zero(), Vector3::zero());
+    sun.mass = 1.0;
+    let position = Vector3::new(1.0, 0.0, 0.0);
+    let velocity = scene::circular_orbit(sun.mass, position, g);
+    let mut earth = BodyConfig::new(BodyType::Planet, position, velocity);
+    earth.mass = 3.0e-6;
+    earth.radius = 1e-3;
+    sun.radius = 1e-2;
+    let scene = Scene {
+        bodies: vec![sun, earth],
+        units,
+        softening: Some(0.0),
+        ..Default::default()
+    };
+    let steps = 10_000;
+    let mut simulation = Simulation::new(&scene, f64::from(period) / steps as f64);
+    for _ in 0..steps {
+        simulation.step(simulation.dt);
+    }
+    let offset = simulation.entities[1].position - simulation.entities[0].position;
+    assert!(
+        (offset - position.map(f64::from)).magnitude() < 1e-2,
+        "ended at {:?}",
+        offset
+    );
+}